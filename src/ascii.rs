@@ -0,0 +1,298 @@
+use crate::def::*;
+use crate::grid::*;
+#[cfg(test)]
+use crate::state::*;
+use std::fmt::Write;
+#[cfg(test)]
+use std::io::IsTerminal;
+
+// 'O': 初期点, 'o': 追加した点, '.': 空き, '#': 置けないマス
+// 強調する位置は、Asciiでは点なら'@'、空きなら'*'、Ansiでは赤で表示する
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextStyle {
+    Ascii,
+    Ansi,
+}
+
+impl TextStyle {
+    // 端末に出すときだけ色を付ける
+    #[cfg(test)]
+    pub fn for_stderr() -> TextStyle {
+        if std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal() {
+            TextStyle::Ansi
+        } else {
+            TextStyle::Ascii
+        }
+    }
+}
+
+const LABEL_WIDTH: usize = 3;
+
+struct Cell {
+    c: char,
+    highlighted: bool,
+}
+
+// 点(x, y)を(2x, 2(h-1-y))に置き、その間に辺を描く
+pub fn render_grid(grid: &Grid, highlights: &[Pos], style: TextStyle) -> String {
+    let canvas_width = 2 * grid.width - 1;
+    let canvas_height = 2 * grid.height - 1;
+    let mut canvas: Vec<Vec<Cell>> = (0..canvas_height)
+        .map(|_| {
+            (0..canvas_width)
+                .map(|_| Cell {
+                    c: ' ',
+                    highlighted: false,
+                })
+                .collect()
+        })
+        .collect();
+    let col = |x: i32| 2 * x as usize;
+    let row = |y: i32| 2 * (grid.height - 1 - y as usize);
+
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let pos = Pos { x, y };
+            canvas[row(y)][col(x)].c = if grid.is_blocked(&pos) {
+                '#'
+            } else {
+                match &grid.points[y as usize][x as usize] {
+                    Some(point) if point.added_info.is_none() => 'O',
+                    Some(_) => 'o',
+                    None => '.',
+                }
+            };
+
+            // 辺は左側または下側の端点から描く
+            let edges = [
+                (Dir::Right, 0, 1, '-'),
+                (Dir::Up, -1, 0, '|'),
+                (Dir::UpRight, -1, 1, '/'),
+                (Dir::UpLeft, -1, -1, '\\'),
+            ];
+            for (dir, dr, dc, c) in edges {
+                let next = &pos + &dir.to_pos();
+                if !grid.is_inside(&next) || !grid.has_edge(&pos, &dir) {
+                    continue;
+                }
+                let cell =
+                    &mut canvas[(row(y) as i32 + dr) as usize][(col(x) as i32 + dc) as usize];
+                cell.c = match (cell.c, c) {
+                    ('/', '\\') | ('\\', '/') => 'X',
+                    _ => c,
+                };
+            }
+        }
+    }
+
+    for pos in highlights {
+        if grid.is_inside(pos) {
+            let cell = &mut canvas[row(pos.y)][col(pos.x)];
+            cell.highlighted = true;
+            if style == TextStyle::Ascii {
+                cell.c = if cell.c == 'O' || cell.c == 'o' {
+                    '@'
+                } else {
+                    '*'
+                };
+            }
+        }
+    }
+
+    let mut text = String::new();
+    for (r, line) in canvas.iter().enumerate() {
+        if r % 2 == 0 {
+            write!(text, "{:>2} ", grid.height - 1 - r / 2).unwrap();
+        } else {
+            text.push_str(&" ".repeat(LABEL_WIDTH));
+        }
+        for cell in line {
+            if cell.highlighted && style == TextStyle::Ansi {
+                write!(text, "\x1b[1;31m{}\x1b[0m", cell.c).unwrap();
+            } else {
+                text.push(cell.c);
+            }
+        }
+        text.push('\n');
+    }
+    text.push_str(&" ".repeat(LABEL_WIDTH));
+    for x in 0..grid.width {
+        text.push(char::from_digit((x % 10) as u32, 10).unwrap());
+        if x + 1 < grid.width {
+            text.push(' ');
+        }
+    }
+    text.push('\n');
+    text
+}
+
+// 点、辺、置けないマスのいずれかが異なる位置
+#[cfg(test)]
+pub fn diff_positions(a: &Grid, b: &Grid) -> Vec<Pos> {
+    let mut positions = vec![];
+    for y in 0..usize::min(a.height, b.height) {
+        for x in 0..usize::min(a.width, b.width) {
+            if a.points[y][x] != b.points[y][x]
+                || a.edges[y][x] != b.edges[y][x]
+                || a.blocked[y][x] != b.blocked[y][x]
+            {
+                positions.push(Pos {
+                    x: x as i32,
+                    y: y as i32,
+                });
+            }
+        }
+    }
+    positions
+}
+
+// 二つの描画結果を左右に並べる
+#[cfg(test)]
+pub fn side_by_side(left: &Grid, right: &Grid, highlights: &[Pos], style: TextStyle) -> String {
+    let left_width = LABEL_WIDTH + 2 * left.width - 1;
+    let left_text = render_grid(left, highlights, style);
+    let right_text = render_grid(right, highlights, style);
+    let left_lines: Vec<&str> = left_text.lines().collect();
+    let right_lines: Vec<&str> = right_text.lines().collect();
+    let blank = " ".repeat(left_width);
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{:<width$}  |  expected",
+        "actual",
+        width = left_width
+    )
+    .unwrap();
+    for i in 0..usize::max(left_lines.len(), right_lines.len()) {
+        let l = left_lines.get(i).copied().unwrap_or(&blank);
+        let r = right_lines.get(i).copied().unwrap_or("");
+        // 各行の見た目の幅はどれもleft_widthになる
+        writeln!(text, "{}  |  {}", l, r).unwrap();
+    }
+    text
+}
+
+// 二つの状態の違いを説明する。同じならNone
+#[cfg(test)]
+pub fn describe_state_diff(actual: &State, expected: &State, style: TextStyle) -> Option<String> {
+    if actual == expected {
+        return None;
+    }
+    let mut text = String::from("states differ\n");
+    if actual.score != expected.score {
+        writeln!(
+            text,
+            "score: {} != {}",
+            actual.score.base, expected.score.base
+        )
+        .unwrap();
+    }
+    if actual.squares != expected.squares {
+        let describe = |squares: &[Square]| {
+            squares
+                .iter()
+                .map(|s| format!("#{}({}, {})", s.id, s.new_pos.x, s.new_pos.y))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        writeln!(text, "squares: [{}]", describe(&actual.squares)).unwrap();
+        writeln!(text, "   != [{}]", describe(&expected.squares)).unwrap();
+    }
+    if actual.grid.journal != expected.grid.journal {
+        writeln!(text, "journal differs (is a transaction still open?)").unwrap();
+    }
+    if actual.grid.width != expected.grid.width || actual.grid.height != expected.grid.height {
+        writeln!(
+            text,
+            "board size: {}x{} != {}x{}",
+            actual.grid.width, actual.grid.height, expected.grid.width, expected.grid.height
+        )
+        .unwrap();
+    }
+
+    let positions = diff_positions(&actual.grid, &expected.grid);
+    if !positions.is_empty() {
+        let list: Vec<String> = positions
+            .iter()
+            .map(|pos| format!("({}, {})", pos.x, pos.y))
+            .collect();
+        writeln!(text, "grid differs at {}", list.join(" ")).unwrap();
+        // 点の中身の違いは描画では分からないので、先頭の数個だけ書き出す
+        for pos in positions.iter().take(3) {
+            let (y, x) = (pos.y as usize, pos.x as usize);
+            if actual.grid.points[y][x] != expected.grid.points[y][x] {
+                writeln!(
+                    text,
+                    "  actual   ({}, {}): {:?}",
+                    x, y, actual.grid.points[y][x]
+                )
+                .unwrap();
+                writeln!(
+                    text,
+                    "  expected ({}, {}): {:?}",
+                    x, y, expected.grid.points[y][x]
+                )
+                .unwrap();
+            }
+        }
+    }
+    text.push_str(&side_by_side(
+        &actual.grid,
+        &expected.grid,
+        &positions,
+        style,
+    ));
+    Some(text)
+}
+
+// assert_eq!と違い、Gridを全部Debug出力する代わりに違う位置を図示する
+#[cfg(test)]
+#[track_caller]
+pub fn assert_state_eq(actual: &State, expected: &State) {
+    if let Some(diff) = describe_state_diff(actual, expected, TextStyle::for_stderr()) {
+        panic!("{}", diff);
+    }
+}
+
+#[test]
+fn test_render_grid() {
+    let diagonal = Pos { x: 1, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 0, y: 0 }, Pos { x: 1, y: 1 }];
+    let p = vec![diagonal, connect[0], connect[1]];
+    let mut state = State::new(3, p);
+    let copied_state = state.clone();
+    let square = Square::new(Pos { x: 0, y: 1 }, diagonal, connect);
+    state.perform_add(&square, false);
+
+    let expected = [
+        " 2 . . .",
+        "          ",
+        " 1 o-O .",
+        "   | |  ",
+        " 0 O-O .",
+        "   0 1 2",
+    ];
+    let text = render_grid(&state.grid, &[], TextStyle::Ascii);
+    let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).collect();
+    let expected: Vec<&str> = expected.iter().map(|line| line.trim_end()).collect();
+    assert_eq!(lines, expected);
+
+    let text = render_grid(
+        &state.grid,
+        &[Pos { x: 0, y: 1 }, Pos { x: 2, y: 2 }],
+        TextStyle::Ascii,
+    );
+    assert!(text.starts_with(" 2 . . *\n"));
+    assert!(text.contains(" 1 @-O .\n"));
+    let text = render_grid(&state.grid, &[Pos { x: 0, y: 1 }], TextStyle::Ansi);
+    assert!(text.contains(" 1 \x1b[1;31mo\x1b[0m-O .\n"));
+
+    assert_eq!(
+        describe_state_diff(&copied_state, &copied_state.clone(), TextStyle::Ascii),
+        None
+    );
+    let diff = describe_state_diff(&state, &copied_state, TextStyle::Ascii).unwrap();
+    assert!(diff.contains("grid differs at (0, 0) (1, 0) (0, 1) (1, 1)"));
+    assert!(diff.contains(" 1 @-@ .  |   1 * @ ."));
+}
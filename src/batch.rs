@@ -0,0 +1,306 @@
+use crate::config::*;
+use crate::framework::*;
+use crate::grid::*;
+use crate::input::*;
+use crate::scorer;
+use crate::solver::*;
+use crate::util::*;
+use crate::weight::*;
+use std::fmt::Write;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// 1ケース分の結果
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaseResult {
+    pub seed: u64,
+    pub n: usize,
+    pub m: usize,
+    pub score: i32,
+    pub loop_count: usize,
+    pub elapsed_seconds: f64,
+}
+
+// ケースごとの入力と、解いた出力
+pub struct Case {
+    pub seed: u64,
+    pub input: String,
+}
+
+pub struct CaseOutput {
+    pub result: CaseResult,
+    pub output: String,
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|n| usize::max(1, n.get().saturating_sub(2)))
+        .unwrap_or(1)
+}
+
+// 入力を解いて、このクレートの採点器で採点する。不正な出力は0点
+pub fn solve_case(case: &Case, config: &SolverConfig) -> Result<CaseOutput, InputError> {
+    let input = Input::parse(&case.input)?;
+    time::restart_clock();
+    let mut solver = Solver::from_input(
+        &input,
+        Grid::new(input.n),
+        WeightMap::center_distance(input.n, input.n),
+        config.clone(),
+    );
+    solver.solve(config.time_limit);
+    let elapsed_seconds = time::elapsed_seconds();
+    let output = solver.output();
+    let score = scorer::compute_score(&case.input, &output).unwrap_or(0);
+    Ok(CaseOutput {
+        result: CaseResult {
+            seed: case.seed,
+            n: input.n,
+            m: input.m(),
+            score,
+            loop_count: solver.loop_count,
+            elapsed_seconds,
+        },
+        output,
+    })
+}
+
+// 別にビルドしたバイナリに標準入出力で解かせて、このクレートの採点器で採点する
+pub fn solve_case_with_binary(
+    case: &Case,
+    program: &str,
+    args: &[String],
+) -> Result<CaseOutput, String> {
+    let input = Input::parse(&case.input).map_err(|e| format!("input {}", e))?;
+    let start = std::time::Instant::now();
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(case.input.as_bytes())
+        .map_err(|e| format!("{}: {}", program, e))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    let elapsed_seconds = start.elapsed().as_secs_f64();
+    let output = String::from_utf8_lossy(&output.stdout).to_string();
+    let score = scorer::compute_score(&case.input, &output).unwrap_or(0);
+    Ok(CaseOutput {
+        result: CaseResult {
+            seed: case.seed,
+            n: input.n,
+            m: input.m(),
+            score,
+            loop_count: 0,
+            elapsed_seconds,
+        },
+        output,
+    })
+}
+
+// スレッドプールでケースを解く。終わった順にon_doneを呼び、結果はケースの順で返す
+pub fn run_cases<E: Send>(
+    cases: &[Case],
+    threads: usize,
+    solve: impl Fn(&Case) -> Result<CaseOutput, E> + Sync,
+    mut on_done: impl FnMut(&Case, &Result<CaseOutput, E>),
+) -> Vec<Result<CaseOutput, E>> {
+    let next_index = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut outputs: Vec<Option<Result<CaseOutput, E>>> = (0..cases.len()).map(|_| None).collect();
+    thread::scope(|scope| {
+        for _ in 0..usize::max(1, threads) {
+            let sender = sender.clone();
+            let (next_index, solve) = (&next_index, &solve);
+            scope.spawn(move || loop {
+                let i = next_index.fetch_add(1, Ordering::SeqCst);
+                if i >= cases.len() || sender.send((i, solve(&cases[i]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (i, output) in receiver {
+            on_done(&cases[i], &output);
+            outputs[i] = Some(output);
+        }
+    });
+    outputs
+        .into_iter()
+        .map(|output| output.expect("case was not solved"))
+        .collect()
+}
+
+pub fn to_csv(results: &[CaseResult]) -> String {
+    let mut csv = String::from("seed,n,m,score,loop_count,elapsed_seconds\n");
+    for r in results {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{:.3}",
+            r.seed, r.n, r.m, r.score, r.loop_count, r.elapsed_seconds
+        )
+        .unwrap();
+    }
+    csv
+}
+
+const HISTOGRAM_BASE: i32 = 500000;
+const HISTOGRAM_STEP: i32 = 50000;
+const HISTOGRAM_BINS: usize = 30;
+
+const BUCKET_ROWS: usize = 6;
+const BUCKET_COLS: usize = 10;
+
+// Nは31~35, 36~40, ...、Mは30~59, 60~89, ...で区切る
+pub fn bucket_index(n: usize, m: usize) -> (usize, usize) {
+    (
+        usize::min(BUCKET_ROWS - 1, n.saturating_sub(31) / 5),
+        usize::min(BUCKET_COLS - 1, m.saturating_sub(30) / 30),
+    )
+}
+
+// (N, M, 値)をN×Mで区切って平均を表にする
+pub fn render_bucket_table(
+    values: &[(usize, usize, f64)],
+    format: impl Fn(f64) -> String,
+) -> String {
+    let mut sums = [[0.; BUCKET_COLS]; BUCKET_ROWS];
+    let mut counts = [[0; BUCKET_COLS]; BUCKET_ROWS];
+    for &(n, m, v) in values {
+        let (i, j) = bucket_index(n, m);
+        sums[i][j] += v;
+        counts[i][j] += 1;
+    }
+    let mut s = String::new();
+    write!(s, "      |").unwrap();
+    for j in 0..BUCKET_COLS {
+        write!(s, " {:>7}", format!("{}~{}", j * 30 + 30, j * 30 + 59)).unwrap();
+    }
+    writeln!(s).unwrap();
+    writeln!(s, "{}", "-".repeat(BUCKET_COLS * 8 + 7)).unwrap();
+    for i in 0..BUCKET_ROWS {
+        write!(s, "{}~{} |", i * 5 + 31, i * 5 + 35).unwrap();
+        for j in 0..BUCKET_COLS {
+            if counts[i][j] != 0 {
+                write!(s, " {}", format(sums[i][j] / counts[i][j] as f64)).unwrap();
+            } else {
+                write!(s, "     nan").unwrap();
+            }
+        }
+        writeln!(s).unwrap();
+    }
+    s
+}
+
+// 上位・下位10件、スコアの分布、N×Mごとの平均スコア
+pub fn render_summary(results: &[CaseResult]) -> String {
+    let mut s = String::new();
+    if results.is_empty() {
+        return s;
+    }
+    let total: i64 = results.iter().map(|r| r.score as i64).sum();
+    let mut scores: Vec<(i32, u64)> = results.iter().map(|r| (r.score, r.seed)).collect();
+    scores.sort();
+
+    writeln!(s, "total: {}", total).unwrap();
+    for (score, seed) in scores.iter().rev().take(10) {
+        writeln!(s, "({}, '{:04}')", score, seed).unwrap();
+    }
+    for (score, seed) in scores.iter().take(10) {
+        writeln!(s, "({}, '{:04}')", score, seed).unwrap();
+    }
+    writeln!(s, "ave: {}", total as f64 / results.len() as f64).unwrap();
+
+    let mut counts = [0; HISTOGRAM_BINS];
+    for (score, _) in &scores {
+        let bin = (score - HISTOGRAM_BASE).max(0) / HISTOGRAM_STEP;
+        counts[usize::min(HISTOGRAM_BINS - 1, bin as usize)] += 1;
+    }
+    for (i, count) in counts.iter().enumerate() {
+        let low = HISTOGRAM_BASE + i as i32 * HISTOGRAM_STEP;
+        writeln!(
+            s,
+            "{:7} ~ {:7}: {}",
+            low,
+            low + HISTOGRAM_STEP - 1,
+            "o".repeat(count * 100 / results.len())
+        )
+        .unwrap();
+    }
+
+    let values: Vec<(usize, usize, f64)> =
+        results.iter().map(|r| (r.n, r.m, r.score as f64)).collect();
+    s += &render_bucket_table(&values, |v| format!("{:7}", v as i64));
+    s
+}
+
+#[test]
+fn test_render_summary() {
+    let results: Vec<CaseResult> = [
+        (0, 31, 30, 1_000_000),
+        (1, 35, 59, 600_000),
+        (2, 61, 300, 2_100_000),
+    ]
+    .iter()
+    .map(|&(seed, n, m, score)| CaseResult {
+        seed,
+        n,
+        m,
+        score,
+        loop_count: 0,
+        elapsed_seconds: 0.,
+    })
+    .collect();
+    assert_eq!(bucket_index(31, 30), (0, 0));
+    assert_eq!(bucket_index(61, 300), (5, 9));
+
+    let summary = render_summary(&results);
+    assert!(summary.starts_with("total: 3700000\n(2100000, '0002')\n"));
+    assert!(summary.contains("ave: 1233333.3333333333\n"));
+    assert!(summary.contains(" 600000 ~  649999: ooooooooooooooooooooooooooooooooo\n"));
+    assert!(summary.contains("31~35 |  800000     nan"));
+    assert!(summary.contains("56~60 |     nan"));
+    assert!(summary.ends_with(" 2100000\n"));
+
+    let csv = to_csv(&results);
+    assert_eq!(csv.lines().count(), 4);
+    assert_eq!(csv.lines().nth(1), Some("0,31,30,1000000,0,0.000"));
+}
+
+#[test]
+fn test_run_cases() {
+    let mut config = SolverConfig::new();
+    config.time_limit = 0.05;
+    let cases = vec![
+        Case {
+            seed: 0,
+            input: "5 4\n0 0\n2 0\n0 2\n2 4\n".to_string(),
+        },
+        Case {
+            seed: 1,
+            input: "5 1\n".to_string(),
+        },
+    ];
+    let mut done = 0;
+    let outputs = run_cases(
+        &cases,
+        2,
+        |case| solve_case(case, &config),
+        |_, _| done += 1,
+    );
+    assert_eq!(done, 2);
+    let output = outputs[0].as_ref().unwrap();
+    assert_eq!(output.result.seed, 0);
+    assert!(output.result.score >= 1_200_000);
+    assert!(matches!(outputs[1], Err(InputError::CountMismatch { .. })));
+}
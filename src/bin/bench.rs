@@ -0,0 +1,7 @@
+// よく呼ばれる処理の1回あたりの時間を、固定seedの盤面で測る
+//
+// usage: cargo run --release --bin bench -- [--sizes 31,45,61] [--seed 0] [--min-time 0.3] [--filter name]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    ahc014::run_microbench(&args);
+}
@@ -0,0 +1,820 @@
+// 入口のファイルから辿れるモジュールを1ファイルにまとめて、提出用のsubmit.rsを作る
+// ライブラリのモジュールは、入口から参照されているものだけを埋め込む
+//
+// usage: cargo run --bin bundle -- [--src dir] [--main src/bin/solve.rs] [--out submit.rs]
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CRATE_NAME: &str = "ahc014";
+
+const USAGE: &str = "usage: bundle [--src <dir>] [--main <path>] [--out <path>]
+
+Writes <main> (default <src>/bin/solve.rs) and the library modules it reaches
+as a single file to <out>, or to stdout if omitted.
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Ident,
+    Punct,
+    Literal,
+    Lifetime,
+    Comment,
+    Whitespace,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    // バイト位置
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+
+    fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Comment | TokenKind::Whitespace)
+    }
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    chars: Vec<(usize, char)>,
+    i: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self, k: usize) -> Option<char> {
+        self.chars.get(self.i + k).map(|&(_, c)| c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.get(self.i).map_or(self.src.len(), |&(p, _)| p)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.src[..self.offset()].matches('\n').count() + 1;
+        format!("line {}: {}", line, message)
+    }
+
+    // 開きの"の次から、エスケープを考慮して閉じの"まで
+    fn quoted(&mut self, quote: char) -> Result<(), String> {
+        while let Some(c) = self.peek(0) {
+            self.i += 1;
+            if c == '\\' {
+                self.i += 1;
+            } else if c == quote {
+                return Ok(());
+            }
+        }
+        Err(self.error("unterminated literal"))
+    }
+
+    // r#"..."#の、rの次から
+    fn raw_string(&mut self) -> Result<(), String> {
+        let mut hashes = 0;
+        while self.peek(0) == Some('#') {
+            hashes += 1;
+            self.i += 1;
+        }
+        if self.peek(0) != Some('"') {
+            return Err(self.error("malformed raw string"));
+        }
+        self.i += 1;
+        while let Some(c) = self.peek(0) {
+            self.i += 1;
+            if c == '"' && (0..hashes).all(|k| self.peek(k) == Some('#')) {
+                self.i += hashes;
+                return Ok(());
+            }
+        }
+        Err(self.error("unterminated raw string"))
+    }
+
+    fn next_kind(&mut self) -> Result<TokenKind, String> {
+        let c = self.peek(0).unwrap();
+        let next = self.peek(1);
+        if c.is_whitespace() {
+            while self.peek(0).is_some_and(|c| c.is_whitespace()) {
+                self.i += 1;
+            }
+            return Ok(TokenKind::Whitespace);
+        }
+        if c == '/' && next == Some('/') {
+            while self.peek(0).is_some_and(|c| c != '\n') {
+                self.i += 1;
+            }
+            return Ok(TokenKind::Comment);
+        }
+        if c == '/' && next == Some('*') {
+            // ブロックコメントは入れ子にできる
+            let mut depth = 0;
+            loop {
+                match (self.peek(0), self.peek(1)) {
+                    (Some('/'), Some('*')) => {
+                        depth += 1;
+                        self.i += 2;
+                    }
+                    (Some('*'), Some('/')) => {
+                        depth -= 1;
+                        self.i += 2;
+                        if depth == 0 {
+                            return Ok(TokenKind::Comment);
+                        }
+                    }
+                    (Some(_), _) => self.i += 1,
+                    (None, _) => return Err(self.error("unterminated block comment")),
+                }
+            }
+        }
+        // r"..", r#".."#, b"..", br"..", b'.'
+        let (prefix, after) = match (c, next, self.peek(2)) {
+            ('b', Some('r'), Some('"' | '#')) => (2, 'r'),
+            ('r', Some('"'), _) => (1, 'r'),
+            ('r', Some('#'), Some('"' | '#')) => (1, 'r'),
+            ('b', Some(q @ ('"' | '\'')), _) => (1, q),
+            _ => (0, ' '),
+        };
+        if prefix > 0 {
+            self.i += prefix;
+            if after == 'r' {
+                self.raw_string()?;
+            } else {
+                self.i += 1;
+                self.quoted(after)?;
+            }
+            return Ok(TokenKind::Literal);
+        }
+        if c == '"' {
+            self.i += 1;
+            self.quoted('"')?;
+            return Ok(TokenKind::Literal);
+        }
+        if c == '\'' {
+            // 'a'や'\n'は文字、'aや'staticはライフタイム
+            if next == Some('\\') || self.peek(2) == Some('\'') {
+                self.i += 1;
+                self.quoted('\'')?;
+                return Ok(TokenKind::Literal);
+            }
+            self.i += 1;
+            while self
+                .peek(0)
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            {
+                self.i += 1;
+            }
+            return Ok(TokenKind::Lifetime);
+        }
+        if c.is_ascii_digit() {
+            let is_hex = self.src[self.offset()..].starts_with("0x");
+            while let Some(c) = self.peek(0) {
+                let exponent_sign = matches!(c, '+' | '-')
+                    && matches!(self.chars[self.i - 1].1, 'e' | 'E')
+                    && !is_hex;
+                if c.is_alphanumeric()
+                    || c == '_'
+                    || (c == '.' && self.peek(1).is_some_and(|c| c.is_ascii_digit()))
+                    || exponent_sign
+                {
+                    self.i += 1;
+                } else {
+                    break;
+                }
+            }
+            return Ok(TokenKind::Literal);
+        }
+        if c.is_alphabetic() || c == '_' {
+            // r#identも識別子
+            if c == 'r' && next == Some('#') {
+                self.i += 2;
+            }
+            while self
+                .peek(0)
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            {
+                self.i += 1;
+            }
+            return Ok(TokenKind::Ident);
+        }
+        self.i += 1;
+        Ok(TokenKind::Punct)
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut lexer = Lexer {
+        src,
+        chars: src.char_indices().collect(),
+        i: 0,
+    };
+    let mut tokens = vec![];
+    while lexer.i < lexer.chars.len() {
+        let start = lexer.offset();
+        let kind = lexer.next_kind()?;
+        tokens.push(Token {
+            kind,
+            start,
+            end: lexer.offset(),
+        });
+    }
+    Ok(tokens)
+}
+
+// コメントと空白を除いたトークン列
+struct Tokens<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(src: &'a str) -> Result<Tokens<'a>, String> {
+        let tokens = tokenize(src)?
+            .into_iter()
+            .filter(|token| !token.is_trivia())
+            .collect();
+        Ok(Tokens { src, tokens })
+    }
+
+    fn text(&self, i: usize) -> &'a str {
+        self.tokens.get(i).map_or("", |token| token.text(self.src))
+    }
+
+    fn is(&self, i: usize, text: &str) -> bool {
+        self.text(i) == text
+    }
+
+    fn is_ident(&self, i: usize) -> bool {
+        self.tokens
+            .get(i)
+            .is_some_and(|token| token.kind == TokenKind::Ident)
+    }
+
+    fn is_path_separator(&self, i: usize) -> bool {
+        self.is(i, ":") && self.is(i + 1, ":") && self.tokens[i].end == self.tokens[i + 1].start
+    }
+
+    // 対応する閉じ括弧の位置
+    fn matching(&self, open: usize) -> Result<usize, String> {
+        let mut depth = 0;
+        for i in open..self.tokens.len() {
+            match self.text(i) {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(format!(
+            "unbalanced bracket at byte {}",
+            self.tokens[open].start
+        ))
+    }
+
+    // #[...]の中身がtestかcfg(test)か
+    fn is_test_attribute(&self, open: usize, close: usize) -> bool {
+        let inner: Vec<&str> = (open + 1..close).map(|i| self.text(i)).collect();
+        inner == ["test"] || inner == ["cfg", "(", "test", ")"]
+    }
+
+    // attrから始まるアイテムの最後のトークン。;か、最初の{}の閉じ
+    fn item_end(&self, start: usize) -> Result<usize, String> {
+        let mut i = start;
+        while i < self.tokens.len() {
+            match self.text(i) {
+                ";" => return Ok(i),
+                "{" => return self.matching(i),
+                "(" | "[" => i = self.matching(i)?,
+                _ => {}
+            }
+            i += 1;
+        }
+        Err(format!(
+            "item at byte {} does not end",
+            self.tokens[start].start
+        ))
+    }
+}
+
+// 範囲を取り除く。行に他に何もなければ行ごと取り除く
+fn remove_ranges(src: &str, ranges: &[(usize, usize)]) -> String {
+    let mut result = String::new();
+    let mut last = 0;
+    for &(start, end) in ranges {
+        let line_start = src[..start].rfind('\n').map_or(0, |p| p + 1);
+        let start = if src[line_start..start].trim().is_empty() {
+            line_start
+        } else {
+            start
+        };
+        let line_end = src[end..].find('\n').map_or(src.len(), |p| end + p + 1);
+        let end = if src[end..line_end].trim().is_empty() {
+            line_end
+        } else {
+            end
+        };
+        if start < last {
+            continue;
+        }
+        result += &src[last..start];
+        last = end;
+    }
+    result += &src[last..];
+    result
+}
+
+// #[test]と#[cfg(test)]のついたアイテムを取り除く
+fn strip_tests(src: &str) -> Result<String, String> {
+    let tokens = Tokens::new(src)?;
+    let mut ranges = vec![];
+    let mut i = 0;
+    while i < tokens.tokens.len() {
+        if !(tokens.is(i, "#") && tokens.is(i + 1, "[")) {
+            i += 1;
+            continue;
+        }
+        // 続けて書かれた属性をまとめて見る
+        let start = i;
+        let mut is_test = false;
+        while tokens.is(i, "#") && tokens.is(i + 1, "[") {
+            let close = tokens.matching(i + 1)?;
+            is_test |= tokens.is_test_attribute(i + 1, close);
+            i = close + 1;
+        }
+        if is_test {
+            let end = tokens.item_end(i)?;
+            ranges.push((tokens.tokens[start].start, tokens.tokens[end].end));
+            i = end + 1;
+        }
+    }
+    Ok(remove_ranges(src, &ranges))
+}
+
+// 文字列やコメントの途中から始まる行以外を字下げする
+fn indent(src: &str) -> Result<String, String> {
+    let multiline: Vec<(usize, usize)> = tokenize(src)?
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Whitespace)
+        .map(|token| (token.start, token.end))
+        .collect();
+    let mut result = String::new();
+    let mut offset = 0;
+    for line in src.split_inclusive('\n') {
+        let inside = multiline
+            .iter()
+            .any(|&(start, end)| start < offset && offset < end);
+        if !inside && !line.trim().is_empty() {
+            result += "    ";
+        }
+        result += line;
+        offset += line.len();
+    }
+    Ok(result)
+}
+
+struct Bundler {
+    src_dir: PathBuf,
+    main_path: PathBuf,
+}
+
+impl Bundler {
+    // ルートのファイル(main.rs, lib.rs)の子はsrc直下、それ以外はsrc/<親>/の下
+    fn child_path(&self, dir: &Path, name: &str) -> Result<PathBuf, String> {
+        let candidates = [
+            dir.join(format!("{}.rs", name)),
+            dir.join(name).join("mod.rs"),
+        ];
+        candidates
+            .iter()
+            .find(|path| path.exists())
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "unresolved module {:?}: none of {:?} exists",
+                    name, candidates
+                )
+            })
+    }
+
+    // ファイルを読んでテストを取り除く
+    fn read_file(path: &Path) -> Result<String, String> {
+        let src = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        strip_tests(&src).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // ファイルを読んでテストを取り除き、mod x;を中身に置き換える
+    fn expand_file(&self, path: &Path, child_dir: &Path) -> Result<String, String> {
+        let src = Bundler::read_file(path)?;
+        self.expand_src(&src, path, child_dir)
+    }
+
+    fn expand_src(&self, src: &str, path: &Path, child_dir: &Path) -> Result<String, String> {
+        let tokens = Tokens::new(src).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut result = String::new();
+        let mut last = 0;
+        let mut depth = 0;
+        for i in 0..tokens.tokens.len() {
+            match tokens.text(i) {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                "mod" if depth == 0 && tokens.is_ident(i + 1) && tokens.is(i + 2, ";") => {
+                    let name = tokens.text(i + 1);
+                    let child = self.child_path(child_dir, name)?;
+                    let body = self.expand_file(&child, &child_dir.join(name))?;
+                    result += &src[last..tokens.tokens[i].start];
+                    result += &format!("mod {} {{\n{}}}", name, indent(&body)?);
+                    last = tokens.tokens[i + 2].end;
+                }
+                _ => {}
+            }
+        }
+        result += &src[last..];
+        Ok(result)
+    }
+
+    // ルートのファイルの先頭の#![...]を分ける
+    fn split_inner_attributes(src: &str) -> Result<(String, String), String> {
+        let tokens = Tokens::new(src)?;
+        let mut i = 0;
+        while tokens.is(i, "#") && tokens.is(i + 1, "!") && tokens.is(i + 2, "[") {
+            i = tokens.matching(i + 2)? + 1;
+        }
+        if i == 0 {
+            return Ok((String::new(), src.to_string()));
+        }
+        let end = tokens.tokens[i - 1].end;
+        Ok((
+            src[..end].trim().to_string() + "\n",
+            src[end..].trim_start().to_string(),
+        ))
+    }
+
+    // lib.rsの直下のmod x;と、x::から始まるuseのうち、mainから辿れないものを取り除く
+    fn prune_lib(&self, lib: &str, main: &str) -> Result<String, String> {
+        let tokens = Tokens::new(lib)?;
+        // モジュール名と、pubを含めたmod x;の範囲
+        let mut items: Vec<(&str, (usize, usize))> = vec![];
+        // 再公開しているモジュール名と、その名前、pubを含めたuseの範囲
+        let mut reexports: Vec<(&str, Vec<&str>, (usize, usize))> = vec![];
+        let mut depth = 0;
+        for i in 0..tokens.tokens.len() {
+            let start = if i > 0 && tokens.is(i - 1, "pub") {
+                i - 1
+            } else {
+                i
+            };
+            match tokens.text(i) {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                "mod" if depth == 0 && tokens.is_ident(i + 1) && tokens.is(i + 2, ";") => {
+                    let range = (tokens.tokens[start].start, tokens.tokens[i + 2].end);
+                    items.push((tokens.text(i + 1), range));
+                }
+                "use"
+                    if depth == 0 && tokens.is_ident(i + 1) && tokens.is_path_separator(i + 2) =>
+                {
+                    let end = tokens.item_end(i)?;
+                    let names = (i + 4..end)
+                        .filter(|&j| tokens.is_ident(j))
+                        .map(|j| tokens.text(j))
+                        .collect();
+                    let range = (tokens.tokens[start].start, tokens.tokens[end].end);
+                    reexports.push((tokens.text(i + 1), names, range));
+                }
+                _ => {}
+            }
+        }
+        reexports.retain(|&(module, _, _)| items.iter().any(|&(item, _)| item == module));
+
+        let mut reached: HashSet<String> = HashSet::new();
+        // ahc014::xのxが再公開した名前なら、そのモジュールを辿る
+        let mut queue: Vec<String> = referenced_modules(main, CRATE_NAME)?
+            .into_iter()
+            .map(|name| {
+                reexports
+                    .iter()
+                    .find(|(_, names, _)| names.contains(&name.as_str()))
+                    .map_or(name, |&(module, _, _)| module.to_string())
+            })
+            .collect();
+        // lib.rsに直接書かれたコードからの参照も辿る
+        queue.extend(referenced_modules(lib, "crate")?);
+        while let Some(name) = queue.pop() {
+            if !items.iter().any(|&(item, _)| item == name) || !reached.insert(name.clone()) {
+                continue;
+            }
+            let child = self.child_path(&self.src_dir, &name)?;
+            let body = self.expand_file(&child, &self.src_dir.join(&name))?;
+            queue.extend(referenced_modules(&body, "crate")?);
+        }
+        let mut ranges: Vec<(usize, usize)> = items
+            .iter()
+            .filter(|&&(name, _)| !reached.contains(name))
+            .map(|&(_, range)| range)
+            .chain(
+                reexports
+                    .iter()
+                    .filter(|&&(module, _, _)| !reached.contains(module))
+                    .map(|&(_, _, range)| range),
+            )
+            .collect();
+        ranges.sort();
+        Ok(remove_ranges(lib, &ranges))
+    }
+
+    fn bundle(&self) -> Result<String, String> {
+        let main_dir = self.main_path.parent().unwrap_or(&self.src_dir);
+        let main = self.expand_file(&self.main_path, main_dir)?;
+        let (mut attributes, mut body) = Bundler::split_inner_attributes(&main)?;
+
+        // ライブラリはモジュールとして埋め込み、ライブラリ内のcrate::をcrate::ahc014::にする
+        let lib_path = self.src_dir.join("lib.rs");
+        if lib_path.exists() {
+            let lib = self.prune_lib(&Bundler::read_file(&lib_path)?, &main)?;
+            let lib = self.expand_src(&lib, &lib_path, &self.src_dir)?;
+            let (lib_attributes, lib) = Bundler::split_inner_attributes(&lib)?;
+            attributes += &lib_attributes;
+            let lib = rewrite_path_prefix(&lib, "crate", &format!("crate::{}", CRATE_NAME))?;
+            // 入口が使わない公開アイテムや再公開もあるので、未使用の警告は出さない
+            body += &format!(
+                "\n#[allow(dead_code, unused_imports)]\nmod {} {{\n{}}}\n",
+                CRATE_NAME,
+                indent(&lib)?
+            );
+        }
+        let body = rewrite_path_prefix(&body, CRATE_NAME, &format!("crate::{}", CRATE_NAME))?;
+
+        let bundle = if attributes.is_empty() {
+            body
+        } else {
+            attributes + "\n" + &body
+        };
+        check_modules(&bundle)?;
+        Ok(bundle)
+    }
+}
+
+// from::で始まるパスをto::に書き換える
+fn rewrite_path_prefix(src: &str, from: &str, to: &str) -> Result<String, String> {
+    let tokens = Tokens::new(src)?;
+    let mut result = String::new();
+    let mut last = 0;
+    for i in 0..tokens.tokens.len() {
+        let is_path_start = i < 2 || !tokens.is_path_separator(i - 2);
+        if tokens.is(i, from) && tokens.is_path_separator(i + 1) && is_path_start {
+            result += &src[last..tokens.tokens[i].start];
+            result += to;
+            last = tokens.tokens[i].end;
+        }
+    }
+    result += &src[last..];
+    Ok(result)
+}
+
+// from::xで始まるパスのx
+fn referenced_modules(src: &str, from: &str) -> Result<Vec<String>, String> {
+    let tokens = Tokens::new(src)?;
+    let mut modules = vec![];
+    for i in 0..tokens.tokens.len() {
+        let is_path_start = i < 2 || !tokens.is_path_separator(i - 2);
+        if tokens.is(i, from)
+            && tokens.is_path_separator(i + 1)
+            && is_path_start
+            && tokens.is_ident(i + 3)
+        {
+            modules.push(tokens.text(i + 3).to_string());
+        }
+    }
+    Ok(modules)
+}
+
+// まとめたファイルに、解決できないモジュールへの参照がないか確かめる
+// - mod x;が残っていない
+// - ライブラリのクレート名での参照が残っていない
+// - crate::a::b::の小文字のモジュールが全て存在する
+fn check_modules(bundle: &str) -> Result<(), String> {
+    let tokens = Tokens::new(bundle)?;
+    let line_of = |i: usize| bundle[..tokens.tokens[i].start].matches('\n').count() + 1;
+
+    // モジュールのパスから、その直下のモジュール名
+    let mut modules: HashMap<Vec<&str>, Vec<&str>> = HashMap::new();
+    modules.insert(vec![], vec![]);
+    let mut stack: Vec<(Vec<&str>, usize)> = vec![(vec![], 0)];
+    let mut depth = 0;
+    for i in 0..tokens.tokens.len() {
+        match tokens.text(i) {
+            "{" => depth += 1,
+            "}" => {
+                depth -= 1;
+                if stack.len() > 1 && stack.last().unwrap().1 == depth {
+                    stack.pop();
+                }
+            }
+            "mod" if tokens.is_ident(i + 1) => {
+                let name = tokens.text(i + 1);
+                if tokens.is(i + 2, ";") {
+                    return Err(format!("line {}: unresolved module {:?}", line_of(i), name));
+                }
+                let mut path = stack.last().unwrap().0.clone();
+                modules.get_mut(&path).unwrap().push(name);
+                path.push(name);
+                modules.insert(path.clone(), vec![]);
+                stack.push((path, depth));
+            }
+            _ => {}
+        }
+    }
+
+    for i in 0..tokens.tokens.len() {
+        if tokens.is(i, CRATE_NAME)
+            && tokens.is_path_separator(i + 1)
+            && !(i >= 3 && tokens.is(i - 3, "crate") && tokens.is_path_separator(i - 2))
+        {
+            return Err(format!(
+                "line {}: reference to the external crate {}",
+                line_of(i),
+                CRATE_NAME
+            ));
+        }
+        if !(tokens.is(i, "crate") && tokens.is_path_separator(i + 1)) {
+            continue;
+        }
+        let mut path: Vec<&str> = vec![];
+        let mut j = i + 3;
+        while tokens.is_ident(j) && tokens.is_path_separator(j + 1) {
+            let name = tokens.text(j);
+            if !name.starts_with(|c: char| c.is_lowercase()) {
+                break;
+            }
+            if !modules[&path].contains(&name) {
+                return Err(format!(
+                    "line {}: unresolved module crate::{}",
+                    line_of(j),
+                    path.iter()
+                        .chain(&[name])
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join("::")
+                ));
+            }
+            path.push(name);
+            j += 3;
+        }
+    }
+    Ok(())
+}
+
+// --src、--main、--outの値。知らない引数と値のないフラグはエラーにする
+fn parse_args(args: &[String]) -> Result<HashMap<&str, &str>, String> {
+    let mut values = HashMap::new();
+    let mut i = 0;
+    while i < args.len() {
+        let name = args[i].as_str();
+        if !matches!(name, "--src" | "--main" | "--out") {
+            return Err(format!("unknown argument {:?}", name));
+        }
+        let value = args
+            .get(i + 1)
+            .ok_or_else(|| format!("{} needs a value", name))?;
+        values.insert(name, value.as_str());
+        i += 2;
+    }
+    Ok(values)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    let values = parse_args(&args).unwrap_or_else(|e| {
+        eprint!("{}", USAGE);
+        eprintln!("bundle: {}", e);
+        std::process::exit(1);
+    });
+    let src_dir = PathBuf::from(values.get("--src").copied().unwrap_or("src"));
+    let bundler = Bundler {
+        main_path: values
+            .get("--main")
+            .map_or_else(|| src_dir.join("bin/solve.rs"), PathBuf::from),
+        src_dir,
+    };
+    let bundle = bundler.bundle().unwrap_or_else(|e| {
+        eprintln!("bundle: {}", e);
+        std::process::exit(1);
+    });
+    match values.get("--out") {
+        Some(path) => fs::write(path, bundle).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => print!("{}", bundle),
+    }
+}
+
+#[test]
+fn test_tokenize() {
+    let src = "let s = r#\"a \" }\"#; let c = '}'; fn f<'a>(x: &'a str) {} /* { /* } */ */ 1e-5";
+    let tokens = Tokens::new(src).unwrap();
+    let texts: Vec<&str> = (0..tokens.tokens.len()).map(|i| tokens.text(i)).collect();
+    assert!(texts.contains(&"r#\"a \" }\"#"));
+    assert!(texts.contains(&"'}'"));
+    assert!(texts.contains(&"'a"));
+    assert!(texts.contains(&"1e-5"));
+    assert_eq!(texts.iter().filter(|t| **t == "{").count(), 1);
+    assert!(tokenize("\"abc").is_err());
+}
+
+#[test]
+fn test_strip_tests() {
+    let src = "fn a() {}\n\n#[test]\nfn test_a() {\n    let s = \"}\";\n}\n\n#[cfg(test)]\nmod tests {\n    fn b() {}\n}\n#[cfg(test)]\nuse std::fmt;\n#[derive(Debug)]\nstruct S;\n";
+    assert_eq!(
+        strip_tests(src).unwrap(),
+        "fn a() {}\n\n\n#[derive(Debug)]\nstruct S;\n"
+    );
+}
+
+#[test]
+fn test_bundle() {
+    let dir = std::env::temp_dir().join(format!("bundle_test_{}", std::process::id()));
+    fs::create_dir_all(dir.join("a")).unwrap();
+    let write = |path: &str, contents: &str| fs::write(dir.join(path), contents).unwrap();
+    write(
+        "main.rs",
+        "#![allow(dead_code)]\nmod a; // expand\nuse ahc014::b::f;\nfn main() { crate::a::c::g(); f(); ahc014::E; }\n",
+    );
+    write(
+        "a.rs",
+        "pub mod c;\n#[test]\nfn t() {}\npub const S: &str = \"x\ny\";\n",
+    );
+    write("a/c.rs", "pub fn g() {}\n");
+    write(
+        "lib.rs",
+        "pub mod b {\n    pub fn f() { crate::b::h(); crate::d::k(); }\n    fn h() {}\n}\npub mod d;\nmod e;\nmod unused;\n\npub use e::E;\npub use unused::{u, U};\n",
+    );
+    write(
+        "d.rs",
+        "pub fn k() {}\n#[test]\nfn t() { crate::unused::u(); }\n",
+    );
+    write("e.rs", "pub struct E;\n");
+    write("unused.rs", "pub fn u() {}\npub struct U;\n");
+
+    let bundler = Bundler {
+        src_dir: dir.clone(),
+        main_path: dir.join("main.rs"),
+    };
+    let bundle = bundler.bundle().unwrap();
+    assert!(bundle.starts_with(
+        "#![allow(dead_code)]\n\nmod a {\n    pub mod c {\n        pub fn g() {}\n    }\n"
+    ));
+    assert!(bundle.contains("pub const S: &str = \"x\ny\";"));
+    assert!(bundle.contains("use crate::ahc014::b::f;"));
+    assert!(bundle.contains("crate::ahc014::b::h();"));
+    assert!(!bundle.contains("fn t()"));
+    // テストからしか参照されていないモジュールは埋め込まない
+    assert!(bundle.contains("pub mod d {\n        pub fn k() {}\n    }"));
+    assert!(!bundle.contains("mod unused"));
+    assert!(!bundle.contains("unused::"));
+    // 再公開した名前から、そのモジュールを辿る
+    assert!(bundle.contains("pub use e::E;"));
+    assert!(bundle.contains("mod e {"));
+
+    write("main.rs", "mod a;\nfn main() { crate::a::d::g(); }\n");
+    assert!(bundler.bundle().unwrap_err().contains("crate::a::d"));
+    write("a.rs", "mod missing;\n");
+    assert!(bundler
+        .bundle()
+        .unwrap_err()
+        .contains("unresolved module \"missing\""));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| -> Vec<String> { s.split_whitespace().map(String::from).collect() };
+    let given = args("--out submit.rs --src src");
+    let values = parse_args(&given).unwrap();
+    assert_eq!(values.get("--out"), Some(&"submit.rs"));
+    assert_eq!(values.get("--src"), Some(&"src"));
+    assert_eq!(values.get("--main"), None);
+    assert!(parse_args(&[]).unwrap().is_empty());
+    assert_eq!(
+        parse_args(&args("--ouut submit.rs")),
+        Err("unknown argument \"--ouut\"".to_string())
+    );
+    assert_eq!(
+        parse_args(&args("--out")),
+        Err("--out needs a value".to_string())
+    );
+}
@@ -0,0 +1,14 @@
+// 提出用の入口。標準入力の問題を既定の設定で解いて、標準出力に書く
+// bundleはこのファイルから辿れるモジュールだけをsubmit.rsにまとめる
+//
+// usage: cargo run --release --bin solve < in.txt
+use ahc014::config::SolverConfig;
+use ahc014::Input;
+use std::io::Read;
+
+fn main() {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).unwrap();
+    let input = Input::parse(&text).unwrap_or_else(|e| panic!("input: {}", e));
+    print!("{}", ahc014::solve(&input, SolverConfig::new()));
+}
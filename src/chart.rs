@@ -0,0 +1,264 @@
+use crate::svg::*;
+use crate::telemetry::*;
+use std::fmt::Write;
+
+const WIDTH: f64 = 800.;
+const PANEL_HEIGHT: f64 = 180.;
+const LEFT: f64 = 70.;
+const RIGHT: f64 = 130.;
+const TOP: f64 = 24.;
+const GAP: f64 = 40.;
+// 折れ線の点の数の上限
+const MAX_POINTS: usize = 400;
+
+struct Series {
+    name: String,
+    color: String,
+    points: Vec<(f64, f64)>,
+}
+
+struct Panel {
+    title: String,
+    series: Vec<Series>,
+}
+
+impl Panel {
+    fn y_range(&self) -> (f64, f64) {
+        let values = self
+            .series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|p| p.1));
+        let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| {
+            (f64::min(min, v), f64::max(max, v))
+        });
+        if min > max {
+            (0., 1.)
+        } else if min == max {
+            (min - 1., max + 1.)
+        } else {
+            (min, max)
+        }
+    }
+
+    fn render(&self, top: f64, x_max: f64, svg: &mut String) {
+        let plot_width = WIDTH - LEFT - RIGHT;
+        let (y_min, y_max) = self.y_range();
+        let px = |x: f64| LEFT + x / x_max * plot_width;
+        let py = |y: f64| top + PANEL_HEIGHT - (y - y_min) / (y_max - y_min) * PANEL_HEIGHT;
+
+        writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="none" stroke="#999"/>"##,
+            LEFT, top, plot_width, PANEL_HEIGHT
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" font-size="12" font-weight="bold">{}</text>"#,
+            LEFT,
+            top - 6.,
+            self.title
+        )
+        .unwrap();
+        for (y, anchor) in [(y_max, top + 10.), (y_min, top + PANEL_HEIGHT)] {
+            writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-size="10" text-anchor="end">{}</text>"#,
+                LEFT - 4.,
+                anchor,
+                format_value(y)
+            )
+            .unwrap();
+        }
+
+        for (i, series) in self.series.iter().enumerate() {
+            let points: Vec<String> = series
+                .points
+                .iter()
+                .map(|&(x, y)| format!("{:.1},{:.1}", px(x), py(y)))
+                .collect();
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.2"/>"#,
+                points.join(" "),
+                series.color
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-size="11" fill="{}">{}</text>"#,
+                WIDTH - RIGHT + 8.,
+                top + 12. + 14. * i as f64,
+                series.color,
+                series.name
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn format_value(v: f64) -> String {
+    if v.abs() >= 100. {
+        format!("{:.0}", v)
+    } else {
+        format!("{:.3}", v)
+    }
+}
+
+// 点が多すぎないように間引いたエントリ（最後のエントリは必ず含む）
+fn sampled_entries(log: &ScoreLog) -> Vec<&LogEntry> {
+    let step = usize::max(1, (log.entries.len() + MAX_POINTS - 1) / MAX_POINTS);
+    let mut entries: Vec<&LogEntry> = log.entries.iter().step_by(step).collect();
+    if let Some(last) = log.entries.last() {
+        if entries.last().map(|e| e.iteration) != Some(last.iteration) {
+            entries.push(last);
+        }
+    }
+    entries
+}
+
+fn panels(log: &ScoreLog) -> Vec<Panel> {
+    let entries = sampled_entries(log);
+    let line = |f: &dyn Fn(&LogEntry) -> f64| -> Vec<(f64, f64)> {
+        entries.iter().map(|e| (e.iteration as f64, f(e))).collect()
+    };
+
+    let score = Panel {
+        title: "score".to_string(),
+        series: vec![
+            Series {
+                name: "current".to_string(),
+                color: category_color(0).to_string(),
+                points: line(&|e| e.score as f64),
+            },
+            Series {
+                name: "best".to_string(),
+                color: category_color(1).to_string(),
+                points: line(&|e| e.best_score as f64),
+            },
+        ],
+    };
+    let temp = Panel {
+        title: "temperature".to_string(),
+        series: vec![Series {
+            name: "temp".to_string(),
+            color: category_color(3).to_string(),
+            points: line(&|e| e.temp as f64),
+        }],
+    };
+
+    // 間引いた区間ごとの採用率。試行がない区間は点を打たない
+    let acceptance = Panel {
+        title: "acceptance rate".to_string(),
+        series: log
+            .neighborhoods
+            .iter()
+            .enumerate()
+            .map(|(j, name)| Series {
+                name: name.clone(),
+                color: category_color(j).to_string(),
+                points: entries
+                    .windows(2)
+                    .filter_map(|w| {
+                        let total = w[1].total[j] - w[0].total[j];
+                        let adopted = w[1].adopted[j] - w[0].adopted[j];
+                        if total > 0 {
+                            Some((w[1].iteration as f64, adopted as f64 / total as f64))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    vec![score, temp, acceptance]
+}
+
+pub fn render_chart_svg(log: &ScoreLog) -> String {
+    let panels = panels(log);
+    let height = TOP + panels.len() as f64 * (PANEL_HEIGHT + GAP);
+    let x_max = log
+        .entries
+        .last()
+        .map_or(1., |e| f64::max(1., e.iteration as f64));
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {:.0} {:.0}" font-family="sans-serif">"#,
+        WIDTH, height, WIDTH, height
+    )
+    .unwrap();
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    for (i, panel) in panels.iter().enumerate() {
+        panel.render(TOP + i as f64 * (PANEL_HEIGHT + GAP), x_max, &mut svg);
+    }
+    writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" font-size="10" text-anchor="end">iteration {}</text>"#,
+        WIDTH - RIGHT,
+        height - GAP + 14.,
+        x_max
+    )
+    .unwrap();
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// グラフと近傍ごとの最終的な採用率の表
+pub fn render_chart_html(log: &ScoreLog) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>AHC014 score log</title>\n</head>\n<body style=\"font-family: sans-serif\">\n",
+    );
+    html.push_str(&render_chart_svg(log));
+    if let Some(last) = log.entries.last() {
+        writeln!(
+            html,
+            "<p>iterations: {}, final score: {}, best score: {}</p>",
+            last.iteration, last.score, last.best_score
+        )
+        .unwrap();
+        html.push_str("<table border=\"1\" cellpadding=\"4\" style=\"border-collapse: collapse\">\n<tr><th>neighborhood</th><th>total</th><th>adopted</th><th>ratio</th></tr>\n");
+        for (j, name) in log.neighborhoods.iter().enumerate() {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.4}</td></tr>",
+                name,
+                last.total[j],
+                last.adopted[j],
+                last.adopted[j] as f64 / i32::max(1, last.total[j]) as f64
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[test]
+fn test_render_chart() {
+    let mut log = ScoreLog::new(vec!["Add".to_string(), "Delete".to_string()]);
+    for i in 0..1000 {
+        let score = 1000 + i as i32;
+        log.record(
+            i * 100,
+            100. - i as f32 * 0.1,
+            score,
+            score,
+            &[i as i32 * 90, i as i32 * 10],
+            &[i as i32 * 30, 0],
+        );
+    }
+    assert!(sampled_entries(&log).len() <= MAX_POINTS + 1);
+    assert_eq!(sampled_entries(&log).last().unwrap().iteration, 99900);
+
+    let svg = render_chart_svg(&log);
+    assert_eq!(svg.matches("<polyline").count(), 5);
+    assert!(svg.contains(">Delete</text>"));
+
+    let html = render_chart_html(&log);
+    assert!(html.contains("<tr><td>Add</td><td>89910</td><td>29970</td><td>0.3333</td></tr>"));
+}
@@ -0,0 +1,591 @@
+use crate::config::*;
+use crate::def::*;
+use crate::framework::*;
+use crate::grid::*;
+use crate::input::*;
+use crate::solver::*;
+use crate::state::*;
+use crate::util::*;
+use crate::weight::*;
+use crate::{ascii, batch, chart, compare, generator, html, scorer, svg, telemetry, validator};
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
+
+const USAGE: &str = "usage: ahc014 [<command>] [options]
+
+With no command, reads an input from stdin and writes a solution to stdout.
+
+commands:
+  solve [<input>] [--out <path>]   solve an input file (stdin if omitted)
+  score <input> <output>           print \"Score = X\" for a solution
+  validate <input> <output>        check a solution against the rules
+  render <input> <output>          draw a solution as SVG
+  replay <input> <output>          write a step-through HTML replay
+  show <input> <output> [--ansi]   draw a solution as text
+  chart <score log>                draw a recorded score log
+  gen <seed>                       generate an input
+  config [options]                 print the solver settings as a config file
+  bench [--seed S] [--count K]     solve generated inputs and summarize
+  batch [--seed S] [--cases K]     solve inputs in parallel and write a CSV
+        [--threads T] [--in <dir>] [--out <dir>] [--csv <path>]
+  compare [--a <flags>] [--b <flags>] [--a-bin <path>] [--b-bin <path>]
+          run two configurations or binaries on the same seeds
+          (also takes the batch options except --out)
+
+solver options:
+  --config <path>     key = value settings; the flags below override it
+  --time-limit <sec>  --loop-interval <k>
+  --iterations <k>    stop after k iterations instead of time (reproducible)
+  --multiple-add-limit <k>  --deletion-limit <k>
+  --outward-samples <k>  --outward-chain <k>
+  --start-temp <t>  --end-temp <t>    temperature at N = 30, scaled by (N/30)^2
+  --board <path>  --weight <spec>  --report <path>
+                      (score, validate, render, replay and show take
+                      --board and --weight too)
+  --svg <path>  --color <mode>  --heatmap
+  --trajectory <dir>  --trajectory-interval <k>
+  --score-log <path>  --chart <path>
+";
+
+// 値のないフラグは使い方の誤りとして終了する
+pub fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == name)?;
+    match args.get(i + 1) {
+        Some(v) => Some(v.as_str()),
+        None => exit_with_error("usage", &format!("{} needs a value", name)),
+    }
+}
+
+pub fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+// 値が読めなければ終了する
+pub fn parse_arg<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    arg_value(args, name).map(|v| {
+        v.parse::<T>()
+            .unwrap_or_else(|_| exit_with_error(name, &format!("invalid value {:?}", v)))
+    })
+}
+
+pub fn exit_with_error(name: &str, message: &str) -> ! {
+    eprintln!("{}: {}", name, message);
+    std::process::exit(1);
+}
+
+pub fn read_file(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| exit_with_error(path, &e.to_string()))
+}
+
+pub fn write_file(path: &str, contents: &str) {
+    fs::write(path, contents).unwrap_or_else(|e| exit_with_error(path, &e.to_string()));
+}
+
+pub fn run(args: &[String]) {
+    let rest = if args.len() >= 2 { &args[2..] } else { &[] };
+    match args.get(1).map(|s| s.as_str()) {
+        // 引数なし、またはオプションだけならコンテストと同じく標準入出力で解く
+        None => run_solve(&[]),
+        Some(arg) if arg.starts_with("--") && arg != "--help" => run_solve(&args[1..]),
+        Some("solve") => run_solve(rest),
+        Some("score") => run_score(rest),
+        Some("validate") => run_validate(rest),
+        Some("render") => run_render(rest),
+        Some("replay") => run_replay(rest),
+        Some("show") => run_show(rest),
+        Some("chart") => run_chart(rest),
+        Some("gen") => run_gen(rest),
+        Some("bench") => run_bench(rest),
+        Some("batch") => run_batch(rest),
+        Some("compare") => run_compare(rest),
+        Some("config") => print!("{}", solver_config(rest).to_config_file()),
+        Some("help") | Some("--help") | Some("-h") => print!("{}", USAGE),
+        Some(command) => {
+            eprint!("{}", USAGE);
+            exit_with_error("ahc014", &format!("unknown command {:?}", command));
+        }
+    }
+}
+
+// --config pathの設定に、引数で指定した値を上書きする
+pub fn solver_config(args: &[String]) -> SolverConfig {
+    let mut config = match arg_value(args, "--config") {
+        Some(path) => SolverConfig::parse_config_file(&read_file(path))
+            .unwrap_or_else(|e| exit_with_error(path, &e)),
+        None => SolverConfig::new(),
+    };
+    if let Some(v) = parse_arg(args, "--time-limit") {
+        config.time_limit = v;
+    }
+    if let Some(v) = parse_arg(args, "--iterations") {
+        config.iteration_limit = Some(v);
+    }
+    if let Some(v) = parse_arg::<usize>(args, "--loop-interval") {
+        config.loop_interval = usize::max(1, v);
+    }
+    if let Some(v) = parse_arg(args, "--multiple-add-limit") {
+        config.multiple_add_recursion_limit = v;
+    }
+    if let Some(v) = parse_arg(args, "--deletion-limit") {
+        config.deletion_recursion_limit = v;
+    }
+    if let Some(v) = parse_arg::<usize>(args, "--outward-samples") {
+        config.outward_growth_samples = usize::max(1, v);
+    }
+    if let Some(v) = parse_arg(args, "--outward-chain") {
+        config.outward_growth_chain_limit = v;
+    }
+    if let Some(v) = parse_arg(args, "--start-temp") {
+        config.start_temp = v;
+    }
+    if let Some(v) = parse_arg(args, "--end-temp") {
+        config.end_temp = v;
+    }
+    let telemetry = &mut config.telemetry;
+    for (name, path) in [
+        ("--report", &mut telemetry.report),
+        ("--svg", &mut telemetry.svg),
+        ("--score-log", &mut telemetry.score_log),
+        ("--chart", &mut telemetry.chart),
+        ("--trajectory", &mut telemetry.trajectory),
+    ] {
+        if let Some(v) = arg_value(args, name) {
+            *path = Some(v.to_string());
+        }
+    }
+    if let Some(v) = parse_arg::<usize>(args, "--trajectory-interval") {
+        telemetry.trajectory_interval = usize::max(1, v);
+    }
+    config
+}
+
+// 入力と、--board path、--weight center | uniform | center:x,y | file:pathから、盤面と重みを作る
+fn read_problem(text: &str, args: &[String]) -> (Input, Grid, WeightMap) {
+    let board = arg_value(args, "--board").map(|path| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| Grid::parse_board(&s))
+            .unwrap_or_else(|e| exit_with_error("--board", &e))
+    });
+    let parsed = match &board {
+        Some(grid) => Input::parse_within(text, grid.width, grid.height),
+        None => Input::parse(text),
+    };
+    let input = parsed.unwrap_or_else(|e| exit_with_error("input", &e.to_string()));
+    let grid = board.unwrap_or_else(|| Grid::new(input.n));
+
+    let weight_map = match arg_value(args, "--weight") {
+        Some(spec) => WeightMap::from_spec(grid.width, grid.height, spec)
+            .unwrap_or_else(|e| exit_with_error("--weight", &e.to_string())),
+        None => WeightMap::center_distance(grid.width, grid.height),
+    };
+    (input, grid, weight_map)
+}
+
+// <input> <output>を、--board、--weightの盤面と重みで確かめる
+fn validate_files(args: &[String]) -> Result<State, validator::ValidationError> {
+    let (input, grid, weight_map) = read_problem(&read_file(&args[0]), args);
+    validator::validate(&input, grid, weight_map, &read_file(&args[1]))
+}
+
+// solve [<input>] [--out path] [options]
+fn run_solve(args: &[String]) {
+    time::start_clock();
+
+    let config = solver_config(args);
+    let text = match args.first() {
+        Some(path) if !path.starts_with("--") => read_file(path),
+        _ => {
+            let mut stdin = String::new();
+            std::io::stdin()
+                .read_to_string(&mut stdin)
+                .unwrap_or_else(|e| exit_with_error("input", &e.to_string()));
+            stdin
+        }
+    };
+
+    let (input, grid, weight_map) = read_problem(&text, args);
+    for pos in &input.points {
+        if !grid.is_valid(pos) {
+            exit_with_error(
+                "input",
+                &format!("point ({}, {}) is on a blocked cell", pos.x, pos.y),
+            );
+        }
+    }
+
+    let time_limit = config.time_limit;
+    let telemetry = config.telemetry.clone();
+    let mut solver = Solver::from_input(&input, grid, weight_map, config);
+
+    // --trajectory dir [--trajectory-interval K]
+    if telemetry.trajectory.is_some() {
+        solver.trajectory = Some(telemetry::Trajectory::new(telemetry.trajectory_interval));
+    }
+
+    solver.solve(time_limit);
+    match arg_value(args, "--out") {
+        Some(path) => write_file(path, &solver.output()),
+        None => print!("{}", solver.output()),
+    }
+
+    // --report path (指定しなければ標準エラー出力)
+    let report = solver.report(input.m()).to_json();
+    match &telemetry.report {
+        Some(path) => write_file(path, &(report + "\n")),
+        None => eprintln!("{}", report),
+    }
+
+    // --svg path [--color uniform|depth|weight|neighborhood] [--heatmap]
+    let mut options = svg_options(args);
+    options.color_by = match arg_value(args, "--color") {
+        None => svg::ColorBy::Neighborhood(&solver.square_origins),
+        Some(v) => svg::ColorBy::parse(v, Some(&solver.square_origins))
+            .unwrap_or_else(|e| exit_with_error("--color", &e)),
+    };
+    if let Some(path) = &telemetry.svg {
+        write_svg(&solver.state, &options, Some(path));
+    }
+    // --score-log path.tsv, --chart path.svg|path.html
+    if let Some(score_log) = &solver.score_log {
+        if let Some(path) = &telemetry.score_log {
+            write_file(path, &score_log.to_tsv());
+        }
+        if let Some(path) = &telemetry.chart {
+            write_chart(score_log, path);
+        }
+    }
+    if let (Some(dir), Some(trajectory)) = (&telemetry.trajectory, &solver.trajectory) {
+        trajectory
+            .write(dir, &solver.state, &options)
+            .unwrap_or_else(|e| exit_with_error(dir, &e.to_string()));
+    }
+}
+
+// bench [--seed S] [--count K] [solver options]
+// 公式と同じ生成方法の入力を順に解いて、スコアと速度をまとめる
+fn run_bench(args: &[String]) {
+    let seed: u64 = parse_arg(args, "--seed").unwrap_or(0);
+    let count: u64 = parse_arg(args, "--count").unwrap_or(5);
+    let config = solver_config(args);
+
+    println!("seed\tn\tm\tscore\tloop_count");
+    let mut total_score: i64 = 0;
+    let mut total_loops: usize = 0;
+    let mut total_seconds: f64 = 0.;
+    for seed in seed..seed + count {
+        let input = generator::generate(seed, &generator::GeneratorConfig::official()).unwrap();
+        time::restart_clock();
+        let mut solver = Solver::from_input(
+            &input,
+            Grid::new(input.n),
+            WeightMap::center_distance(input.n, input.n),
+            config.clone(),
+        );
+        solver.solve(config.time_limit);
+        total_seconds += time::elapsed_seconds();
+
+        let report = solver.report(input.m());
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            seed,
+            input.n,
+            input.m(),
+            report.real_score,
+            report.loop_count
+        );
+        total_score += report.real_score as i64;
+        total_loops += report.loop_count;
+    }
+    if count > 0 {
+        println!(
+            "mean score: {:.1}, loops/sec: {:.0}",
+            total_score as f64 / count as f64,
+            total_loops as f64 / total_seconds
+        );
+    }
+}
+
+// batch [--seed S] [--cases K] [--threads T] [--in dir] [--out dir] [--csv path] [solver options]
+// --inがなければ公式と同じ生成方法で入力を作る
+fn run_batch(args: &[String]) {
+    let seed: u64 = parse_arg(args, "--seed").unwrap_or(0);
+    let case_count: u64 = parse_arg(args, "--cases").unwrap_or(100);
+    let threads = parse_arg(args, "--threads").unwrap_or_else(batch::default_threads);
+    let csv_path = arg_value(args, "--csv").unwrap_or("tools/out/batch.csv");
+    let out_dir = arg_value(args, "--out");
+    let config = solver_config(args);
+
+    let cases = load_cases(args, seed, case_count);
+
+    let mut done = 0;
+    let mut total: i64 = 0;
+    let solve = |case: &batch::Case| batch::solve_case(case, &config);
+    let outputs = batch::run_cases(&cases, threads, solve, |case, output| match output {
+        Ok(output) => {
+            done += 1;
+            total += output.result.score as i64;
+            println!(
+                "case {:3}: (score: {:7}, current ave: {:10.2})",
+                case.seed,
+                output.result.score,
+                total as f64 / done as f64
+            );
+        }
+        Err(e) => eprintln!("case {:3}: input {}", case.seed, e),
+    });
+
+    let mut results = vec![];
+    for (case, output) in cases.iter().zip(&outputs) {
+        if let (Some(dir), Ok(output)) = (out_dir, output) {
+            fs::create_dir_all(dir).unwrap_or_else(|e| exit_with_error(dir, &e.to_string()));
+            write_file(&format!("{}/{:04}.txt", dir, case.seed), &output.output);
+        }
+        if let Ok(output) = output {
+            results.push(output.result.clone());
+        }
+    }
+    println!();
+    print!("{}", batch::render_summary(&results));
+
+    if let Some(parent) = std::path::Path::new(csv_path).parent() {
+        fs::create_dir_all(parent).unwrap_or_else(|e| exit_with_error(csv_path, &e.to_string()));
+    }
+    write_file(csv_path, &batch::to_csv(&results));
+    if results.len() != outputs.len() {
+        exit_with_error(
+            "batch",
+            &format!("{} cases failed", outputs.len() - results.len()),
+        );
+    }
+}
+
+// --in dirがあれば dir/{seed:04}.txt、なければ公式と同じ生成方法で入力を作る
+fn load_cases(args: &[String], seed: u64, count: u64) -> Vec<batch::Case> {
+    (seed..seed + count)
+        .map(|seed| batch::Case {
+            seed,
+            input: match arg_value(args, "--in") {
+                Some(dir) => read_file(&format!("{}/{:04}.txt", dir, seed)),
+                None => generator::generate(seed, &generator::GeneratorConfig::official())
+                    .unwrap()
+                    .to_string(),
+            },
+        })
+        .collect()
+}
+
+// compare [--a "<flags>"] [--b "<flags>"] [--a-bin path] [--b-bin path]
+//         [--seed S] [--cases K] [--threads T] [--in dir] [--csv path] [solver options]
+// バイナリを指定した側は、そのバイナリに--a/--bの引数を渡して標準入出力で解かせる
+fn run_compare(args: &[String]) {
+    let seed: u64 = parse_arg(args, "--seed").unwrap_or(0);
+    let case_count: u64 = parse_arg(args, "--cases").unwrap_or(100);
+    let threads = parse_arg(args, "--threads").unwrap_or_else(batch::default_threads);
+    let cases = load_cases(args, seed, case_count);
+
+    let mut results = vec![];
+    for side in ["a", "b"] {
+        let flags: Vec<String> = arg_value(args, &format!("--{}", side))
+            .unwrap_or("")
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        let binary = arg_value(args, &format!("--{}-bin", side));
+        eprintln!(
+            "running {}: {} {}",
+            side.to_uppercase(),
+            binary.unwrap_or("(this binary)"),
+            flags.join(" ")
+        );
+        // 個別の引数を先に置いて、共通の引数より優先する
+        let config = solver_config(&[&flags[..], args].concat());
+        let solve = |case: &batch::Case| match binary {
+            Some(program) => batch::solve_case_with_binary(case, program, &flags),
+            None => batch::solve_case(case, &config).map_err(|e| format!("input {}", e)),
+        };
+        let outputs = batch::run_cases(&cases, threads, solve, |case, output| {
+            if let Err(e) = output {
+                eprintln!("case {:3}: {}", case.seed, e);
+            }
+        });
+        let side_results: Vec<batch::CaseResult> = outputs
+            .into_iter()
+            .filter_map(|output| output.ok().map(|output| output.result))
+            .collect();
+        results.push(side_results);
+    }
+
+    let paired = compare::pair_results(&results[0], &results[1]);
+    print!("{}", compare::render_comparison(&paired));
+    if let Some(path) = arg_value(args, "--csv") {
+        write_file(path, &compare::to_csv(&paired));
+    }
+}
+
+// validate <input> <output>
+fn run_validate(args: &[String]) {
+    if args.len() < 2 {
+        exit_with_error(
+            "usage",
+            "validate <input> <output> [--board path] [--weight spec]",
+        );
+    }
+    match validate_files(args) {
+        Ok(state) => println!("OK: {} squares", state.squares.len()),
+        Err(e) => exit_with_error("validate", &e.to_string()),
+    }
+}
+
+// score <input> <output>
+// 公式のビジュアライザと同じく"Score = X"を出力し、不正な出力は0点とする
+// 不正な出力では、違反を標準エラー出力に書いて失敗として終了する
+fn run_score(args: &[String]) {
+    if args.len() < 2 {
+        exit_with_error(
+            "usage",
+            "score <input> <output> [--board path] [--weight spec]",
+        );
+    }
+    match validate_files(args) {
+        Ok(state) => println!("Score = {}", scorer::score_state(&state)),
+        Err(e) => {
+            println!("Score = 0");
+            exit_with_error("score", &e.to_string());
+        }
+    }
+}
+
+// gen <seed> [--count K --out dir] [--n N] [--m M] [--density low|mid|high] [--layout layout]
+fn run_gen(args: &[String]) {
+    let seed: u64 = match args.first().map(|s| s.parse()) {
+        Some(Ok(seed)) => seed,
+        _ => exit_with_error("usage", "gen <seed> [options]"),
+    };
+    let mut config = generator::GeneratorConfig::official();
+    config.n = parse_arg(args, "--n");
+    config.m = parse_arg(args, "--m");
+    if let Some(v) = arg_value(args, "--density") {
+        config.density =
+            Some(generator::Density::parse(v).unwrap_or_else(|e| exit_with_error("--density", &e)));
+    }
+    if let Some(v) = arg_value(args, "--layout") {
+        config.layout =
+            generator::Layout::parse(v).unwrap_or_else(|e| exit_with_error("--layout", &e));
+    }
+    let generate = |seed: u64| {
+        generator::generate(seed, &config).unwrap_or_else(|e| exit_with_error("gen", &e))
+    };
+
+    match arg_value(args, "--out") {
+        Some(dir) => {
+            let count: u64 = parse_arg(args, "--count").unwrap_or(1);
+            fs::create_dir_all(dir).unwrap_or_else(|e| exit_with_error(dir, &e.to_string()));
+            for seed in seed..seed + count {
+                let path = format!("{}/{:04}.txt", dir, seed);
+                write_file(&path, &generate(seed).to_string());
+            }
+        }
+        None => print!("{}", generate(seed)),
+    }
+}
+
+// render <input> <output> [--out file.svg] [--color uniform|depth|weight] [--heatmap]
+fn run_render(args: &[String]) {
+    if args.len() < 2 {
+        exit_with_error("usage", "render <input> <output> [options]");
+    }
+    let state = validate_files(args).unwrap_or_else(|e| exit_with_error("render", &e.to_string()));
+    let mut options = svg_options(args);
+    if let Some(v) = arg_value(args, "--color") {
+        options.color_by =
+            svg::ColorBy::parse(v, None).unwrap_or_else(|e| exit_with_error("--color", &e));
+    }
+    write_svg(&state, &options, arg_value(args, "--out"));
+}
+
+// replay <input> <output> [--out file.html]
+fn run_replay(args: &[String]) {
+    if args.len() < 2 {
+        exit_with_error("usage", "replay <input> <output> [--out file.html]");
+    }
+    let state = validate_files(args).unwrap_or_else(|e| exit_with_error("replay", &e.to_string()));
+    let html = html::render_replay(&state);
+    match arg_value(args, "--out") {
+        Some(path) => write_file(path, &html),
+        None => print!("{}", html),
+    }
+}
+
+// chart <score log> [--out file.svg|file.html]
+fn run_chart(args: &[String]) {
+    if args.is_empty() {
+        exit_with_error("usage", "chart <score log> [--out file.svg|file.html]");
+    }
+    let log = telemetry::ScoreLog::parse_tsv(&read_file(&args[0]))
+        .unwrap_or_else(|e| exit_with_error(&args[0], &e));
+    match arg_value(args, "--out") {
+        Some(path) => write_chart(&log, path),
+        None => print!("{}", chart::render_chart_svg(&log)),
+    }
+}
+
+// 拡張子が.htmlならHTML、それ以外はSVG
+fn write_chart(log: &telemetry::ScoreLog, path: &str) {
+    let chart = if path.ends_with(".html") {
+        chart::render_chart_html(log)
+    } else {
+        chart::render_chart_svg(log)
+    };
+    write_file(path, &chart);
+}
+
+// show <input> <output> [--ansi]
+fn run_show(args: &[String]) {
+    if args.len() < 2 {
+        exit_with_error("usage", "show <input> <output> [--ansi]");
+    }
+    let state = validate_files(args).unwrap_or_else(|e| exit_with_error("show", &e.to_string()));
+    let style = if has_flag(args, "--ansi") {
+        ascii::TextStyle::Ansi
+    } else {
+        ascii::TextStyle::Ascii
+    };
+    let highlights: Vec<Pos> = state.squares.last().map_or(vec![], |square| {
+        square.all_pos().iter().map(|pos| **pos).collect()
+    });
+    print!("{}", ascii::render_grid(&state.grid, &highlights, style));
+}
+
+fn svg_options(args: &[String]) -> svg::SvgOptions<'static> {
+    let mut options = svg::SvgOptions::new();
+    options.heatmap = has_flag(args, "--heatmap");
+    options
+}
+
+fn write_svg(state: &State, options: &svg::SvgOptions, path: Option<&str>) {
+    let svg = svg::render_state(state, options);
+    match path {
+        Some(path) => write_file(path, &svg),
+        None => print!("{}", svg),
+    }
+}
+
+#[test]
+fn test_solver_config() {
+    let args: Vec<String> = ["--time-limit", "1.5", "--deletion-limit", "3", "--heatmap"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let config = solver_config(&args);
+    assert_eq!(config.time_limit, 1.5);
+    assert_eq!(config.deletion_recursion_limit, 3);
+    assert_eq!(
+        config.multiple_add_recursion_limit,
+        MULTIPLE_ADD_RECURSION_LIMIT
+    );
+    assert_eq!(solver_config(&[]), SolverConfig::new());
+    assert!(has_flag(&args, "--heatmap"));
+    assert_eq!(arg_value(&args, "--time-limit"), Some("1.5"));
+}
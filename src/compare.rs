@@ -0,0 +1,279 @@
+use crate::batch::*;
+use std::fmt::Write;
+
+// 同じシードでの2つの設定のスコア
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairedResult {
+    pub seed: u64,
+    pub n: usize,
+    pub m: usize,
+    pub a: i32,
+    pub b: i32,
+}
+
+impl PairedResult {
+    pub fn delta(&self) -> i32 {
+        self.b - self.a
+    }
+
+    // Aに対するBの改善率
+    pub fn relative(&self) -> f64 {
+        if self.a == 0 {
+            0.
+        } else {
+            self.delta() as f64 / self.a as f64
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairedStats {
+    pub count: usize,
+    pub mean_delta: f64,
+    pub mean_relative: f64,
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+    // 対応のあるt検定 (両側)
+    pub t: f64,
+    pub p_value: f64,
+}
+
+pub fn pair_results(a: &[CaseResult], b: &[CaseResult]) -> Vec<PairedResult> {
+    a.iter()
+        .filter_map(|a| {
+            let b = b.iter().find(|b| b.seed == a.seed)?;
+            Some(PairedResult {
+                seed: a.seed,
+                n: a.n,
+                m: a.m,
+                a: a.score,
+                b: b.score,
+            })
+        })
+        .collect()
+}
+
+impl PairedStats {
+    pub fn new(results: &[PairedResult]) -> PairedStats {
+        let count = results.len();
+        let deltas: Vec<f64> = results.iter().map(|r| r.delta() as f64).collect();
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / usize::max(1, v.len()) as f64;
+        let mean_delta = mean(&deltas);
+        let relatives: Vec<f64> = results.iter().map(|r| r.relative()).collect();
+
+        let (t, p_value) = if count < 2 {
+            (0., 1.)
+        } else {
+            let variance =
+                deltas.iter().map(|d| (d - mean_delta).powi(2)).sum::<f64>() / (count - 1) as f64;
+            let se = (variance / count as f64).sqrt();
+            if se == 0. {
+                // 全て同じ差なら、差が0かどうかだけで決まる
+                if mean_delta == 0. {
+                    (0., 1.)
+                } else {
+                    (f64::INFINITY.copysign(mean_delta), 0.)
+                }
+            } else {
+                let t = mean_delta / se;
+                (t, t_test_p_value(t, (count - 1) as f64))
+            }
+        };
+
+        PairedStats {
+            count,
+            mean_delta,
+            mean_relative: mean(&relatives),
+            wins: results.iter().filter(|r| r.b > r.a).count(),
+            losses: results.iter().filter(|r| r.b < r.a).count(),
+            ties: results.iter().filter(|r| r.b == r.a).count(),
+            t,
+            p_value,
+        }
+    }
+}
+
+// 自由度dfのt分布で|T| >= |t|となる確率
+pub fn t_test_p_value(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2., 0.5, df / (df + t * t))
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos近似
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1. + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+// 正則化不完全ベータ関数 I_x(a, b)
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    // 連分数が速く収束する側で計算する
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1. - front * beta_continued_fraction(b, a, 1. - x) / b
+    }
+}
+
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const EPS: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.;
+    let mut d = 1. / clamp(1. - (a + b) * x / (a + 1.));
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let numerator = m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m));
+        d = 1. / clamp(1. + numerator * d);
+        c = clamp(1. + numerator / c);
+        h *= d * c;
+        let numerator = -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.));
+        d = 1. / clamp(1. + numerator * d);
+        c = clamp(1. + numerator / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < EPS {
+            break;
+        }
+    }
+    h
+}
+
+pub fn to_csv(results: &[PairedResult]) -> String {
+    let mut csv = String::from("seed,n,m,a,b,delta,relative\n");
+    for r in results {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.6}",
+            r.seed,
+            r.n,
+            r.m,
+            r.a,
+            r.b,
+            r.delta(),
+            r.relative()
+        )
+        .unwrap();
+    }
+    csv
+}
+
+// シードごとの差、全体の統計、N×Mごとの平均改善率
+pub fn render_comparison(results: &[PairedResult]) -> String {
+    let mut s = String::new();
+    writeln!(s, "seed   n   m        a        b    delta relative").unwrap();
+    for r in results {
+        writeln!(
+            s,
+            "{:04} {:3} {:3} {:8} {:8} {:+8} {:+7.2}%",
+            r.seed,
+            r.n,
+            r.m,
+            r.a,
+            r.b,
+            r.delta(),
+            r.relative() * 100.
+        )
+        .unwrap();
+    }
+    writeln!(s).unwrap();
+
+    let stats = PairedStats::new(results);
+    writeln!(s, "cases: {}", stats.count).unwrap();
+    writeln!(s, "mean delta: {:+.1}", stats.mean_delta).unwrap();
+    writeln!(
+        s,
+        "mean relative improvement: {:+.3}%",
+        stats.mean_relative * 100.
+    )
+    .unwrap();
+    writeln!(
+        s,
+        "win/loss/tie (B vs A): {}/{}/{}",
+        stats.wins, stats.losses, stats.ties
+    )
+    .unwrap();
+    writeln!(
+        s,
+        "paired t-test: t = {:.3}, p = {:.4}",
+        stats.t, stats.p_value
+    )
+    .unwrap();
+    writeln!(s).unwrap();
+
+    writeln!(s, "mean relative improvement (%) by N x M").unwrap();
+    let values: Vec<(usize, usize, f64)> = results
+        .iter()
+        .map(|r| (r.n, r.m, r.relative() * 100.))
+        .collect();
+    s += &render_bucket_table(&values, |v| format!("{:+7.2}", v));
+    s
+}
+
+#[test]
+fn test_t_test_p_value() {
+    // t分布表の値
+    assert!((t_test_p_value(2.262, 9.) - 0.05).abs() < 1e-3);
+    assert!((t_test_p_value(1.96, 1e6) - 0.05).abs() < 1e-3);
+    assert!((t_test_p_value(0., 5.) - 1.).abs() < 1e-9);
+    assert!((incomplete_beta(2., 3., 0.4) - 0.5248).abs() < 1e-4);
+}
+
+#[test]
+fn test_paired_stats() {
+    let results: Vec<PairedResult> = [(100, 110), (200, 190), (300, 330), (400, 400)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(a, b))| PairedResult {
+            seed: i as u64,
+            n: 31,
+            m: 30,
+            a,
+            b,
+        })
+        .collect();
+    let stats = PairedStats::new(&results);
+    assert_eq!((stats.wins, stats.losses, stats.ties), (2, 1, 1));
+    assert_eq!(stats.mean_delta, 7.5);
+    assert!((stats.mean_relative - 0.0375).abs() < 1e-9);
+    assert!(stats.p_value > 0.3 && stats.p_value < 1.);
+
+    let summary = render_comparison(&results);
+    assert!(summary.contains("0002  31  30      300      330      +30  +10.00%"));
+    assert!(summary.contains("win/loss/tie (B vs A): 2/1/1"));
+    assert!(summary.contains("31~35 |   +3.75     nan"));
+
+    let a = vec![CaseResult {
+        seed: 3,
+        n: 31,
+        m: 30,
+        score: 10,
+        loop_count: 0,
+        elapsed_seconds: 0.,
+    }];
+    let mut b = a.clone();
+    b[0].score = 12;
+    assert_eq!(pair_results(&a, &b)[0].delta(), 2);
+    assert_eq!(PairedStats::new(&pair_results(&a, &b)).p_value, 1.);
+}
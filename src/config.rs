@@ -0,0 +1,364 @@
+use crate::neighborhood::Neighborhood;
+use std::fmt::Write;
+
+pub const TIME_LIMIT: f32 = 4.97;
+pub const LOOP_INTERVAL: usize = 100;
+
+pub const MULTIPLE_ADD_RECURSION_LIMIT: usize = 20;
+pub const DELETION_RECURSION_LIMIT: usize = 10;
+
+// OutwardGrowthで起点の候補にする外周の点の数と、続けて外側に足す回数
+pub const OUTWARD_GROWTH_SAMPLES: usize = 8;
+pub const OUTWARD_GROWTH_CHAIN_LIMIT: usize = 3;
+
+// N = 30のときの温度。Nに応じて(N/30)^2倍する
+pub const START_TEMP: f32 = 500.;
+pub const END_TEMP: f32 = 25.;
+
+// Neighborhood::all()の順の、近傍を選ぶ確率
+pub const NEIGHBORHOOD_PROBS: [f32; 6] = [0.75, 0.05, 0.1, 0.1, 0., 0.];
+
+pub const TRAJECTORY_INTERVAL: usize = 10000;
+
+// 解いた結果の書き出し先。指定がなければ書き出さない
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TelemetryConfig {
+    // 指定しなければ標準エラー出力
+    pub report: Option<String>,
+    pub svg: Option<String>,
+    pub score_log: Option<String>,
+    pub chart: Option<String>,
+    pub trajectory: Option<String>,
+    pub trajectory_interval: usize,
+}
+
+// 点と四角を選ぶときの重み付け。係数が正なら値の大きいものを、負なら小さいものを選びやすくする
+// すべて0なら一様に選ぶ
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SamplerConfig {
+    // 中心からの距離の係数。進み具合に応じてstartからendへ線形に変える
+    pub center_distance_start: f32,
+    pub center_distance_end: f32,
+    // 作るのに必要な四角の段数
+    pub depth: f32,
+    // 辺が出ていない方向の数
+    pub free_degree: f32,
+}
+
+// 焼きなましのパラメータ
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolverConfig {
+    pub time_limit: f32,
+    // 指定すると、時間ではなくループ回数で進み具合を決める。同じ入力なら同じ結果になる
+    pub iteration_limit: Option<usize>,
+    // 時間と温度を更新する間隔
+    pub loop_interval: usize,
+    pub multiple_add_recursion_limit: usize,
+    pub deletion_recursion_limit: usize,
+    pub outward_growth_samples: usize,
+    pub outward_growth_chain_limit: usize,
+    pub start_temp: f32,
+    pub end_temp: f32,
+    pub neighborhood_probs: [f32; 6],
+    pub sampler: SamplerConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+// "ChangeSquare" -> "change_square"
+fn snake_case(name: &str) -> String {
+    let mut s = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            s.push('_');
+        }
+        s.push(c.to_ascii_lowercase());
+    }
+    s
+}
+
+fn neighborhood_key(neighborhood: &Neighborhood) -> String {
+    snake_case(&format!("{:?}", neighborhood))
+}
+
+// '#'以降をコメントとして取り除く。ただし文字列の中の'#'は残す
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value {:?} for {}", value, key))
+}
+
+fn parse_string(key: &str, value: &str) -> Result<String, String> {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("{} must be a quoted string", key)),
+    }
+}
+
+impl Default for SolverConfig {
+    fn default() -> SolverConfig {
+        SolverConfig::new()
+    }
+}
+
+impl SolverConfig {
+    pub fn new() -> SolverConfig {
+        SolverConfig {
+            time_limit: TIME_LIMIT,
+            iteration_limit: None,
+            loop_interval: LOOP_INTERVAL,
+            multiple_add_recursion_limit: MULTIPLE_ADD_RECURSION_LIMIT,
+            deletion_recursion_limit: DELETION_RECURSION_LIMIT,
+            outward_growth_samples: OUTWARD_GROWTH_SAMPLES,
+            outward_growth_chain_limit: OUTWARD_GROWTH_CHAIN_LIMIT,
+            start_temp: START_TEMP,
+            end_temp: END_TEMP,
+            neighborhood_probs: NEIGHBORHOOD_PROBS,
+            sampler: SamplerConfig::default(),
+            telemetry: TelemetryConfig {
+                trajectory_interval: TRAJECTORY_INTERVAL,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn temp_scale(n: usize) -> f32 {
+        (n as f32 / 30.).powf(2.)
+    }
+
+    #[allow(unused_variables)]
+    pub fn calc_start_temp(&self, n: usize, m: usize) -> f32 {
+        self.start_temp * SolverConfig::temp_scale(n)
+    }
+
+    #[allow(unused_variables)]
+    pub fn calc_end_temp(&self, n: usize, m: usize) -> f32 {
+        self.end_temp * SolverConfig::temp_scale(n)
+    }
+
+    // 設定ファイルの1項目を反映する。keyは"section.key"
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let sampler = &mut self.sampler;
+        let telemetry = &mut self.telemetry;
+        match key {
+            "time_limit" => self.time_limit = parse_value(key, value)?,
+            "iteration_limit" => self.iteration_limit = Some(parse_value(key, value)?),
+            "loop_interval" => self.loop_interval = usize::max(1, parse_value(key, value)?),
+            "multiple_add_recursion_limit" => {
+                self.multiple_add_recursion_limit = parse_value(key, value)?
+            }
+            "deletion_recursion_limit" => self.deletion_recursion_limit = parse_value(key, value)?,
+            "outward_growth_samples" => {
+                self.outward_growth_samples = usize::max(1, parse_value(key, value)?)
+            }
+            "outward_growth_chain_limit" => {
+                self.outward_growth_chain_limit = parse_value(key, value)?
+            }
+            "start_temp" => self.start_temp = parse_value(key, value)?,
+            "end_temp" => self.end_temp = parse_value(key, value)?,
+            "sampler.center_distance_start" => {
+                sampler.center_distance_start = parse_value(key, value)?
+            }
+            "sampler.center_distance_end" => sampler.center_distance_end = parse_value(key, value)?,
+            "sampler.depth" => sampler.depth = parse_value(key, value)?,
+            "sampler.free_degree" => sampler.free_degree = parse_value(key, value)?,
+            "telemetry.report" => telemetry.report = Some(parse_string(key, value)?),
+            "telemetry.svg" => telemetry.svg = Some(parse_string(key, value)?),
+            "telemetry.score_log" => telemetry.score_log = Some(parse_string(key, value)?),
+            "telemetry.chart" => telemetry.chart = Some(parse_string(key, value)?),
+            "telemetry.trajectory" => telemetry.trajectory = Some(parse_string(key, value)?),
+            "telemetry.trajectory_interval" => {
+                telemetry.trajectory_interval = usize::max(1, parse_value(key, value)?)
+            }
+            _ => {
+                let neighborhood = key.strip_prefix("neighborhood.").and_then(|name| {
+                    Neighborhood::all()
+                        .into_iter()
+                        .find(|neighborhood| neighborhood_key(neighborhood) == name)
+                });
+                match neighborhood {
+                    Some(neighborhood) => {
+                        let prob: f32 = parse_value(key, value)?;
+                        if prob < 0. || prob.is_nan() {
+                            return Err(format!("{} must not be negative", key));
+                        }
+                        self.neighborhood_probs[neighborhood as usize] = prob;
+                    }
+                    None => return Err(format!("unknown key {:?}", key)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // key = value の形式。[section]以下のkeyは"section.key"として扱う
+    pub fn parse_config_file(s: &str) -> Result<SolverConfig, String> {
+        let mut config = SolverConfig::new();
+        let mut section = String::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result =
+                if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                    section = name.trim().to_string();
+                    Ok(())
+                } else if let Some((key, value)) = line.split_once('=') {
+                    let (key, value) = (key.trim(), value.trim());
+                    if section.is_empty() {
+                        config.set(key, value)
+                    } else {
+                        config.set(&format!("{}.{}", section, key), value)
+                    }
+                } else {
+                    Err(format!("expected \"key = value\", found {:?}", line))
+                };
+            result.map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+        if config.neighborhood_probs.iter().sum::<f32>() <= 0. {
+            return Err("neighborhood probabilities must not all be zero".to_string());
+        }
+        Ok(config)
+    }
+
+    // parse_config_fileで読める形式で書き出す
+    pub fn to_config_file(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "time_limit = {}", self.time_limit).unwrap();
+        match self.iteration_limit {
+            Some(limit) => writeln!(s, "iteration_limit = {}", limit).unwrap(),
+            None => writeln!(s, "# iteration_limit = 100000    # overrides time_limit").unwrap(),
+        }
+        writeln!(s, "loop_interval = {}", self.loop_interval).unwrap();
+        writeln!(
+            s,
+            "multiple_add_recursion_limit = {}",
+            self.multiple_add_recursion_limit
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "deletion_recursion_limit = {}",
+            self.deletion_recursion_limit
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "outward_growth_samples = {}",
+            self.outward_growth_samples
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "outward_growth_chain_limit = {}",
+            self.outward_growth_chain_limit
+        )
+        .unwrap();
+        writeln!(s, "# temperatures at N = 30, scaled by (N/30)^2").unwrap();
+        writeln!(s, "start_temp = {}", self.start_temp).unwrap();
+        writeln!(s, "end_temp = {}", self.end_temp).unwrap();
+
+        writeln!(s, "\n[neighborhood]").unwrap();
+        for neighborhood in Neighborhood::all() {
+            writeln!(
+                s,
+                "{} = {}",
+                neighborhood_key(&neighborhood),
+                self.neighborhood_probs[neighborhood as usize]
+            )
+            .unwrap();
+        }
+
+        writeln!(s, "\n[sampler]").unwrap();
+        writeln!(
+            s,
+            "# > 0 prefers larger values, < 0 smaller ones, 0 samples uniformly"
+        )
+        .unwrap();
+        let sampler = &self.sampler;
+        for (key, coef) in [
+            ("center_distance_start", sampler.center_distance_start),
+            ("center_distance_end", sampler.center_distance_end),
+            ("depth", sampler.depth),
+            ("free_degree", sampler.free_degree),
+        ] {
+            writeln!(s, "{} = {}", key, coef).unwrap();
+        }
+
+        writeln!(s, "\n[telemetry]").unwrap();
+        let telemetry = &self.telemetry;
+        for (key, path) in [
+            ("report", &telemetry.report),
+            ("svg", &telemetry.svg),
+            ("score_log", &telemetry.score_log),
+            ("chart", &telemetry.chart),
+            ("trajectory", &telemetry.trajectory),
+        ] {
+            match path {
+                Some(path) => writeln!(s, "{} = \"{}\"", key, path).unwrap(),
+                None => writeln!(s, "# {} = \"\"", key).unwrap(),
+            }
+        }
+        writeln!(s, "trajectory_interval = {}", telemetry.trajectory_interval).unwrap();
+        s
+    }
+}
+
+#[test]
+fn test_config_file() {
+    let config = SolverConfig::parse_config_file(
+        "# comment\ntime_limit = 1.5\niteration_limit = 2000\nend_temp=10 # inline\n\n[neighborhood]\ndelete = 0.2\nmultiple_add = 0.05\noutward_growth = 0.1\n\n[sampler]\ncenter_distance_start = -2\ncenter_distance_end = 2.5\n\n[telemetry]\nsvg = \"out/#1.svg\"\n",
+    )
+    .unwrap();
+    assert_eq!(config.time_limit, 1.5);
+    assert_eq!(config.iteration_limit, Some(2000));
+    assert_eq!(config.end_temp, 10.);
+    assert_eq!(config.start_temp, START_TEMP);
+    assert_eq!(
+        config.neighborhood_probs[Neighborhood::Delete as usize],
+        0.2
+    );
+    assert_eq!(
+        config.neighborhood_probs[Neighborhood::MultipleAdd as usize],
+        0.05
+    );
+    assert_eq!(
+        config.neighborhood_probs[Neighborhood::OutwardGrowth as usize],
+        0.1
+    );
+    assert_eq!(config.sampler.center_distance_start, -2.);
+    assert_eq!(config.sampler.center_distance_end, 2.5);
+    assert_eq!(config.sampler.depth, 0.);
+    assert_eq!(config.telemetry.svg, Some("out/#1.svg".to_string()));
+    assert_eq!(config.telemetry.report, None);
+
+    assert_eq!(
+        SolverConfig::parse_config_file(&config.to_config_file()),
+        Ok(config)
+    );
+    assert_eq!(
+        SolverConfig::parse_config_file(&SolverConfig::new().to_config_file()),
+        Ok(SolverConfig::new())
+    );
+
+    assert_eq!(
+        SolverConfig::parse_config_file("time_limit = 1\nstart_tmp = 3\n"),
+        Err("line 2: unknown key \"start_tmp\"".to_string())
+    );
+    assert!(SolverConfig::parse_config_file("loop_interval = x\n").is_err());
+    assert!(SolverConfig::parse_config_file("[telemetry]\nsvg = a.svg\n").is_err());
+    assert!(SolverConfig::parse_config_file("[neighborhood]\nadd = -1\n").is_err());
+    assert!(SolverConfig::parse_config_file("time_limit\n").is_err());
+}
@@ -1,4 +1,3 @@
-use proconio::derive_readable;
 use std::ops;
 
 pub const DIR_MAX: usize = 8;
@@ -8,6 +7,12 @@ pub struct Score {
     pub base: i32,
 }
 
+impl Default for Score {
+    fn default() -> Score {
+        Score::new()
+    }
+}
+
 impl Score {
     pub fn new() -> Score {
         Score { base: 0 }
@@ -36,12 +41,15 @@ pub struct Square {
 
 impl Square {
     pub fn new(new_pos: Pos, diagonal: Pos, connect: [Pos; 2]) -> Square {
-        static mut SQUARE_COUNTER: i32 = 0;
-        unsafe {
-            SQUARE_COUNTER += 1;
+        thread_local! {
+            static SQUARE_COUNTER: std::cell::Cell<i32> = const { std::cell::Cell::new(0) };
         }
+        let id = SQUARE_COUNTER.with(|counter| {
+            counter.set(counter.get() + 1);
+            counter.get()
+        });
         Square {
-            id: unsafe { SQUARE_COUNTER },
+            id,
             new_pos,
             diagonal,
             connect: [
@@ -153,7 +161,6 @@ impl Dir {
     }
 }
 
-#[derive_readable]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Pos {
     pub x: i32,
@@ -171,7 +178,7 @@ impl Pos {
         if i32::abs(a.x - b.x) == i32::abs(a.y - b.y) {
             return true;
         }
-        return false;
+        false
     }
 
     pub fn get_dir(from: &Pos, to: &Pos) -> Dir {
@@ -206,7 +213,7 @@ impl Pos {
     pub fn between(from: &Pos, to: &Pos) -> Vec<Pos> {
         debug_assert!(Pos::is_aligned(from, to));
 
-        let mut cur = from.clone();
+        let mut cur = *from;
         let mut ret: Vec<Pos> = vec![];
 
         let dir = Pos::get_dir(from, to);
@@ -217,10 +224,10 @@ impl Pos {
             if &cur == to {
                 break;
             }
-            ret.push(cur.clone());
+            ret.push(cur);
         }
 
-        return ret;
+        ret
     }
 
     pub fn dist(a: &Pos, b: &Pos) -> i32 {
@@ -302,16 +309,19 @@ pub struct Point {
     pub used_dir: [bool; DIR_MAX],
     // 追加されたときに使われた点の情報
     pub added_info: Option<Square>,
+    // 初期点を0として、四角の頂点の深さの最大+1
+    pub depth: usize,
 }
 
 impl Point {
     pub fn new(pos: &Pos) -> Point {
         Point {
-            pos: pos.clone(),
+            pos: *pos,
             nearest_points: [None; DIR_MAX],
             created_points: vec![],
             used_dir: [false; DIR_MAX],
             added_info: None,
+            depth: 0,
         }
     }
 }
@@ -4,6 +4,7 @@ use crate::neighborhood::Neighborhood;
 pub trait IState {
     fn get_score(&self, progress: f32) -> f32;
     fn perform_command(&mut self, command: &Command) -> Vec<Command>;
+    #[allow(dead_code)]
     fn reverse_command(&mut self, command: &Command);
 }
 
@@ -0,0 +1,311 @@
+use crate::def::*;
+use crate::input::*;
+use crate::util::rnd::Xorshift;
+use std::fmt;
+
+pub const MIN_N: usize = 31;
+pub const MAX_N: usize = 61;
+
+// Mの範囲[N, N^2/12]を三等分したどこから選ぶか
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Density {
+    Low,
+    Mid,
+    High,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    // 公式の生成方法
+    Uniform,
+    // clusters個の中心の周りに集める
+    Clustered { clusters: usize, spread: f64 },
+    // ratioの割合の点を盤面の四隅に置く
+    CornerHeavy { ratio: f64 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorConfig {
+    pub n: Option<usize>,
+    pub m: Option<usize>,
+    pub density: Option<Density>,
+    pub layout: Layout,
+}
+
+impl GeneratorConfig {
+    pub fn official() -> GeneratorConfig {
+        GeneratorConfig {
+            n: None,
+            m: None,
+            density: None,
+            layout: Layout::Uniform,
+        }
+    }
+}
+
+impl Density {
+    pub fn parse(s: &str) -> Result<Density, String> {
+        match s {
+            "low" => Ok(Density::Low),
+            "mid" => Ok(Density::Mid),
+            "high" => Ok(Density::High),
+            _ => Err(format!("unknown density {:?}", s)),
+        }
+    }
+}
+
+impl Layout {
+    // uniform | clustered[:clusters[,spread]] | corner[:ratio]
+    pub fn parse(s: &str) -> Result<Layout, String> {
+        let (name, params) = match s.split_once(':') {
+            Some((name, params)) => (name, params.split(',').collect::<Vec<&str>>()),
+            None => (s, vec![]),
+        };
+        let param = |i: usize, default: f64| -> Result<f64, String> {
+            match params.get(i) {
+                Some(v) => v
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid layout parameter {:?}", v)),
+                None => Ok(default),
+            }
+        };
+        match name {
+            "uniform" => Ok(Layout::Uniform),
+            "clustered" => Ok(Layout::Clustered {
+                clusters: usize::max(1, param(0, 3.)? as usize),
+                spread: param(1, 3.)?,
+            }),
+            "corner" => Ok(Layout::CornerHeavy {
+                ratio: param(0, 0.3)?.clamp(0., 1.),
+            }),
+            _ => Err(format!("unknown layout {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layout::Uniform => write!(f, "uniform"),
+            Layout::Clustered { clusters, spread } => {
+                write!(f, "clustered:{},{}", clusters, spread)
+            }
+            Layout::CornerHeavy { ratio } => write!(f, "corner:{}", ratio),
+        }
+    }
+}
+
+// N < 12ではN^2/12 < Nになるので、上限をNまで広げて空にならないようにする
+pub fn m_range(n: usize) -> (usize, usize) {
+    (n, usize::max(n, n * n / 12))
+}
+
+// 公式の生成方法では、初期点は⌊N/4⌋ <= x, y <= ⌊3N/4⌋の範囲に置かれる
+pub fn central_range(n: usize) -> (usize, usize) {
+    (n / 4, 3 * n / 4)
+}
+
+pub fn generate(seed: u64, config: &GeneratorConfig) -> Result<Input, String> {
+    let mut rng = Xorshift::new(seed);
+
+    let n = config
+        .n
+        .unwrap_or_else(|| MIN_N + 2 * rng.gen_range_inclusive(0, (MAX_N - MIN_N) / 2));
+    if n == 0 {
+        return Err("N must be at least 1".to_string());
+    }
+    // 入力として読み直せないNは作らない
+    if n > MAX_BOARD_SIZE {
+        return Err(format!("N = {} must be at most {}", n, MAX_BOARD_SIZE));
+    }
+    if config.m == Some(0) {
+        return Err("M must be at least 1".to_string());
+    }
+
+    let (m_min, m_max) = m_range(n);
+    let m = match (config.m, config.density) {
+        (Some(m), _) => m,
+        (None, Some(density)) => {
+            // 範囲が3より狭いときは、一部が重なってもいいので空にしない
+            let width = usize::max(1, (m_max - m_min + 1) / 3);
+            let low = usize::min(m_min + width * density as usize, m_max);
+            let high = if density == Density::High {
+                m_max
+            } else {
+                usize::min(low + width - 1, m_max)
+            };
+            rng.gen_range_inclusive(low, high)
+        }
+        (None, None) => rng.gen_range_inclusive(m_min, m_max),
+    };
+    // 置ける点の数を超えないようにする
+    let m = usize::min(m, n * n);
+
+    let mut used = vec![vec![false; n]; n];
+    let mut points: Vec<Pos> = vec![];
+    let mut push = |pos: Pos, points: &mut Vec<Pos>| -> bool {
+        if pos.x < 0 || pos.y < 0 || pos.x >= n as i32 || pos.y >= n as i32 {
+            return false;
+        }
+        if used[pos.y as usize][pos.x as usize] {
+            return false;
+        }
+        used[pos.y as usize][pos.x as usize] = true;
+        points.push(pos);
+        true
+    };
+
+    let (c_min, c_max) = central_range(n);
+    let central_size = (c_max - c_min + 1) * (c_max - c_min + 1);
+    let uniform_in = |rng: &mut Xorshift, low: usize, high: usize| Pos {
+        x: rng.gen_range_inclusive(low, high) as i32,
+        y: rng.gen_range_inclusive(low, high) as i32,
+    };
+
+    match config.layout {
+        Layout::Uniform => {
+            // 中央に入りきらない場合は盤面全体から選ぶ
+            let (low, high) = if m <= central_size {
+                (c_min, c_max)
+            } else {
+                (0, n - 1)
+            };
+            while points.len() < m {
+                let pos = uniform_in(&mut rng, low, high);
+                push(pos, &mut points);
+            }
+        }
+        Layout::Clustered { clusters, spread } => {
+            let centers: Vec<Pos> = (0..clusters)
+                .map(|_| uniform_in(&mut rng, c_min, c_max))
+                .collect();
+            let mut failed = 0;
+            while points.len() < m {
+                let center = centers[rng.gen_range_inclusive(0, clusters - 1)];
+                // 一様乱数の和で近似した正規分布
+                let offset = |rng: &mut Xorshift| {
+                    let z = (0..4).map(|_| rng.nextf()).sum::<f64>() - 2.;
+                    (z * spread * 3f64.sqrt()).round() as i32
+                };
+                let pos = Pos {
+                    x: center.x + offset(&mut rng),
+                    y: center.y + offset(&mut rng),
+                };
+                if !push(pos, &mut points) {
+                    failed += 1;
+                    // 密集しすぎて置けない場合は盤面全体から選ぶ
+                    if failed > 100 * m {
+                        let pos = uniform_in(&mut rng, 0, n - 1);
+                        push(pos, &mut points);
+                    }
+                }
+            }
+        }
+        Layout::CornerHeavy { ratio } => {
+            let corner_size = n / 4;
+            let corner_count = usize::min(
+                (m as f64 * ratio).round() as usize,
+                4 * corner_size * corner_size,
+            );
+            while points.len() < corner_count {
+                let mut pos = uniform_in(&mut rng, 0, corner_size - 1);
+                if rng.next() % 2 == 1 {
+                    pos.x = n as i32 - 1 - pos.x;
+                }
+                if rng.next() % 2 == 1 {
+                    pos.y = n as i32 - 1 - pos.y;
+                }
+                push(pos, &mut points);
+            }
+            let (low, high) = if m - corner_count <= central_size {
+                (c_min, c_max)
+            } else {
+                (0, n - 1)
+            };
+            while points.len() < m {
+                let pos = uniform_in(&mut rng, low, high);
+                push(pos, &mut points);
+            }
+        }
+    }
+
+    Ok(Input { n, points })
+}
+
+#[test]
+fn test_generate() {
+    for seed in 0..20 {
+        let input = generate(seed, &GeneratorConfig::official()).unwrap();
+        assert!(input.n % 2 == 1 && MIN_N <= input.n && input.n <= MAX_N);
+        let (m_min, m_max) = m_range(input.n);
+        assert!(m_min <= input.m() && input.m() <= m_max);
+
+        let (c_min, c_max) = central_range(input.n);
+        let mut points = input.points.clone();
+        points.sort();
+        points.dedup();
+        assert_eq!(points.len(), input.m());
+        for pos in &input.points {
+            assert!(c_min as i32 <= pos.x && pos.x <= c_max as i32);
+            assert!(c_min as i32 <= pos.y && pos.y <= c_max as i32);
+        }
+        assert_eq!(input, generate(seed, &GeneratorConfig::official()).unwrap());
+    }
+}
+
+#[test]
+fn test_generate_knobs() {
+    let mut config = GeneratorConfig {
+        n: Some(41),
+        m: None,
+        density: Some(Density::High),
+        layout: Layout::parse("corner:0.5").unwrap(),
+    };
+    let input = generate(0, &config).unwrap();
+    let (m_min, m_max) = m_range(41);
+    assert!(input.m() >= m_min + (m_max - m_min + 1) / 3 * 2);
+    let corner_count = input
+        .points
+        .iter()
+        .filter(|pos| (pos.x < 10 || pos.x > 30) && (pos.y < 10 || pos.y > 30))
+        .count();
+    assert!(corner_count * 2 >= input.m() - 1);
+
+    config.m = Some(200);
+    config.layout = Layout::parse("clustered:2,1.5").unwrap();
+    let input = generate(0, &config).unwrap();
+    assert_eq!(input.m(), 200);
+
+    assert!(Layout::parse("spiral").is_err());
+    assert!(Density::parse("none").is_err());
+}
+
+#[test]
+fn test_generate_small_n() {
+    for n in 1..12 {
+        for density in [
+            None,
+            Some(Density::Low),
+            Some(Density::Mid),
+            Some(Density::High),
+        ] {
+            let config = GeneratorConfig {
+                n: Some(n),
+                density,
+                ..GeneratorConfig::official()
+            };
+            let input = generate(0, &config).unwrap();
+            let (m_min, m_max) = m_range(n);
+            assert!(m_min <= input.m() && input.m() <= m_max);
+            assert_eq!(Input::parse(&input.to_string()), Ok(input));
+        }
+    }
+    let mut config = GeneratorConfig::official();
+    config.n = Some(0);
+    assert!(generate(0, &config).is_err());
+    config.n = Some(MAX_BOARD_SIZE + 1);
+    assert!(generate(0, &config).is_err());
+    config.n = Some(5);
+    config.m = Some(0);
+    assert!(generate(0, &config).is_err());
+}
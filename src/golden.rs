@@ -0,0 +1,97 @@
+// golden/in の入力をループ回数を決めて解き、作った四角の列とスコアを golden/out と比べる
+// 探索の結果が変わるのが意図した変更なら、UPDATE_GOLDEN=1 cargo test golden で作り直す
+use crate::config::*;
+use crate::framework::*;
+use crate::grid::*;
+use crate::input::*;
+use crate::solver::*;
+use crate::util::*;
+use crate::weight::*;
+use std::fs;
+use std::path::PathBuf;
+
+const ITERATION_LIMIT: usize = 20000;
+const SEED: u64 = 0;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+// 1行目がスコア、残りは提出する出力
+pub fn solve_golden(input: &Input) -> String {
+    let mut config = SolverConfig::new();
+    config.iteration_limit = Some(ITERATION_LIMIT);
+    rnd::seed(SEED);
+    let mut solver = Solver::from_input(
+        input,
+        Grid::new(input.n),
+        WeightMap::center_distance(input.n, input.n),
+        config.clone(),
+    );
+    solver.solve(config.time_limit);
+    let report = solver.report(input.m());
+    format!("score {}\n{}", report.real_score, solver.output())
+}
+
+// 一致しなければ、最初に食い違った行を返す
+fn describe_mismatch(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let (expected_lines, actual_lines): (Vec<&str>, Vec<&str>) =
+        (expected.lines().collect(), actual.lines().collect());
+    let i = (0..)
+        .find(|&i| expected_lines.get(i) != actual_lines.get(i))
+        .unwrap();
+    Some(format!(
+        "line {}: expected {:?}, found {:?}",
+        i + 1,
+        expected_lines.get(i).unwrap_or(&"<end>"),
+        actual_lines.get(i).unwrap_or(&"<end>")
+    ))
+}
+
+#[test]
+fn test_golden() {
+    let update = std::env::var("UPDATE_GOLDEN").is_ok_and(|v| !v.is_empty() && v != "0");
+    let mut names: Vec<String> = fs::read_dir(golden_dir().join("in"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    let mut mismatches = vec![];
+    for name in &names {
+        let input = Input::parse(&fs::read_to_string(golden_dir().join("in").join(name)).unwrap())
+            .unwrap_or_else(|e| panic!("golden/in/{}: {:?}", name, e));
+        let actual = solve_golden(&input);
+        let path = golden_dir().join("out").join(name);
+        if update {
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        if let Some(mismatch) = describe_mismatch(&expected, &actual) {
+            mismatches.push(format!("golden/out/{}: {}", name, mismatch));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "search results changed (run UPDATE_GOLDEN=1 cargo test golden if intended):\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn test_describe_mismatch() {
+    assert_eq!(describe_mismatch("score 1\n0\n", "score 1\n0\n"), None);
+    assert_eq!(
+        describe_mismatch("score 1\n0\n", "score 2\n0\n"),
+        Some("line 1: expected \"score 1\", found \"score 2\"".to_string())
+    );
+    assert_eq!(
+        describe_mismatch("score 1\n", "score 1\n1\n"),
+        Some("line 2: expected \"<end>\", found \"1\"".to_string())
+    );
+}
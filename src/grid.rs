@@ -1,19 +1,132 @@
 use crate::def::*;
+use crate::journal::*;
+use crate::point_set::*;
+use std::fmt;
+
+// 二点を繋げない理由
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectError {
+    Blocked(Pos),
+    PointOnSide(Pos),
+    EdgeOverlap(Pos),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectError::Blocked(pos) => {
+                write!(f, "side passes blocked cell ({}, {})", pos.x, pos.y)
+            }
+            ConnectError::PointOnSide(pos) => {
+                write!(f, "point ({}, {}) lies on a side", pos.x, pos.y)
+            }
+            ConnectError::EdgeOverlap(pos) => {
+                write!(
+                    f,
+                    "side overlaps an existing edge at ({}, {})",
+                    pos.x, pos.y
+                )
+            }
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Grid {
-    pub size: usize,
+    pub width: usize,
+    pub height: usize,
     pub points: Vec<Vec<Option<Point>>>,
     pub edges: Vec<Vec<Vec<bool>>>,
+    // pointsで点があるマスの一覧
+    pub point_set: PointSet,
+    // 各行、各列の両端にある点の座標。点がなければNone
+    pub row_ends: Vec<Option<(i32, i32)>>,
+    pub column_ends: Vec<Option<(i32, i32)>>,
+    // 点も辺も置けないマス
+    pub blocked: Vec<Vec<bool>>,
+    pub journal: Journal,
 }
 
 impl Grid {
     pub fn new(n: usize) -> Grid {
+        Grid::with_board(n, n, &[])
+    }
+
+    pub fn with_board(width: usize, height: usize, blocked_cells: &[Pos]) -> Grid {
+        let mut blocked = vec![vec![false; width]; height];
+        for pos in blocked_cells {
+            blocked[pos.y as usize][pos.x as usize] = true;
+        }
         Grid {
-            size: n,
-            points: vec![vec![None; n]; n],
-            edges: vec![vec![vec![false; DIR_MAX]; n]; n],
+            width,
+            height,
+            points: vec![vec![None; width]; height],
+            edges: vec![vec![vec![false; DIR_MAX]; width]; height],
+            point_set: PointSet::new(width, height),
+            row_ends: vec![None; height],
+            column_ends: vec![None; width],
+            blocked,
+            journal: Journal::new(),
+        }
+    }
+
+    // 1行目に"W H"、続くH行にW文字の'.'(置ける)か'#'(置けない)で、i行目がy=iを表す
+    pub fn parse_board(s: &str) -> Result<Grid, String> {
+        let mut lines = s.lines().filter(|line| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .ok_or_else(|| "missing board header".to_string())?;
+        let v = header
+            .split_whitespace()
+            .map(|v| v.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|e| format!("line 1: {}", e))?;
+        if v.len() != 2 || v[0] == 0 || v[1] == 0 {
+            return Err(format!("line 1: invalid board size {:?}", header));
+        }
+        let (width, height) = (v[0], v[1]);
+
+        let mut blocked_cells = vec![];
+        for y in 0..height {
+            let line = lines
+                .next()
+                .ok_or_else(|| format!("expected {} rows, found {}", height, y))?
+                .trim();
+            if line.chars().count() != width {
+                return Err(format!("line {}: expected {} cells", y + 2, width));
+            }
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '#' => blocked_cells.push(Pos {
+                        x: x as i32,
+                        y: y as i32,
+                    }),
+                    _ => return Err(format!("line {}: invalid cell {:?}", y + 2, c)),
+                }
+            }
+        }
+        Ok(Grid::with_board(width, height, &blocked_cells))
+    }
+
+    // 同じ盤面で、点と辺がない状態
+    pub fn cleared(&self) -> Grid {
+        Grid::with_board(self.width, self.height, &self.blocked_cells())
+    }
+
+    pub fn blocked_cells(&self) -> Vec<Pos> {
+        let mut blocked_cells = vec![];
+        for (y, row) in self.blocked.iter().enumerate() {
+            for (x, &is_blocked) in row.iter().enumerate() {
+                if is_blocked {
+                    blocked_cells.push(Pos {
+                        x: x as i32,
+                        y: y as i32,
+                    });
+                }
+            }
         }
+        blocked_cells
     }
 
     pub fn point(&mut self, pos: &Pos) -> &mut Option<Point> {
@@ -21,23 +134,30 @@ impl Grid {
     }
 
     pub fn can_connect(&self, a: &Pos, b: &Pos) -> bool {
+        self.check_connect(a, b).is_ok()
+    }
+
+    pub fn check_connect(&self, a: &Pos, b: &Pos) -> Result<(), ConnectError> {
         debug_assert!(Pos::is_aligned(a, b));
         let dir = Pos::get_dir(a, b);
         if self.has_edge(a, &dir) {
-            return false;
+            return Err(ConnectError::EdgeOverlap(*a));
         }
         for p in Pos::between(a, b) {
+            if self.is_blocked(&p) {
+                return Err(ConnectError::Blocked(p));
+            }
             if self.has_point(&p) {
-                return false;
+                return Err(ConnectError::PointOnSide(p));
             }
             if self.has_edge(&p, &dir) || self.has_edge(&p, &dir.rev()) {
-                return false;
+                return Err(ConnectError::EdgeOverlap(p));
             }
         }
         if self.has_edge(b, &dir.rev()) {
-            return false;
+            return Err(ConnectError::EdgeOverlap(*b));
         }
-        return true;
+        Ok(())
     }
 
     fn connect(&mut self, a: &Pos, b: &Pos, is_reverse: bool) {
@@ -68,11 +188,7 @@ impl Grid {
 
     pub fn create_square(&mut self, square: &Square, is_reverse: bool) {
         // 点を追加する
-        self.add_point(
-            &square.new_pos,
-            Point::new(&square.new_pos),
-            Some(square.clone()),
-        );
+        self.add_point(&square.new_pos, Point::new(&square.new_pos), Some(*square));
 
         // 辺を追加する
         self.connect(&square.connect[0], &square.new_pos, is_reverse);
@@ -103,47 +219,136 @@ impl Grid {
     }
 
     pub fn remove_point(&mut self, pos: &Pos) {
-        debug_assert!(self.has_point(&pos));
-        let nearest_points = self.point(&pos).as_ref().unwrap().nearest_points.clone();
+        debug_assert!(self.has_point(pos));
+        let nearest_points = self.point(pos).as_ref().unwrap().nearest_points;
         for i in 0..DIR_MAX {
             let dir = Dir::from_i32(i as i32);
             if let Some(nearest_pos) = &nearest_points[dir.val() as usize] {
-                debug_assert!(self.has_point(&nearest_pos));
-
-                if let Some(opposite_nearest_pos) = &nearest_points[dir.rev().val() as usize] {
-                    self.point(nearest_pos).as_mut().unwrap().nearest_points
-                        [dir.rev().val() as usize] = Some(opposite_nearest_pos.clone());
-                } else {
-                    self.point(nearest_pos).as_mut().unwrap().nearest_points
-                        [dir.rev().val() as usize] = None;
-                }
+                debug_assert!(self.has_point(nearest_pos));
+
+                self.set_nearest_point(
+                    nearest_pos,
+                    &dir.rev(),
+                    nearest_points[dir.rev().val() as usize],
+                );
             }
         }
-        self.points[pos.y as usize][pos.x as usize] = None;
+        let old = self.points[pos.y as usize][pos.x as usize].take();
+        let index = self.point_set.remove(pos);
+        self.journal.record(Change::Point {
+            pos: *pos,
+            old,
+            index,
+        });
+        self.update_ends_on_remove(pos);
     }
 
     pub fn add_point(&mut self, pos: &Pos, mut point: Point, square: Option<Square>) {
-        debug_assert!(!self.has_point(&pos));
+        debug_assert!(!self.has_point(pos));
 
         for i in 0..DIR_MAX {
             let dir = Dir::from_i32(i as i32);
-            if let Some(nearest_pos) = self.nearest_point_pos(&pos, &dir) {
-                let nearest_point = self.point(&nearest_pos).as_mut().unwrap();
-
-                nearest_point.nearest_points[dir.rev().val() as usize] = Some(pos.clone());
-                point.nearest_points[dir.val() as usize] = Some(nearest_pos.clone());
+            if let Some(nearest_pos) = self.nearest_point_pos(pos, &dir) {
+                self.set_nearest_point(&nearest_pos, &dir.rev(), Some(*pos));
+                point.nearest_points[dir.val() as usize] = Some(nearest_pos);
             }
         }
+        if let Some(square) = &square {
+            // is_reverseのときは頂点があるか確かめていないので、ある点だけ見る
+            point.depth = [square.connect[0], square.diagonal, square.connect[1]]
+                .iter()
+                .filter_map(|pos| self.points[pos.y as usize][pos.x as usize].as_ref())
+                .map(|point| point.depth + 1)
+                .max()
+                .unwrap_or(1);
+        }
         point.added_info = square;
         self.points[pos.y as usize][pos.x as usize] = Some(point);
+        self.point_set.insert(pos);
+        self.journal.record(Change::Point {
+            pos: *pos,
+            old: None,
+            index: self.point_set.len() - 1,
+        });
+        self.update_ends_on_add(pos);
+    }
+
+    fn update_ends_on_add(&mut self, pos: &Pos) {
+        let extend = |ends: Option<(i32, i32)>, v: i32| match ends {
+            Some((low, high)) => Some((i32::min(low, v), i32::max(high, v))),
+            None => Some((v, v)),
+        };
+        self.set_row_ends(pos.y as usize, extend(self.row_ends[pos.y as usize], pos.x));
+        self.set_column_ends(
+            pos.x as usize,
+            extend(self.column_ends[pos.x as usize], pos.y),
+        );
+    }
+
+    // 端の点が消えたときだけ、その行と列を見直す
+    fn update_ends_on_remove(&mut self, pos: &Pos) {
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        if let Some((left, right)) = self.row_ends[y] {
+            if pos.x == left || pos.x == right {
+                let row = &self.points[y];
+                let ends = row
+                    .iter()
+                    .position(Option::is_some)
+                    .zip(row.iter().rposition(Option::is_some))
+                    .map(|(left, right)| (left as i32, right as i32));
+                self.set_row_ends(y, ends);
+            }
+        }
+        if let Some((top, bottom)) = self.column_ends[x] {
+            if pos.y == top || pos.y == bottom {
+                let has_point = |y: &usize| self.points[*y][x].is_some();
+                let ends = (0..self.height)
+                    .find(has_point)
+                    .zip((0..self.height).rev().find(has_point))
+                    .map(|(top, bottom)| (top as i32, bottom as i32));
+                self.set_column_ends(x, ends);
+            }
+        }
+    }
+
+    fn set_row_ends(&mut self, y: usize, ends: Option<(i32, i32)>) {
+        let old = std::mem::replace(&mut self.row_ends[y], ends);
+        self.journal.record(Change::RowEnds { y, old });
+    }
+
+    fn set_column_ends(&mut self, x: usize, ends: Option<(i32, i32)>) {
+        let old = std::mem::replace(&mut self.column_ends[x], ends);
+        self.journal.record(Change::ColumnEnds { x, old });
+    }
+
+    fn set_nearest_point(&mut self, pos: &Pos, dir: &Dir, nearest_pos: Option<Pos>) {
+        let nearest_points = &mut self.point(pos).as_mut().unwrap().nearest_points;
+        let old = nearest_points[dir.val() as usize];
+        nearest_points[dir.val() as usize] = nearest_pos;
+        self.journal.record(Change::NearestPoint {
+            pos: *pos,
+            dir: *dir,
+            old,
+        });
     }
 
     fn add_edge(&mut self, pos: &Pos, dir: &Dir) {
-        self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = true;
+        self.set_edge(pos, dir, true);
     }
 
     fn remove_edge(&mut self, pos: &Pos, dir: &Dir) {
-        self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = false;
+        self.set_edge(pos, dir, false);
+    }
+
+    fn set_edge(&mut self, pos: &Pos, dir: &Dir, value: bool) {
+        let edge = &mut self.edges[pos.y as usize][pos.x as usize][dir.val() as usize];
+        let old = *edge;
+        *edge = value;
+        self.journal.record(Change::Edge {
+            pos: *pos,
+            dir: *dir,
+            old,
+        });
     }
 
     pub fn has_point(&self, pos: &Pos) -> bool {
@@ -151,14 +356,37 @@ impl Grid {
     }
 
     pub fn has_edge(&self, pos: &Pos, dir: &Dir) -> bool {
-        self.edges[pos.y as usize][pos.x as usize][dir.val() as usize].clone()
+        self.edges[pos.y as usize][pos.x as usize][dir.val() as usize]
+    }
+
+    // 各行、各列の両端にある点。これより外側に点はない
+    pub fn frontier_points(&self) -> Vec<Pos> {
+        let mut frontier = vec![];
+        for (y, ends) in self.row_ends.iter().enumerate() {
+            if let Some((left, right)) = *ends {
+                let y = y as i32;
+                frontier.push(Pos { x: left, y });
+                frontier.push(Pos { x: right, y });
+            }
+        }
+        for (x, ends) in self.column_ends.iter().enumerate() {
+            if let Some((top, bottom)) = *ends {
+                let x = x as i32;
+                frontier.push(Pos { x, y: top });
+                frontier.push(Pos { x, y: bottom });
+            }
+        }
+        frontier.sort();
+        frontier.dedup();
+        frontier
     }
 
     pub fn nearest_point_pos(&self, from: &Pos, dir: &Dir) -> Option<Pos> {
-        let mut cur = from.clone();
+        let mut cur = *from;
 
-        // 最大でもself.size回loopを回せばいい
-        for _ in 0..self.size {
+        // 最大でもmax(width, height)回loopを回せばいい
+        // 置けないマスより先の点は繋げないので、見えないものとする
+        for _ in 0..usize::max(self.width, self.height) {
             cur += &dir.to_pos();
             if !self.is_valid(&cur) {
                 break;
@@ -167,28 +395,183 @@ impl Grid {
                 return Some(cur);
             }
         }
-        return None;
+        None
     }
 
     fn unregister_created_points(&mut self, a: &Pos, target: &Pos) {
-        let created_points = &self.point(&a).as_mut().unwrap().created_points;
+        let created_points = &self.point(a).as_mut().unwrap().created_points;
         let target_index = created_points.iter().position(|x| *x == *target).unwrap();
-        self.point(&a)
+        self.point(a)
             .as_mut()
             .unwrap()
             .created_points
             .remove(target_index);
+        self.journal.record(Change::CreatedPointRemove {
+            pos: *a,
+            index: target_index,
+            target: *target,
+        });
     }
 
     fn register_created_points(&mut self, a: &Pos, target: &Pos) {
-        self.point(&a)
-            .as_mut()
-            .unwrap()
-            .created_points
-            .push(target.clone());
+        self.point(a).as_mut().unwrap().created_points.push(*target);
+        self.journal.record(Change::CreatedPointPush { pos: *a });
     }
 
+    // 記録された変更を一つ戻す
+    pub fn undo(&mut self, change: Change) {
+        match change {
+            Change::Edge { pos, dir, old } => {
+                self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = old;
+            }
+            Change::Point { pos, old, index } => {
+                match (self.has_point(&pos), old.is_some()) {
+                    (true, false) => {
+                        // 追加した点は最後にあるので、取り除いても他の点の並び順は変わらない
+                        let removed = self.point_set.remove(&pos);
+                        debug_assert_eq!(removed, index);
+                    }
+                    (false, true) => self.point_set.insert_at(&pos, index),
+                    _ => {}
+                }
+                self.points[pos.y as usize][pos.x as usize] = old;
+            }
+            Change::RowEnds { y, old } => {
+                self.row_ends[y] = old;
+            }
+            Change::ColumnEnds { x, old } => {
+                self.column_ends[x] = old;
+            }
+            Change::NearestPoint { pos, dir, old } => {
+                self.point(&pos).as_mut().unwrap().nearest_points[dir.val() as usize] = old;
+            }
+            Change::CreatedPointPush { pos } => {
+                self.point(&pos).as_mut().unwrap().created_points.pop();
+            }
+            Change::CreatedPointRemove { pos, index, target } => {
+                self.point(&pos)
+                    .as_mut()
+                    .unwrap()
+                    .created_points
+                    .insert(index, target);
+            }
+            Change::SquarePush | Change::SquareRemove { .. } | Change::Score { .. } => {
+                panic!("Change {:?} is not a grid change.", change)
+            }
+        }
+    }
+
+    pub fn is_inside(&self, pos: &Pos) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width as i32 && pos.y < self.height as i32
+    }
+
+    pub fn is_blocked(&self, pos: &Pos) -> bool {
+        self.blocked[pos.y as usize][pos.x as usize]
+    }
+
+    // 盤面の中で、置けないマスではない
     pub fn is_valid(&self, pos: &Pos) -> bool {
-        pos.x >= 0 && pos.y >= 0 && pos.x < self.size as i32 && pos.y < self.size as i32
+        self.is_inside(pos) && !self.is_blocked(pos)
+    }
+}
+
+#[test]
+fn test_parse_board() {
+    let grid = Grid::parse_board("4 2\n.#..\n...#\n").unwrap();
+    assert_eq!((grid.width, grid.height), (4, 2));
+    assert_eq!(
+        grid.blocked_cells(),
+        vec![Pos { x: 1, y: 0 }, Pos { x: 3, y: 1 }]
+    );
+    assert!(!grid.is_valid(&Pos { x: 1, y: 0 }));
+    assert!(grid.is_valid(&Pos { x: 3, y: 0 }));
+    assert_eq!(grid.cleared(), grid);
+
+    assert!(Grid::parse_board("4 2\n.#..\n").is_err());
+    assert!(Grid::parse_board("4 2\n.#..\n..x.\n").is_err());
+    assert!(Grid::parse_board("4\n").is_err());
+}
+
+#[test]
+fn test_frontier_points() {
+    let mut grid = Grid::new(5);
+    for pos in [
+        Pos { x: 1, y: 1 },
+        Pos { x: 2, y: 2 },
+        Pos { x: 3, y: 2 },
+        Pos { x: 4, y: 2 },
+        Pos { x: 2, y: 3 },
+    ] {
+        grid.add_point(&pos, Point::new(&pos), None);
+    }
+    // (3, 2)は左右を点に挟まれているが、その列の唯一の点なので外周に含まれる
+    assert_eq!(
+        grid.frontier_points(),
+        vec![
+            Pos { x: 1, y: 1 },
+            Pos { x: 2, y: 2 },
+            Pos { x: 2, y: 3 },
+            Pos { x: 3, y: 2 },
+            Pos { x: 4, y: 2 },
+        ]
+    );
+}
+
+#[test]
+fn test_frontier_points_after_rollback() {
+    use crate::util::rnd;
+
+    // 盤面全体を見て求めた外周
+    let rescan = |grid: &Grid| {
+        let mut frontier = vec![];
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                let pos = Pos {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                let on_row = |x: usize| grid.points[y][x].is_some();
+                let on_column = |y: usize| grid.points[y][x].is_some();
+                if on_row(x)
+                    && (!(0..x).any(on_row)
+                        || !(x + 1..grid.width).any(on_row)
+                        || !(0..y).any(on_column)
+                        || !(y + 1..grid.height).any(on_column))
+                {
+                    frontier.push(pos);
+                }
+            }
+        }
+        frontier.sort();
+        frontier
+    };
+
+    rnd::seed(0);
+    let mut grid = Grid::with_board(7, 5, &[Pos { x: 3, y: 2 }]);
+    for _ in 0..200 {
+        let before = grid.clone();
+        grid.journal.begin();
+        for _ in 0..rnd::gen_range(1, 6) {
+            let pos = Pos {
+                x: rnd::gen_range(0, grid.width) as i32,
+                y: rnd::gen_range(0, grid.height) as i32,
+            };
+            if grid.has_point(&pos) {
+                grid.remove_point(&pos);
+            } else if !grid.is_blocked(&pos) {
+                grid.add_point(&pos, Point::new(&pos), None);
+            }
+            assert_eq!(grid.frontier_points(), rescan(&grid));
+        }
+        if rnd::nextf() < 0.5 {
+            for change in grid.journal.pop_transaction() {
+                grid.undo(change);
+            }
+            assert_eq!(grid, before);
+            assert_eq!(grid.point_set.positions(), before.point_set.positions());
+        } else {
+            grid.journal.commit();
+        }
+        assert_eq!(grid.frontier_points(), rescan(&grid));
     }
 }
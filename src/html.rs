@@ -0,0 +1,314 @@
+use crate::def::*;
+use crate::scorer::*;
+use crate::state::*;
+use crate::svg::*;
+use crate::telemetry::*;
+use crate::util::*;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const REPLAY_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>AHC014 replay</title>
+<style>
+body { font-family: sans-serif; margin: 16px; }
+#controls { margin: 8px 0; }
+#step { width: 480px; vertical-align: middle; }
+#info { font-family: monospace; white-space: pre; }
+</style>
+</head>
+<body>
+<canvas id="board"></canvas>
+<div id="controls">
+<button id="prev">&lt;</button>
+<input id="step" type="range" min="0" value="0">
+<button id="next">&gt;</button>
+<button id="play">play</button>
+</div>
+<div id="info"></div>
+<script>
+const DATA = /*DATA*/;
+const CELL = Math.max(6, Math.floor(600 / Math.max(DATA.width, DATA.height)));
+const MARGIN = 10;
+const canvas = document.getElementById("board");
+const ctx = canvas.getContext("2d");
+const slider = document.getElementById("step");
+const info = document.getElementById("info");
+canvas.width = 2 * MARGIN + (DATA.width - 1) * CELL;
+canvas.height = 2 * MARGIN + (DATA.height - 1) * CELL;
+slider.max = DATA.squares.length;
+
+const px = (x) => MARGIN + x * CELL;
+const py = (y) => MARGIN + (DATA.height - 1 - y) * CELL;
+const fmt = (p) => "(" + p[0] + ", " + p[1] + ")";
+const corners = (s) => [[s[0], s[1]], [s[2], s[3]], [s[4], s[5]], [s[6], s[7]]];
+
+function circle(p, r, fill, stroke) {
+  ctx.beginPath();
+  ctx.arc(px(p[0]), py(p[1]), r, 0, 2 * Math.PI);
+  ctx.fillStyle = fill;
+  ctx.fill();
+  ctx.strokeStyle = stroke;
+  ctx.lineWidth = 1.5;
+  ctx.stroke();
+}
+
+function polygon(s, color, width) {
+  const c = corners(s);
+  ctx.beginPath();
+  ctx.moveTo(px(c[0][0]), py(c[0][1]));
+  for (let i = 1; i < 4; i++) ctx.lineTo(px(c[i][0]), py(c[i][1]));
+  ctx.closePath();
+  ctx.strokeStyle = color;
+  ctx.lineWidth = width;
+  ctx.stroke();
+}
+
+function draw(step) {
+  ctx.fillStyle = "white";
+  ctx.fillRect(0, 0, canvas.width, canvas.height);
+  ctx.strokeStyle = "#ddd";
+  ctx.lineWidth = 0.5;
+  for (let x = 0; x < DATA.width; x++) {
+    ctx.beginPath(); ctx.moveTo(px(x), py(0)); ctx.lineTo(px(x), py(DATA.height - 1)); ctx.stroke();
+  }
+  for (let y = 0; y < DATA.height; y++) {
+    ctx.beginPath(); ctx.moveTo(px(0), py(y)); ctx.lineTo(px(DATA.width - 1), py(y)); ctx.stroke();
+  }
+  ctx.fillStyle = "#444";
+  for (const p of DATA.blocked) ctx.fillRect(px(p[0]) - CELL / 2, py(p[1]) - CELL / 2, CELL, CELL);
+
+  for (let i = 0; i < step - 1; i++) polygon(DATA.squares[i], "#1f77b4", 1);
+  const r = CELL * 0.25;
+  for (const p of DATA.initial) circle(p, r, "#000", "#000");
+  for (let i = 0; i < step - 1; i++) circle(corners(DATA.squares[i])[0], r, "#fff", "#333");
+
+  let text = "step " + step + " / " + DATA.squares.length + "\nscore " + DATA.scores[step];
+  if (step > 0) {
+    const s = DATA.squares[step - 1];
+    const c = corners(s);
+    polygon(s, "#d62728", 2.5);
+    for (let i = 1; i < 4; i++) circle(c[i], r * 1.4, "#2ca02c", "#2ca02c");
+    circle(c[0], r * 1.4, "#d62728", "#d62728");
+    text += "\nadded " + fmt(c[0]) + " from " + fmt(c[1]) + " " + fmt(c[2]) + " " + fmt(c[3]);
+  }
+  info.textContent = text;
+}
+
+function setStep(step) {
+  slider.value = Math.max(0, Math.min(DATA.squares.length, step));
+  draw(Number(slider.value));
+}
+
+let timer = null;
+slider.addEventListener("input", () => draw(Number(slider.value)));
+document.getElementById("prev").addEventListener("click", () => setStep(Number(slider.value) - 1));
+document.getElementById("next").addEventListener("click", () => setStep(Number(slider.value) + 1));
+document.getElementById("play").addEventListener("click", () => {
+  if (timer !== null) { clearInterval(timer); timer = null; return; }
+  if (Number(slider.value) >= DATA.squares.length) setStep(0);
+  timer = setInterval(() => {
+    if (Number(slider.value) >= DATA.squares.length) { clearInterval(timer); timer = null; return; }
+    setStep(Number(slider.value) + 1);
+  }, 100);
+});
+document.addEventListener("keydown", (e) => {
+  if (e.key === "ArrowLeft") setStep(Number(slider.value) - 1);
+  if (e.key === "ArrowRight") setStep(Number(slider.value) + 1);
+});
+setStep(0);
+</script>
+</body>
+</html>
+"##;
+
+fn pos_list(positions: &[Pos]) -> String {
+    let items: Vec<String> = positions
+        .iter()
+        .map(|pos| format!("[{},{}]", pos.x, pos.y))
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+// squaresの順に追加していったときの各時点のスコア（先頭は追加前）
+pub fn replay_scores(state: &State) -> Vec<i32> {
+    let mut replayed = state.replay(&[]);
+    let mut scores = vec![score_state(&replayed)];
+    for square in &state.squares {
+        replayed.perform_add(square, false);
+        scores.push(score_state(&replayed));
+    }
+    scores
+}
+
+// JSに埋め込むデータ。四角は出力と同じ順で[new_pos, connect[0], diagonal, connect[1]]
+pub fn replay_data(state: &State) -> String {
+    let squares: Vec<String> = state
+        .squares
+        .iter()
+        .map(|square| {
+            let corners: Vec<String> = square
+                .all_pos()
+                .iter()
+                .map(|pos| format!("{},{}", pos.x, pos.y))
+                .collect();
+            format!("[{}]", corners.join(","))
+        })
+        .collect();
+    let scores: Vec<String> = replay_scores(state)
+        .iter()
+        .map(|score| score.to_string())
+        .collect();
+
+    let mut data = String::new();
+    write!(
+        data,
+        r#"{{"width":{},"height":{},"blocked":{},"initial":{},"squares":[{}],"scores":[{}]}}"#,
+        state.grid.width,
+        state.grid.height,
+        pos_list(&state.grid.blocked_cells()),
+        pos_list(&state.initial_points()),
+        squares.join(","),
+        scores.join(","),
+    )
+    .unwrap();
+    data
+}
+
+// stateのsquaresを先頭から一つずつ追加していくHTMLを作る
+pub fn render_replay(state: &State) -> String {
+    REPLAY_TEMPLATE.replace("/*DATA*/", &replay_data(state))
+}
+
+const PLAYER_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>AHC014 trajectory</title>
+<style>
+body { font-family: sans-serif; margin: 16px; }
+#controls { margin: 8px 0; }
+#frame-slider { width: 480px; vertical-align: middle; }
+#info { font-family: monospace; white-space: pre; }
+</style>
+</head>
+<body>
+<img id="frame">
+<div id="controls">
+<button id="prev">&lt;</button>
+<input id="frame-slider" type="range" min="0" value="0">
+<button id="next">&gt;</button>
+<button id="play">play</button>
+</div>
+<div id="info"></div>
+<script>
+const FRAMES = /*FRAMES*/;
+const image = document.getElementById("frame");
+const slider = document.getElementById("frame-slider");
+const info = document.getElementById("info");
+slider.max = FRAMES.length - 1;
+
+function show(i) {
+  slider.value = Math.max(0, Math.min(FRAMES.length - 1, i));
+  const frame = FRAMES[Number(slider.value)];
+  image.src = frame.file;
+  info.textContent = "frame " + slider.value + " / " + (FRAMES.length - 1) +
+    "\niteration " + frame.iteration + "\ntemp " + frame.temp.toFixed(2) +
+    "\nscore " + frame.score + "\nsquares " + frame.squares;
+}
+
+let timer = null;
+slider.addEventListener("input", () => show(Number(slider.value)));
+document.getElementById("prev").addEventListener("click", () => show(Number(slider.value) - 1));
+document.getElementById("next").addEventListener("click", () => show(Number(slider.value) + 1));
+document.getElementById("play").addEventListener("click", () => {
+  if (timer !== null) { clearInterval(timer); timer = null; return; }
+  if (Number(slider.value) >= FRAMES.length - 1) show(0);
+  timer = setInterval(() => {
+    if (Number(slider.value) >= FRAMES.length - 1) { clearInterval(timer); timer = null; return; }
+    show(Number(slider.value) + 1);
+  }, 150);
+});
+document.addEventListener("keydown", (e) => {
+  if (e.key === "ArrowLeft") show(Number(slider.value) - 1);
+  if (e.key === "ArrowRight") show(Number(slider.value) + 1);
+});
+show(0);
+</script>
+</body>
+</html>
+"##;
+
+// 焼きなましの途中経過を、記録した時点ごとのSVGとそれを順に見るHTMLで書き出す
+impl Trajectory {
+    pub fn frame_file_name(index: usize) -> String {
+        format!("frame_{:05}.svg", index)
+    }
+
+    // dirにframe_xxxxx.svgとindex.htmlを書き出す
+    // baseは初期状態を作り直すためだけに使う
+    pub fn write(&self, dir: &str, base: &State, options: &SvgOptions) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let m = base.initial_points().len();
+        let mut frames: Vec<String> = vec![];
+        for (i, snapshot) in self.snapshots.iter().enumerate() {
+            let state = base.replay(&snapshot.squares);
+            let file = Trajectory::frame_file_name(i);
+            fs::write(Path::new(dir).join(&file), render_state(&state, options))?;
+
+            let mut frame = String::new();
+            write!(
+                frame,
+                r#"{{"file":"{}","iteration":{},"temp":{},"score":{},"squares":{}}}"#,
+                file,
+                snapshot.iteration,
+                snapshot.temp,
+                calc_real_score(&state.weight_map, m, snapshot.score),
+                snapshot.squares.len(),
+            )
+            .unwrap();
+            frames.push(frame);
+        }
+        let html = PLAYER_TEMPLATE.replace("/*FRAMES*/", &format!("[{}]", frames.join(",")));
+        fs::write(Path::new(dir).join("index.html"), html)
+    }
+}
+
+#[test]
+fn test_render_replay() {
+    let input = "5 4\n0 0\n2 0\n0 2\n2 4\n";
+    let state = crate::validator::validate_official(input, "1\n2 2 2 0 0 0 0 2\n").unwrap();
+    assert_eq!(replay_scores(&state), vec![1_200_000, 1_250_000]);
+
+    let data = replay_data(&state);
+    assert!(data.contains(r#""squares":[[2,2,0,2,0,0,2,0]]"#));
+    assert!(data.contains(r#""scores":[1200000,1250000]"#));
+
+    let html = render_replay(&state);
+    assert!(html.contains(&format!("const DATA = {};", data)));
+    assert!(!html.contains("http://") && !html.contains("https://"));
+}
+
+#[test]
+fn test_write_trajectory() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let mut state = State::new(5, vec![diagonal, connect[0], connect[1]]);
+    let mut trajectory = Trajectory::new(10);
+    trajectory.record(0, 100., &state);
+    state.perform_add(&Square::new(Pos { x: 2, y: 2 }, diagonal, connect), false);
+    trajectory.record(10, 50., &state);
+
+    let dir = std::env::temp_dir().join(format!("ahc014_trajectory_{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    trajectory.write(dir, &state, &SvgOptions::new()).unwrap();
+    let frame = fs::read_to_string(Path::new(dir).join(Trajectory::frame_file_name(1))).unwrap();
+    assert_eq!(frame.matches("<polygon").count(), 1);
+    let html = fs::read_to_string(Path::new(dir).join("index.html")).unwrap();
+    assert!(html.contains(r#""file":"frame_00001.svg","iteration":10,"temp":50"#));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,279 @@
+use crate::def::*;
+use std::fmt;
+
+// Nの上限。Nから配列を確保するので、壊れたヘッダで巨大な確保をしないようにする
+pub const MAX_BOARD_SIZE: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input {
+    pub n: usize,
+    pub points: Vec<Pos>,
+}
+
+// 入力のどの行が、なぜ読めなかったか
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputError {
+    MissingHeader,
+    BadHeader {
+        line: usize,
+        message: String,
+    },
+    Malformed {
+        line: usize,
+        message: String,
+    },
+    // 1行目のMと点の数が合わない
+    CountMismatch {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    OutOfRange {
+        line: usize,
+        pos: Pos,
+        width: usize,
+        height: usize,
+    },
+    Duplicate {
+        line: usize,
+        pos: Pos,
+        first_line: usize,
+    },
+}
+
+impl InputError {
+    pub fn line(&self) -> usize {
+        match self {
+            InputError::MissingHeader => 1,
+            InputError::BadHeader { line, .. }
+            | InputError::Malformed { line, .. }
+            | InputError::CountMismatch { line, .. }
+            | InputError::OutOfRange { line, .. }
+            | InputError::Duplicate { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line())?;
+        match self {
+            InputError::MissingHeader => write!(f, "missing header \"N M\""),
+            InputError::BadHeader { message, .. } => {
+                write!(f, "invalid header: {}", message)
+            }
+            InputError::Malformed { message, .. } => write!(f, "{}", message),
+            InputError::CountMismatch {
+                expected, found, ..
+            } => write!(f, "expected M = {} points, found {}", expected, found),
+            InputError::OutOfRange {
+                pos, width, height, ..
+            } => write!(
+                f,
+                "({}, {}) is outside the {}x{} board",
+                pos.x, pos.y, width, height
+            ),
+            InputError::Duplicate {
+                pos, first_line, ..
+            } => write!(
+                f,
+                "({}, {}) is duplicated (first on line {})",
+                pos.x, pos.y, first_line
+            ),
+        }
+    }
+}
+
+// 空行を飛ばして、(行番号, 整数列)を返す
+pub fn parse_lines(s: &str) -> impl Iterator<Item = (usize, Result<Vec<i32>, String>)> + '_ {
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let values = line
+                .split_whitespace()
+                .map(|v| {
+                    v.parse::<i32>()
+                        .map_err(|_| format!("{:?} is not an integer", v))
+                })
+                .collect::<Result<Vec<i32>, String>>();
+            (i + 1, values)
+        })
+}
+
+impl Input {
+    pub fn m(&self) -> usize {
+        self.points.len()
+    }
+
+    // 点はN×Nの盤面に収まっている必要がある
+    pub fn parse(s: &str) -> Result<Input, InputError> {
+        Input::parse_with_bounds(s, None)
+    }
+
+    // 盤面を別に与える場合は、点はwidth×heightに収まっていればいい
+    pub fn parse_within(s: &str, width: usize, height: usize) -> Result<Input, InputError> {
+        Input::parse_with_bounds(s, Some((width, height)))
+    }
+
+    fn parse_with_bounds(s: &str, bounds: Option<(usize, usize)>) -> Result<Input, InputError> {
+        let mut lines = parse_lines(s);
+        let (line, header) = lines.next().ok_or(InputError::MissingHeader)?;
+        let header = header.map_err(|message| InputError::BadHeader { line, message })?;
+        if header.len() != 2 {
+            return Err(InputError::BadHeader {
+                line,
+                message: format!("expected 2 integers, found {}", header.len()),
+            });
+        }
+        if header[0] <= 0 || header[1] <= 0 {
+            return Err(InputError::BadHeader {
+                line,
+                message: format!("N = {} and M = {} must be positive", header[0], header[1]),
+            });
+        }
+        let (n, m) = (header[0] as usize, header[1] as usize);
+        if n > MAX_BOARD_SIZE {
+            return Err(InputError::BadHeader {
+                line,
+                message: format!("N = {} must be at most {}", n, MAX_BOARD_SIZE),
+            });
+        }
+        let (width, height) = bounds.unwrap_or((n, n));
+        if m > width * height {
+            return Err(InputError::BadHeader {
+                line,
+                message: format!(
+                    "M = {} points do not fit on the {}x{} board",
+                    m, width, height
+                ),
+            });
+        }
+
+        let mut points: Vec<Pos> = vec![];
+        // 重複を見つけるための、各マスの点がある行番号
+        let mut lines_of_pos = vec![vec![0; width]; height];
+        let mut last_line = line;
+        for (line, values) in lines {
+            last_line = line;
+            let values = values.map_err(|message| InputError::Malformed { line, message })?;
+            if values.len() != 2 {
+                return Err(InputError::Malformed {
+                    line,
+                    message: format!("expected \"x y\", found {} integers", values.len()),
+                });
+            }
+            if points.len() == m {
+                return Err(InputError::CountMismatch {
+                    line,
+                    expected: m,
+                    found: m + 1,
+                });
+            }
+            let pos = Pos {
+                x: values[0],
+                y: values[1],
+            };
+            if pos.x < 0 || pos.y < 0 || pos.x >= width as i32 || pos.y >= height as i32 {
+                return Err(InputError::OutOfRange {
+                    line,
+                    pos,
+                    width,
+                    height,
+                });
+            }
+            let first_line = &mut lines_of_pos[pos.y as usize][pos.x as usize];
+            if *first_line != 0 {
+                return Err(InputError::Duplicate {
+                    line,
+                    pos,
+                    first_line: *first_line,
+                });
+            }
+            *first_line = line;
+            points.push(pos);
+        }
+        if points.len() != m {
+            return Err(InputError::CountMismatch {
+                line: last_line,
+                expected: m,
+                found: points.len(),
+            });
+        }
+        Ok(Input { n, points })
+    }
+}
+
+// 問題の入力形式で書き出す
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", self.n, self.m())?;
+        for pos in &self.points {
+            writeln!(f, "{} {}", pos.x, pos.y)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_input() {
+    let input = Input::parse("5 3\n0 0\n2 0\n\n4 4\n").unwrap();
+    assert_eq!(input.n, 5);
+    assert_eq!(input.points[2], Pos { x: 4, y: 4 });
+    assert_eq!(Input::parse(&input.to_string()), Ok(input));
+
+    assert_eq!(Input::parse(""), Err(InputError::MissingHeader));
+    assert!(matches!(
+        Input::parse("5\n"),
+        Err(InputError::BadHeader { line: 1, .. })
+    ));
+    assert!(matches!(
+        Input::parse("5 x\n"),
+        Err(InputError::BadHeader { line: 1, .. })
+    ));
+    assert!(matches!(
+        Input::parse("5 2\n0 0\n1 a\n"),
+        Err(InputError::Malformed { line: 3, .. })
+    ));
+    assert_eq!(
+        Input::parse("5 3\n0 0\n1 1\n"),
+        Err(InputError::CountMismatch {
+            line: 3,
+            expected: 3,
+            found: 2
+        })
+    );
+    assert_eq!(
+        Input::parse("5 1\n0 0\n1 1\n"),
+        Err(InputError::CountMismatch {
+            line: 3,
+            expected: 1,
+            found: 2
+        })
+    );
+    let e = Input::parse("5 2\n0 0\n5 1\n").unwrap_err();
+    assert_eq!(e.to_string(), "line 3: (5, 1) is outside the 5x5 board");
+    let e = Input::parse("5 3\n0 0\n1 1\n0 0\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "line 4: (0, 0) is duplicated (first on line 2)"
+    );
+
+    let e = Input::parse("5 0\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "line 1: invalid header: N = 5 and M = 0 must be positive"
+    );
+    let e = Input::parse("2000000000 1\n0 0\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "line 1: invalid header: N = 2000000000 must be at most 1000"
+    );
+    assert!(matches!(
+        Input::parse("2 5\n"),
+        Err(InputError::BadHeader { line: 1, .. })
+    ));
+
+    assert!(Input::parse("5 1\n6 2\n").is_err());
+    assert!(Input::parse_within("5 1\n6 2\n", 7, 3).is_ok());
+}
@@ -0,0 +1,105 @@
+use crate::def::*;
+
+// Grid、Stateに対する低レベルな変更の記録
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Edge {
+        pos: Pos,
+        dir: Dir,
+        old: bool,
+    },
+    // indexはPointSetでのposの添字。戻すときに並び順も元に戻す
+    Point {
+        pos: Pos,
+        old: Option<Point>,
+        index: usize,
+    },
+    NearestPoint {
+        pos: Pos,
+        dir: Dir,
+        old: Option<Pos>,
+    },
+    // 行yにある点のxの最小値と最大値
+    RowEnds {
+        y: usize,
+        old: Option<(i32, i32)>,
+    },
+    // 列xにある点のyの最小値と最大値
+    ColumnEnds {
+        x: usize,
+        old: Option<(i32, i32)>,
+    },
+    CreatedPointPush {
+        pos: Pos,
+    },
+    CreatedPointRemove {
+        pos: Pos,
+        index: usize,
+        target: Pos,
+    },
+    SquarePush,
+    SquareRemove {
+        index: usize,
+        square: Square,
+    },
+    Score {
+        old: i32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Journal {
+    changes: Vec<Change>,
+    // 各トランザクションの開始位置
+    marks: Vec<usize>,
+}
+
+impl Default for Journal {
+    fn default() -> Journal {
+        Journal::new()
+    }
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal {
+            changes: vec![],
+            marks: vec![],
+        }
+    }
+
+    // トランザクション中のみ記録する
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        !self.marks.is_empty()
+    }
+
+    #[inline]
+    pub fn record(&mut self, change: Change) {
+        if self.is_recording() {
+            self.changes.push(change);
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.marks.push(self.changes.len());
+    }
+
+    pub fn commit(&mut self) {
+        debug_assert!(self.is_recording());
+        self.marks.pop();
+        // 一番外側のトランザクションが確定したら、もう戻す必要はない
+        if self.marks.is_empty() {
+            self.changes.clear();
+        }
+    }
+
+    // 最後に開始したトランザクションの変更を、新しい順に取り出す
+    pub fn pop_transaction(&mut self) -> Vec<Change> {
+        debug_assert!(self.is_recording());
+        let mark = self.marks.pop().unwrap();
+        let mut changes = self.changes.split_off(mark);
+        changes.reverse();
+        changes
+    }
+}
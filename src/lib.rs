@@ -0,0 +1,37 @@
+// クレートの外からは、解く入口とCLI、ベンチマーク、設定、採点と検証だけを使う
+mod ascii;
+mod batch;
+mod chart;
+mod cli;
+mod compare;
+pub mod config;
+mod def;
+mod framework;
+mod generator;
+#[cfg(test)]
+mod golden;
+mod grid;
+mod html;
+mod input;
+mod journal;
+mod microbench;
+mod neighborhood;
+mod point_set;
+#[cfg(test)]
+mod property;
+mod report;
+mod sampler;
+pub mod scorer;
+mod solver;
+mod state;
+mod svg;
+mod telemetry;
+mod util;
+pub mod validator;
+mod weight;
+
+pub use cli::run;
+pub use input::Input;
+pub use microbench::run as run_microbench;
+pub use solver::solve;
+pub use state::State;
@@ -0,0 +1,233 @@
+// よく呼ばれる処理の1回あたりの時間を、固定seedの盤面で測る
+//
+// usage: cargo run --release --bin bench -- [--sizes 31,45,61] [--seed 0] [--min-time 0.3] [--filter name]
+use crate::config::*;
+use crate::def::*;
+use crate::generator::*;
+use crate::neighborhood::*;
+use crate::state::*;
+use crate::util::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+// 盤面を埋めるために、先に採用しておくAddの最大試行回数
+const WARMUP_ADDS: usize = 3000;
+// 測るときに順に使う入力の数
+const SAMPLE_COUNT: usize = 1024;
+
+// 測る対象の盤面と、各処理に渡す入力
+struct Fixture {
+    seed: u64,
+    state: State,
+    // 点と、ある方向の最も近い点の組
+    segments: Vec<(Pos, Pos)>,
+    rays: Vec<(Pos, Dir)>,
+    // この盤面に追加できる四角
+    addable: Vec<Square>,
+    config: SolverConfig,
+}
+
+impl Fixture {
+    fn new(n: usize, seed: u64) -> Fixture {
+        let mut generator_config = GeneratorConfig::official();
+        generator_config.n = Some(n);
+        let input = generate(seed, &generator_config).unwrap_or_else(|e| {
+            eprintln!("bench: {}", e);
+            std::process::exit(1);
+        });
+        let config = SolverConfig::new();
+
+        // 埋め切ると追加できる四角がなくなるので、初期点の半分の数だけ四角を作っておく
+        let square_count = input.m() / 2;
+        rnd::seed(seed);
+        let mut state = State::new(input.n, input.points);
+        for _ in 0..WARMUP_ADDS {
+            if state.squares.len() >= square_count {
+                break;
+            }
+            state.begin();
+            Neighborhood::Add.perform(&mut state, &config);
+            state.commit();
+        }
+
+        let mut segments = vec![];
+        let mut rays = vec![];
+        let mut addable = vec![];
+        for _ in 0..SAMPLE_COUNT {
+            let pos = state.sample_point_pos();
+            let dir = Dir::from_i32(rnd::gen_range(0, DIR_MAX) as i32);
+            rays.push((pos, dir));
+            if let Some(nearest_pos) = state.grid.nearest_point_pos(&pos, &dir) {
+                segments.push((pos, nearest_pos));
+            }
+
+            // 戻せば盤面は変わらないので、見つかった四角はどれも追加できる
+            state.begin();
+            for command in Neighborhood::Add.perform(&mut state, &config) {
+                if let Command::Add { square } = command {
+                    addable.push(square);
+                }
+            }
+            state.rollback();
+        }
+
+        Fixture {
+            seed,
+            state,
+            segments,
+            rays,
+            addable,
+            config,
+        }
+    }
+}
+
+// min_seconds以上になるまで、回数を倍にしながら実行する。(ns/op, 実行回数)を返す
+fn measure(min_seconds: f64, op: &mut dyn FnMut(usize)) -> (f64, usize) {
+    let start = Instant::now();
+    let mut ops = 0;
+    let mut batch = 1;
+    loop {
+        for _ in 0..batch {
+            op(ops);
+            ops += 1;
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        if elapsed >= min_seconds {
+            return (elapsed * 1e9 / ops as f64, ops);
+        }
+        batch *= 2;
+    }
+}
+
+// (名前, ns/op, 実行回数)を返す。filterを含む名前だけ測る
+fn run_benches(
+    fixture: &Fixture,
+    min_seconds: f64,
+    filter: Option<&str>,
+) -> Vec<(String, f64, usize)> {
+    let mut results = vec![];
+    // opにはcount未満の添字を渡す。入力がなければ測らない
+    let mut bench = |name: &str, count: usize, op: &mut dyn FnMut(usize)| {
+        if count == 0 || filter.is_some_and(|filter| !name.contains(filter)) {
+            return;
+        }
+        rnd::seed(fixture.seed);
+        let mut op = |i: usize| op(i % count);
+        measure(min_seconds / 10., &mut op);
+        let (ns_per_op, ops) = measure(min_seconds, &mut op);
+        results.push((name.to_string(), ns_per_op, ops));
+    };
+
+    let grid = &fixture.state.grid;
+    bench("Grid::can_connect", fixture.segments.len(), &mut |i| {
+        let (a, b) = &fixture.segments[i];
+        black_box(grid.can_connect(a, b));
+    });
+    bench("Grid::nearest_point_pos", fixture.rays.len(), &mut |i| {
+        let (pos, dir) = &fixture.rays[i];
+        black_box(grid.nearest_point_pos(pos, dir));
+    });
+    bench("Pos::between", fixture.segments.len(), &mut |i| {
+        let (a, b) = &fixture.segments[i];
+        black_box(Pos::between(a, b));
+    });
+
+    // 盤面を変える処理は、rollbackで戻す分も含めて測る
+    let mut state = fixture.state.clone();
+    bench("State::perform_add", fixture.addable.len(), &mut |i| {
+        state.begin();
+        black_box(state.perform_add(&fixture.addable[i], false));
+        state.rollback();
+    });
+    bench(
+        "State::perform_delete",
+        fixture.state.squares.len(),
+        &mut |i| {
+            let mut performed_commands = vec![];
+            state.begin();
+            state.perform_delete(&fixture.state.squares[i], &mut performed_commands);
+            black_box(performed_commands);
+            state.rollback();
+        },
+    );
+    bench("State::sample_point_pos", 1, &mut |_| {
+        black_box(state.sample_point_pos());
+    });
+    for neighborhood in Neighborhood::all() {
+        let name = format!("Neighborhood::{:?}", neighborhood);
+        bench(&name, 1, &mut |_| {
+            state.begin();
+            black_box(neighborhood.perform(&mut state, &fixture.config));
+            state.rollback();
+        });
+    }
+    bench("State::clone", 1, &mut |_| {
+        black_box(fixture.state.clone());
+    });
+    results
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("bench: invalid value {:?} for {}", value, name);
+        std::process::exit(1);
+    })
+}
+
+pub fn run(args: &[String]) {
+    let arg_value = |name: &str| {
+        let i = args.iter().position(|arg| arg == name)?;
+        args.get(i + 1).cloned()
+    };
+    let sizes: Vec<usize> = arg_value("--sizes")
+        .unwrap_or_else(|| "31,45,61".to_string())
+        .split(',')
+        .map(|n| parse("--sizes", n.trim()))
+        .collect();
+    let seed: u64 = arg_value("--seed").map_or(0, |v| parse("--seed", &v));
+    let min_seconds: f64 = arg_value("--min-time").map_or(0.3, |v| parse("--min-time", &v));
+    let filter = arg_value("--filter");
+
+    for n in sizes {
+        let fixture = Fixture::new(n, seed);
+        println!(
+            "N={} seed={} points={} squares={}",
+            n,
+            seed,
+            fixture.state.grid.points.iter().flatten().flatten().count(),
+            fixture.state.squares.len()
+        );
+        println!("{:<28}{:>12}{:>12}", "benchmark", "ns/op", "ops");
+        for (name, ns_per_op, ops) in run_benches(&fixture, min_seconds, filter.as_deref()) {
+            println!("{:<28}{:>12.1}{:>12}", name, ns_per_op, ops);
+        }
+        println!();
+    }
+}
+
+#[test]
+fn test_run_benches() {
+    let fixture = Fixture::new(31, 0);
+    assert!(!fixture.addable.is_empty());
+    let before = fixture.state.clone();
+
+    let results = run_benches(&fixture, 0.001, None);
+    assert_eq!(results.len(), 7 + Neighborhood::all().len());
+    assert!(results
+        .iter()
+        .all(|(_, ns_per_op, ops)| *ns_per_op > 0. && *ops > 0));
+    crate::ascii::assert_state_eq(&fixture.state, &before);
+
+    let results = run_benches(&fixture, 0.001, Some("State::"));
+    let names: Vec<&str> = results.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "State::perform_add",
+            "State::perform_delete",
+            "State::sample_point_pos",
+            "State::clone"
+        ]
+    );
+}
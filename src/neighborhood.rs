@@ -1,4 +1,8 @@
-use crate::*;
+use crate::config::*;
+use crate::def::*;
+use crate::framework::*;
+use crate::state::*;
+use crate::util::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Neighborhood {
@@ -7,20 +11,22 @@ pub enum Neighborhood {
     ChangeSquare = 2,
     SplitSquare = 3,
     MultipleAdd = 4,
+    OutwardGrowth = 5,
 }
 
 impl Neighborhood {
-    pub fn perform(&self, state: &mut State) -> Vec<Command> {
+    pub fn perform(&self, state: &mut State, config: &SolverConfig) -> Vec<Command> {
         match self {
             Neighborhood::Add => Neighborhood::perform_add(state),
-            Neighborhood::Delete => Neighborhood::perform_delete(state),
-            Neighborhood::ChangeSquare => Neighborhood::perform_change_square(state),
-            Neighborhood::SplitSquare => Neighborhood::perform_split_square(state),
-            Neighborhood::MultipleAdd => Neighborhood::perform_multiple_add(state),
+            Neighborhood::Delete => Neighborhood::perform_delete(state, config),
+            Neighborhood::ChangeSquare => Neighborhood::perform_change_square(state, config),
+            Neighborhood::SplitSquare => Neighborhood::perform_split_square(state, config),
+            Neighborhood::MultipleAdd => Neighborhood::perform_multiple_add(state, config),
+            Neighborhood::OutwardGrowth => Neighborhood::perform_outward_growth(state, config),
         }
     }
 
-    fn perform_multiple_add(state: &mut State) -> Vec<Command> {
+    fn perform_multiple_add(state: &mut State, config: &SolverConfig) -> Vec<Command> {
         let selected_p = state.sample_point_pos();
         let mut performed_commands = vec![];
         let mut recursion_count = 0;
@@ -28,7 +34,7 @@ impl Neighborhood {
             state,
             &selected_p,
             &mut recursion_count,
-            &MULTIPLE_ADD_RECURSION_LIMIT,
+            &config.multiple_add_recursion_limit,
             &mut performed_commands,
             state.get_score(1.),
         );
@@ -59,7 +65,7 @@ impl Neighborhood {
             used_bits |= 1 << i;
             let dir = Dir::from_i32(i as i32);
             if let Some(nearest_pos) =
-                state.grid.point(&pos).as_ref().unwrap().nearest_points[dir.val() as usize]
+                state.grid.point(pos).as_ref().unwrap().nearest_points[dir.val() as usize]
             {
                 let mut add = Neighborhood::attempt_add(state, &nearest_pos, None);
                 performed_commands.append(&mut add);
@@ -75,20 +81,92 @@ impl Neighborhood {
         }
     }
 
+    // 外周の点から、なるべく遠くに点を作る。作った点からさらに外側へ少しずつ伸ばす
+    fn perform_outward_growth(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+        let frontier = state.grid.frontier_points();
+        if frontier.is_empty() {
+            return vec![];
+        }
+        let starts: Vec<Pos> = (0..config.outward_growth_samples)
+            .map(|_| frontier[rnd::gen_range(0, frontier.len())])
+            .collect();
+        let mut square = match Neighborhood::find_outward_square(state, &starts, i32::MIN) {
+            Some(square) => square,
+            None => return vec![],
+        };
+        let mut performed_commands = state.perform_command(&Command::Add { square });
+
+        for _ in 0..config.outward_growth_chain_limit {
+            // 作った点と、その点から見える点を起点にする
+            let pos = square.new_pos;
+            let mut starts = vec![pos];
+            starts.extend(
+                state
+                    .grid
+                    .point(&pos)
+                    .as_ref()
+                    .unwrap()
+                    .nearest_points
+                    .iter()
+                    .flatten(),
+            );
+            let min_distance = Neighborhood::center_distance(state, &pos);
+            square = match Neighborhood::find_outward_square(state, &starts, min_distance) {
+                Some(square) => square,
+                None => break,
+            };
+            performed_commands.append(&mut state.perform_command(&Command::Add { square }));
+        }
+        performed_commands
+    }
+
+    // 盤面の中心からの距離の2乗の4倍。中心が半端な座標でも整数で比べられるようにする
+    fn center_distance(state: &State, pos: &Pos) -> i32 {
+        let dx = 2 * pos.x - (state.grid.width as i32 - 1);
+        let dy = 2 * pos.y - (state.grid.height as i32 - 1);
+        dx * dx + dy * dy
+    }
+
+    // startsのどれかを対角の点として作れる四角のうち、新しい点が盤面の中心から最も遠いもの
+    // 重みの設定に関係なく、最も外側に伸ばせる四角になる
+    fn find_outward_square(state: &mut State, starts: &[Pos], min_distance: i32) -> Option<Square> {
+        let mut best: Option<(i32, Square)> = None;
+        for pos in starts {
+            let nearest_points = state.grid.point(pos).as_ref().unwrap().nearest_points;
+            for i in 0..DIR_MAX {
+                let dir = Dir::from_i32(i as i32);
+                if let (Some(pos_prev), Some(pos_next)) = (
+                    nearest_points[dir.prev().val() as usize],
+                    nearest_points[dir.next().val() as usize],
+                ) {
+                    let new_pos = &pos_next + &(&pos_prev - pos);
+                    if !state.grid.is_valid(&new_pos) {
+                        continue;
+                    }
+                    let distance = Neighborhood::center_distance(state, &new_pos);
+                    if distance <= min_distance
+                        || best.is_some_and(|(best_distance, _)| distance <= best_distance)
+                    {
+                        continue;
+                    }
+                    let square = Square::new(new_pos, *pos, [pos_prev, pos_next]);
+                    if state.check_add(&square, false).is_ok() {
+                        best = Some((distance, square));
+                    }
+                }
+            }
+        }
+        best.map(|(_, square)| square)
+    }
+
     fn perform_add(state: &mut State) -> Vec<Command> {
         let selected_p = state.sample_point_pos();
         Neighborhood::attempt_add(state, &selected_p, None)
     }
 
     fn attempt_add(state: &mut State, pos: &Pos, ignore_dir: Option<&Dir>) -> Vec<Command> {
-        debug_assert!(state.grid.has_point(&pos));
-        let nearest_points = state
-            .grid
-            .point(&pos)
-            .as_ref()
-            .unwrap()
-            .nearest_points
-            .clone();
+        debug_assert!(state.grid.has_point(pos));
+        let nearest_points = state.grid.point(pos).as_ref().unwrap().nearest_points;
         let mut used_bits: usize = 0;
         for _ in 0..DIR_MAX {
             let i = rnd::gen_range(0, DIR_MAX);
@@ -103,12 +181,12 @@ impl Neighborhood {
                 }
             }
             let performed_commands =
-                Neighborhood::attempt_add_dir(state, &pos, &nearest_points, &diagonal_dir);
-            if performed_commands.len() > 0 {
+                Neighborhood::attempt_add_dir(state, pos, &nearest_points, &diagonal_dir);
+            if !performed_commands.is_empty() {
                 return performed_commands;
             }
         }
-        return vec![];
+        vec![]
     }
 
     fn attempt_add_dir(
@@ -124,24 +202,24 @@ impl Neighborhood {
             &nearest_points[dir_prev.val() as usize],
             &nearest_points[dir_next.val() as usize],
         ) {
-            let new_pos = pos_next + &(pos_prev - &pos);
+            let new_pos = pos_next + &(pos_prev - pos);
 
             if !state.grid.is_valid(&new_pos) {
                 return vec![];
             }
-            if !state.grid.has_point(&pos)
-                || !state.grid.has_point(&pos_prev)
-                || !state.grid.has_point(&pos_next)
+            if !state.grid.has_point(pos)
+                || !state.grid.has_point(pos_prev)
+                || !state.grid.has_point(pos_next)
                 || state.grid.has_point(&new_pos)
             {
                 return vec![];
             }
 
-            let connect: [Pos; 2] = [pos_prev.clone(), pos_next.clone()];
-            let square = Square::new(new_pos, pos.clone(), connect);
+            let connect: [Pos; 2] = [*pos_prev, *pos_next];
+            let square = Square::new(new_pos, *pos, connect);
 
             let performed_commands = state.perform_command(&Command::Add { square });
-            if performed_commands.len() > 0 {
+            if !performed_commands.is_empty() {
                 return performed_commands;
             }
         }
@@ -149,33 +227,42 @@ impl Neighborhood {
         vec![]
     }
 
-    fn perform_delete(state: &mut State) -> Vec<Command> {
-        if state.squares.len() == 0 {
+    fn perform_delete(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+        if state.squares.is_empty() {
             return vec![];
         }
         let square = state.sample_square();
-        Neighborhood::attempt_delete(state, &square)
+        Neighborhood::attempt_delete(state, &square, config)
     }
 
-    fn attempt_delete(state: &mut State, square: &Square) -> Vec<Command> {
+    fn attempt_delete(state: &mut State, square: &Square, config: &SolverConfig) -> Vec<Command> {
+        // 削除する四角が多すぎるときは不採用
+        let limit = config.deletion_recursion_limit;
+        if state.calc_deletion_size(&square.new_pos, limit, 0) >= limit {
+            return vec![];
+        }
         state.perform_command(&Command::Delete { square: *square })
     }
 
-    fn perform_change_square(state: &mut State) -> Vec<Command> {
+    fn perform_change_square(state: &mut State, config: &SolverConfig) -> Vec<Command> {
         // 四角を作っている点を探す
-        if state.squares.len() == 0 {
+        if state.squares.is_empty() {
             return vec![];
         }
         let square = state.sample_square();
-        Neighborhood::attempt_change_square(state, &square)
+        Neighborhood::attempt_change_square(state, &square, config)
     }
 
-    fn attempt_change_square(state: &mut State, square: &Square) -> Vec<Command> {
+    fn attempt_change_square(
+        state: &mut State,
+        square: &Square,
+        config: &SolverConfig,
+    ) -> Vec<Command> {
         let start_score = state.get_score(1.);
-        let mut performed_commands = state.perform_command(&Command::Delete { square: *square });
+        let mut performed_commands = Neighborhood::attempt_delete(state, square, config);
 
         // 四角を消せなかったら中止
-        if performed_commands.len() == 0 {
+        if performed_commands.is_empty() {
             return performed_commands;
         }
 
@@ -189,29 +276,32 @@ impl Neighborhood {
             state,
             &square.diagonal,
             &mut recursion_count,
-            &MULTIPLE_ADD_RECURSION_LIMIT,
+            &config.multiple_add_recursion_limit,
             &mut performed_commands,
             start_score,
         );
-        return performed_commands;
+        performed_commands
     }
 
-    fn perform_split_square(state: &mut State) -> Vec<Command> {
-        if state.squares.len() == 0 {
+    fn perform_split_square(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+        if state.squares.is_empty() {
             return vec![];
         }
         let selected_square = state.sample_square();
-        Neighborhood::attempt_split_square(state, &selected_square)
+        Neighborhood::attempt_split_square(state, &selected_square, config)
     }
 
-    fn attempt_split_square(state: &mut State, square: &Square) -> Vec<Command> {
+    fn attempt_split_square(
+        state: &mut State,
+        square: &Square,
+        config: &SolverConfig,
+    ) -> Vec<Command> {
         let nearest_points = state
             .grid
             .point(&square.diagonal)
             .as_ref()
             .unwrap()
-            .nearest_points
-            .clone();
+            .nearest_points;
         let dir1 = Pos::get_dir(&square.diagonal, &square.connect[0]);
         let dir2 = Pos::get_dir(&square.diagonal, &square.connect[1]);
 
@@ -219,11 +309,9 @@ impl Neighborhood {
             || nearest_points[dir2.val() as usize] != Some(square.connect[1])
         {
             let start_score = state.get_score(1.);
-            let mut performed_commands = state.perform_command(&Command::Delete {
-                square: square.clone(),
-            });
+            let mut performed_commands = Neighborhood::attempt_delete(state, square, config);
 
-            if performed_commands.len() == 0 {
+            if performed_commands.is_empty() {
                 return vec![];
             }
 
@@ -232,7 +320,7 @@ impl Neighborhood {
                 state,
                 &square.diagonal,
                 &mut recursion_count,
-                &MULTIPLE_ADD_RECURSION_LIMIT,
+                &config.multiple_add_recursion_limit,
                 &mut performed_commands,
                 start_score,
             );
@@ -244,13 +332,14 @@ impl Neighborhood {
 }
 
 impl Neighborhood {
-    pub fn all() -> [Neighborhood; 5] {
+    pub fn all() -> [Neighborhood; 6] {
         [
             Neighborhood::Add,
             Neighborhood::Delete,
             Neighborhood::ChangeSquare,
             Neighborhood::SplitSquare,
             Neighborhood::MultipleAdd,
+            Neighborhood::OutwardGrowth,
         ]
     }
 
@@ -261,11 +350,13 @@ impl Neighborhood {
             2 => Neighborhood::ChangeSquare,
             3 => Neighborhood::SplitSquare,
             4 => Neighborhood::MultipleAdd,
+            5 => Neighborhood::OutwardGrowth,
             _ => panic!("Neighborhood value {} is invalid.", v),
         }
     }
 }
 
+#[allow(clippy::clone_on_copy, clippy::len_zero)]
 #[test]
 fn test_split_square() {
     loop {
@@ -283,7 +374,7 @@ fn test_split_square() {
         state.perform_add(&square, false);
         state.grid.add_point(&add_pos, Point::new(&add_pos), None);
 
-        Neighborhood::attempt_split_square(&mut state, &square);
+        Neighborhood::attempt_split_square(&mut state, &square, &SolverConfig::new());
 
         let mut square = Square::new(new_pos2.clone(), diagonal.clone(), connect2.clone());
 
@@ -296,6 +387,7 @@ fn test_split_square() {
     }
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_change_square() {
     let selected_p = Pos { x: 2, y: 2 };
@@ -321,7 +413,8 @@ fn test_change_square() {
         square: square.clone(),
     });
     let copied_state = state.clone();
-    let performed_commands = Neighborhood::attempt_change_square(&mut state, &square);
+    let performed_commands =
+        Neighborhood::attempt_change_square(&mut state, &square, &SolverConfig::new());
 
     // multiple_addが不定なので消す
     // Squareのidは異なってしまう
@@ -334,5 +427,64 @@ fn test_change_square() {
         state.reverse_command(command);
     }
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
+}
+
+#[test]
+fn test_outward_growth() {
+    let center = Pos { x: 3, y: 3 };
+    let p = vec![
+        center,
+        Pos { x: 6, y: 3 },
+        Pos { x: 3, y: 6 },
+        Pos { x: 3, y: 2 },
+    ];
+    let mut state = State::new(7, p);
+    let copied_state = state.clone();
+    let mut config = SolverConfig::new();
+    config.outward_growth_samples = 64;
+    rnd::seed(0);
+
+    // (6, 2)より中心から遠い(6, 6)に作る
+    let performed_commands = Neighborhood::OutwardGrowth.perform(&mut state, &config);
+    assert_eq!(performed_commands.len(), 1);
+    assert_eq!(state.squares.len(), 1);
+    assert_eq!(state.squares[0].new_pos, Pos { x: 6, y: 6 });
+    assert_eq!(state.squares[0].diagonal, center);
+
+    for command in performed_commands.iter().rev() {
+        state.reverse_command(command);
+    }
+    crate::ascii::assert_state_eq(&state, &copied_state);
+}
+
+#[test]
+fn test_outward_growth_uniform_weight() {
+    use crate::weight::WeightMap;
+    use std::rc::Rc;
+
+    let center = Pos { x: 4, y: 4 };
+    let p = vec![
+        center,
+        Pos { x: 6, y: 4 },
+        Pos { x: 4, y: 6 },
+        Pos { x: 8, y: 6 },
+        Pos { x: 6, y: 8 },
+    ];
+    let weight_map = Rc::new(WeightMap::uniform(9, 9));
+    let mut state = State::with_weight_map(9, p, weight_map);
+    let copied_state = state.clone();
+    let mut config = SolverConfig::new();
+    config.outward_growth_samples = 64;
+    rnd::seed(0);
+
+    // 重みが一様でも、(6, 6)を作った後にさらに外側の(8, 8)まで伸ばす
+    let performed_commands = Neighborhood::OutwardGrowth.perform(&mut state, &config);
+    let new_positions: Vec<Pos> = state.squares.iter().map(|square| square.new_pos).collect();
+    assert_eq!(new_positions, vec![Pos { x: 6, y: 6 }, Pos { x: 8, y: 8 }]);
+
+    for command in performed_commands.iter().rev() {
+        state.reverse_command(command);
+    }
+    crate::ascii::assert_state_eq(&state, &copied_state);
 }
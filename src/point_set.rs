@@ -0,0 +1,94 @@
+use crate::def::*;
+
+// 点がある位置の集合。追加、削除がO(1)で、添字で一様に選べる
+#[derive(Clone, Debug)]
+pub struct PointSet {
+    positions: Vec<Pos>,
+    // 各マスの点のpositionsでの添字
+    index: Vec<Vec<Option<usize>>>,
+}
+
+impl PointSet {
+    pub fn new(width: usize, height: usize) -> PointSet {
+        PointSet {
+            positions: vec![],
+            index: vec![vec![None; width]; height],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn positions(&self) -> &[Pos] {
+        &self.positions
+    }
+
+    pub fn contains(&self, pos: &Pos) -> bool {
+        self.index[pos.y as usize][pos.x as usize].is_some()
+    }
+
+    pub fn insert(&mut self, pos: &Pos) {
+        debug_assert!(!self.contains(pos));
+        self.index[pos.y as usize][pos.x as usize] = Some(self.positions.len());
+        self.positions.push(*pos);
+    }
+
+    // 最後の点を空いた場所に移す。posがあった添字を返す
+    pub fn remove(&mut self, pos: &Pos) -> usize {
+        let i = self.index[pos.y as usize][pos.x as usize].take().unwrap();
+        self.positions.swap_remove(i);
+        if let Some(moved) = self.positions.get(i) {
+            self.index[moved.y as usize][moved.x as usize] = Some(i);
+        }
+        i
+    }
+
+    // removeの逆。iにあった点を最後に戻して、posをiに置く
+    pub fn insert_at(&mut self, pos: &Pos, i: usize) {
+        self.insert(pos);
+        let last = self.positions.len() - 1;
+        self.positions.swap(i, last);
+        for j in [i, last] {
+            let moved = self.positions[j];
+            self.index[moved.y as usize][moved.x as usize] = Some(j);
+        }
+    }
+}
+
+// 並び順は追加と削除の順で変わるので、集合として比べる
+impl PartialEq for PointSet {
+    fn eq(&self, other: &PointSet) -> bool {
+        self.len() == other.len() && self.positions.iter().all(|pos| other.contains(pos))
+    }
+}
+
+impl Eq for PointSet {}
+
+#[test]
+fn test_point_set() {
+    let positions = [Pos { x: 0, y: 0 }, Pos { x: 2, y: 1 }, Pos { x: 1, y: 2 }];
+    let mut set = PointSet::new(3, 3);
+    for pos in &positions {
+        set.insert(pos);
+    }
+    set.remove(&positions[0]);
+    assert_eq!(set.positions(), [positions[2], positions[1]]);
+    assert!(!set.contains(&positions[0]));
+
+    let mut other = PointSet::new(3, 3);
+    other.insert(&positions[1]);
+    other.insert(&positions[2]);
+    assert_eq!(set, other);
+    other.remove(&positions[2]);
+    assert_ne!(set, other);
+    assert_eq!(other.positions(), [positions[1]]);
+
+    let i = set.remove(&positions[2]);
+    set.insert_at(&positions[2], i);
+    assert_eq!(set.positions(), [positions[2], positions[1]]);
+}
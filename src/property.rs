@@ -0,0 +1,404 @@
+use crate::ascii::*;
+use crate::config::*;
+use crate::def::*;
+use crate::framework::*;
+use crate::neighborhood::*;
+use crate::sampler::*;
+use crate::state::*;
+use crate::util::rnd;
+use crate::util::rnd::Xorshift;
+use std::fmt::Write;
+
+// 近傍を試した後の戻し方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Undo {
+    // 採用する
+    Commit,
+    // journalで戻す
+    Rollback,
+    // 実行したコマンドを逆順にreverse_commandする
+    Reverse,
+}
+
+// ランダムな操作列の1手。seedで近傍の中の乱数を決める
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub neighborhood: Neighborhood,
+    pub seed: u64,
+    pub undo: Undo,
+}
+
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub step: usize,
+    pub message: String,
+    // 各手で実行されたコマンド
+    pub performed: Vec<Vec<Command>>,
+}
+
+pub fn random_steps(seed: u64, count: usize) -> Vec<Step> {
+    let mut rng = Xorshift::new(seed);
+    let neighborhoods = Neighborhood::all();
+    (0..count)
+        .map(|_| Step {
+            neighborhood: neighborhoods[rng.gen_range_inclusive(0, neighborhoods.len() - 1)],
+            seed: rng.next() as u64,
+            undo: match rng.gen_range_inclusive(0, 3) {
+                0 | 1 => Undo::Commit,
+                2 => Undo::Rollback,
+                _ => Undo::Reverse,
+            },
+        })
+        .collect()
+}
+
+fn check(condition: bool, message: impl FnOnce() -> String) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message())
+    }
+}
+
+// 四角の集合から決まるはずの点、辺、スコアと、盤面が一致しているか確かめる
+// 後から辺の上に点を置くのは許されるので、辺の上の点は作った時点の順序としてreplayで確かめる
+pub fn check_invariants(state: &State) -> Result<(), String> {
+    let grid = &state.grid;
+    check(!grid.journal.is_recording(), || {
+        "a transaction is still open".to_string()
+    })?;
+
+    let mut expected_edges = vec![vec![vec![false; DIR_MAX]; grid.width]; grid.height];
+    let mut expected_created_points: Vec<Vec<Vec<Pos>>> =
+        vec![vec![vec![]; grid.width]; grid.height];
+    for square in &state.squares {
+        let point = grid.points[square.new_pos.y as usize][square.new_pos.x as usize].as_ref();
+        check(
+            point.is_some_and(|point| point.added_info == Some(*square)),
+            || format!("square #{} is not recorded at its new point", square.id),
+        )?;
+        let corners = square.all_pos();
+        for k in 0..4 {
+            let (a, b) = (corners[k], corners[(k + 1) % 4]);
+            check(grid.has_point(a), || {
+                format!("corner ({}, {}) of #{} has no point", a.x, a.y, square.id)
+            })?;
+            let dir = Pos::get_dir(a, b);
+            let mut path = vec![*a];
+            path.extend(Pos::between(a, b));
+            path.push(*b);
+            for pair in path.windows(2) {
+                for (pos, dir) in [(pair[0], dir), (pair[1], dir.rev())] {
+                    let edge =
+                        &mut expected_edges[pos.y as usize][pos.x as usize][dir.val() as usize];
+                    check(!*edge, || {
+                        format!("sides overlap at ({}, {})", pos.x, pos.y)
+                    })?;
+                    *edge = true;
+                }
+            }
+        }
+        for pos in &[square.connect[0], square.diagonal, square.connect[1]] {
+            expected_created_points[pos.y as usize][pos.x as usize].push(square.new_pos);
+        }
+    }
+
+    let mut score = 0;
+    let mut added_count = 0;
+    let mut row_ends: Vec<Option<(i32, i32)>> = vec![None; grid.height];
+    let mut column_ends: Vec<Option<(i32, i32)>> = vec![None; grid.width];
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let pos = Pos {
+                x: x as i32,
+                y: y as i32,
+            };
+            check(grid.edges[y][x] == expected_edges[y][x], || {
+                format!("edges at ({}, {}) do not match the squares", x, y)
+            })?;
+            let point = match &grid.points[y][x] {
+                Some(point) => point,
+                None => continue,
+            };
+            check(!grid.is_blocked(&pos), || {
+                format!("point ({}, {}) is on a blocked cell", x, y)
+            })?;
+            check(grid.point_set.contains(&pos), || {
+                format!("point ({}, {}) is missing from the point set", x, y)
+            })?;
+            score += state.weight(&pos);
+            added_count += point.added_info.is_some() as usize;
+            // 左上から順に見るので、最初に見つけた点が小さい方の端になる
+            row_ends[y] = Some((row_ends[y].map_or(pos.x, |(low, _)| low), pos.x));
+            column_ends[x] = Some((column_ends[x].map_or(pos.y, |(low, _)| low), pos.y));
+            let depth = point.added_info.map_or(0, |square| {
+                [square.connect[0], square.diagonal, square.connect[1]]
+                    .iter()
+                    .map(|pos| {
+                        grid.points[pos.y as usize][pos.x as usize]
+                            .as_ref()
+                            .unwrap()
+                            .depth
+                            + 1
+                    })
+                    .max()
+                    .unwrap()
+            });
+            check(point.depth == depth, || {
+                format!("depth of ({}, {}) is {}, not {}", x, y, point.depth, depth)
+            })?;
+
+            let mut created_points = point.created_points.clone();
+            created_points.sort();
+            expected_created_points[y][x].sort();
+            check(created_points == expected_created_points[y][x], || {
+                format!("created points of ({}, {}) do not match the squares", x, y)
+            })?;
+            for i in 0..DIR_MAX {
+                let dir = Dir::from_i32(i as i32);
+                check(
+                    point.nearest_points[i] == grid.nearest_point_pos(&pos, &dir),
+                    || format!("nearest point of ({}, {}) toward {:?} is stale", x, y, dir),
+                )?;
+            }
+        }
+    }
+    for (y, ends) in row_ends.iter().enumerate() {
+        check(grid.row_ends[y] == *ends, || {
+            format!(
+                "row ends of y = {} are {:?}, not {:?}",
+                y, grid.row_ends[y], ends
+            )
+        })?;
+    }
+    for (x, ends) in column_ends.iter().enumerate() {
+        check(grid.column_ends[x] == *ends, || {
+            format!(
+                "column ends of x = {} are {:?}, not {:?}",
+                x, grid.column_ends[x], ends
+            )
+        })?;
+    }
+    check(
+        grid.point_set.len() == state.squares.len() + state.initial_points().len(),
+        || format!("point set has {} points", grid.point_set.len()),
+    )?;
+    check(added_count == state.squares.len(), || {
+        format!(
+            "{} added points for {} squares",
+            added_count,
+            state.squares.len()
+        )
+    })?;
+    check(score == state.score.base, || {
+        format!("score {} != sum of weights {}", state.score.base, score)
+    })?;
+
+    // 依存関係に循環がなく、初期状態から作り直せる
+    let replayed = state.replay(&state.ordered_squares());
+    check(
+        replayed.squares.len() == state.squares.len() && replayed.score == state.score,
+        || {
+            format!(
+                "only {} of {} squares can be replayed",
+                replayed.squares.len(),
+                state.squares.len()
+            )
+        },
+    )
+}
+
+// reverse_commandでは四角と作った点の並び順までは戻らないので、揃えて比べる
+fn canonical(state: &State) -> State {
+    let mut state = state.clone();
+    state.squares.sort_by_key(|square| square.new_pos);
+    for point in state.grid.points.iter_mut().flatten().flatten() {
+        point.created_points.sort();
+    }
+    state
+}
+
+pub fn run_steps(initial: &State, steps: &[Step], config: &SolverConfig) -> Result<State, Failure> {
+    let mut state = initial.clone();
+    let mut performed = vec![];
+    for (i, step) in steps.iter().enumerate() {
+        let before = state.clone();
+        rnd::seed(step.seed);
+        state.begin();
+        let commands = step.neighborhood.perform(&mut state, config);
+        let result = match step.undo {
+            Undo::Commit => {
+                state.commit();
+                check_invariants(&state)
+            }
+            Undo::Rollback => {
+                state.rollback();
+                // 点を選ぶ順番が変わらないように、点の並び順まで戻っているか確かめる
+                describe_state_diff(&state, &before, TextStyle::Ascii)
+                    .map_or(Ok(()), Err)
+                    .and_then(|_| {
+                        check(
+                            state.grid.point_set.positions() == before.grid.point_set.positions(),
+                            || "order of the point set is not restored".to_string(),
+                        )
+                    })
+            }
+            Undo::Reverse => {
+                state.commit();
+                for command in commands.iter().rev() {
+                    state.reverse_command(command);
+                }
+                describe_state_diff(&canonical(&state), &canonical(&before), TextStyle::Ascii)
+                    .map_or(Ok(()), Err)
+            }
+        };
+        performed.push(commands);
+        if let Err(message) = result {
+            return Err(Failure {
+                step: i,
+                message,
+                performed,
+            });
+        }
+    }
+    Ok(state)
+}
+
+// 失敗を再現できるまま、手を取り除いていく
+pub fn shrink(initial: &State, steps: &[Step], config: &SolverConfig) -> (Vec<Step>, Failure) {
+    let mut failure = match run_steps(initial, steps, config) {
+        Ok(_) => panic!("steps do not fail"),
+        Err(failure) => failure,
+    };
+    let mut steps = steps[..=failure.step].to_vec();
+    let mut chunk = steps.len() / 2;
+    while chunk >= 1 {
+        let mut i = 0;
+        while i < steps.len() {
+            let mut candidate = steps.clone();
+            candidate.drain(i..usize::min(i + chunk, steps.len()));
+            match run_steps(initial, &candidate, config) {
+                Err(candidate_failure) => {
+                    steps = candidate[..=candidate_failure.step].to_vec();
+                    failure = candidate_failure;
+                }
+                Ok(_) => i += chunk,
+            }
+        }
+        chunk /= 2;
+    }
+    (steps, failure)
+}
+
+pub fn describe_steps(steps: &[Step], failure: &Failure) -> String {
+    let mut s = String::new();
+    for (i, step) in steps.iter().enumerate() {
+        let commands: Vec<String> = failure.performed[i]
+            .iter()
+            .map(|command| match command {
+                Command::Add { square } => {
+                    format!("Add({}, {})", square.new_pos.x, square.new_pos.y)
+                }
+                Command::Delete { square } => {
+                    format!("Delete({}, {})", square.new_pos.x, square.new_pos.y)
+                }
+            })
+            .collect();
+        writeln!(
+            s,
+            "#{} {:?} seed={} {:?}: {}",
+            i,
+            step.neighborhood,
+            step.seed,
+            step.undo,
+            commands.join(" ")
+        )
+        .unwrap();
+    }
+    write!(s, "step #{} failed: {}", failure.step, failure.message).unwrap();
+    s
+}
+
+#[test]
+fn test_random_steps() {
+    use crate::generator::*;
+
+    // PROPERTY_SEEDS, PROPERTY_STEPSで長く回せる
+    let env = |name: &str, default: usize| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    let (seeds, step_count) = (env("PROPERTY_SEEDS", 6), env("PROPERTY_STEPS", 150));
+    let mut config = SolverConfig::new();
+    config.deletion_recursion_limit = 4;
+    for seed in 0..seeds as u64 {
+        let mut generator_config = GeneratorConfig::official();
+        generator_config.n = Some(31);
+        let input = generate(seed, &generator_config).unwrap();
+        let mut initial = State::new(input.n, input.points);
+        // 奇数のseedでは重み付きで選ぶ
+        if seed % 2 == 1 {
+            initial.sampler = Sampler::new(SamplerConfig {
+                center_distance_start: -2.,
+                center_distance_end: 2.,
+                depth: -1.,
+                free_degree: 1.,
+            });
+            initial.sampler.progress = 0.5;
+        }
+        let steps = random_steps(seed, step_count);
+        if run_steps(&initial, &steps, &config).is_err() {
+            let (steps, failure) = shrink(&initial, &steps, &config);
+            panic!(
+                "seed {} fails with {} steps:\n{}",
+                seed,
+                steps.len(),
+                describe_steps(&steps, &failure)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_shrink() {
+    let initial = State::new(
+        5,
+        vec![Pos { x: 0, y: 0 }, Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }],
+    );
+    let config = SolverConfig::new();
+    assert_eq!(check_invariants(&initial), Ok(()));
+    let mut broken = initial.clone();
+    broken.score.base += 1;
+    assert!(check_invariants(&broken).unwrap_err().starts_with("score "));
+    let mut broken_ends = initial.clone();
+    broken_ends.grid.row_ends[0] = Some((0, 0));
+    assert!(check_invariants(&broken_ends)
+        .unwrap_err()
+        .starts_with("row ends of y = 0"));
+
+    // 戻す手は壊れた状態でも通るので、最後に採用する手だけが残る
+    let steps: Vec<Step> = (0..10)
+        .map(|seed| Step {
+            neighborhood: Neighborhood::Add,
+            seed,
+            undo: if seed % 3 == 0 {
+                Undo::Reverse
+            } else {
+                Undo::Rollback
+            },
+        })
+        .chain([Step {
+            neighborhood: Neighborhood::Delete,
+            seed: 0,
+            undo: Undo::Commit,
+        }])
+        .collect();
+    assert!(run_steps(&initial, &steps, &config).is_ok());
+    let (shrunk, failure) = shrink(&broken, &steps, &config);
+    assert_eq!(shrunk, steps[10..]);
+    assert_eq!(failure.step, 0);
+    assert!(describe_steps(&shrunk, &failure)
+        .starts_with("#0 Delete seed=0 Commit: \nstep #0 failed: score "));
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NeighborhoodReport {
+    pub name: String,
+    pub total: i32,
+    pub adopted: i32,
+}
+
+// 1回の実行結果。バッチ処理で読めるようにJSONで出す
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolveReport {
+    pub loop_count: usize,
+    // 焼きなまし終了時の内部スコア
+    pub final_score: i32,
+    // 出力する状態の内部スコア
+    pub best_score: i32,
+    pub real_score: i32,
+    pub elapsed_seconds: f64,
+    pub neighborhoods: Vec<NeighborhoodReport>,
+}
+
+pub fn escape_json(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl SolveReport {
+    // 1行のJSONオブジェクト
+    pub fn to_json(&self) -> String {
+        let neighborhoods: Vec<String> = self
+            .neighborhoods
+            .iter()
+            .map(|n| {
+                format!(
+                    r#"{{"name":"{}","total":{},"adopted":{}}}"#,
+                    escape_json(&n.name),
+                    n.total,
+                    n.adopted
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"loop_count":{},"final_score":{},"best_score":{},"real_score":{},"elapsed_seconds":{:.3},"neighborhoods":[{}]}}"#,
+            self.loop_count,
+            self.final_score,
+            self.best_score,
+            self.real_score,
+            self.elapsed_seconds,
+            neighborhoods.join(",")
+        )
+    }
+}
+
+#[test]
+fn test_to_json() {
+    let report = SolveReport {
+        loop_count: 1000,
+        final_score: 90,
+        best_score: 100,
+        real_score: 1_250_000,
+        elapsed_seconds: 4.9712,
+        neighborhoods: vec![
+            NeighborhoodReport {
+                name: "Add".to_string(),
+                total: 700,
+                adopted: 20,
+            },
+            NeighborhoodReport {
+                name: "Delete".to_string(),
+                total: 300,
+                adopted: 100,
+            },
+        ],
+    };
+    assert_eq!(
+        report.to_json(),
+        r#"{"loop_count":1000,"final_score":90,"best_score":100,"real_score":1250000,"elapsed_seconds":4.971,"neighborhoods":[{"name":"Add","total":700,"adopted":20},{"name":"Delete","total":300,"adopted":100}]}"#
+    );
+    assert_eq!(escape_json("a\"b\\c\n"), r#"a\"b\\c\n"#);
+}
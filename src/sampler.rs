@@ -0,0 +1,118 @@
+use crate::config::*;
+use crate::def::*;
+use crate::grid::*;
+use crate::util::*;
+
+// 受理されないまま、この回数候補を引いたら最後の候補を使う
+const MAX_TRIALS: usize = 64;
+// 深さはこの値で打ち切って0..1にする
+const DEPTH_SCALE: usize = 10;
+
+// 焼きなましの進み具合に応じて、点や四角を重み付きで選ぶ
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sampler {
+    pub config: SamplerConfig,
+    // 0から1
+    pub progress: f32,
+}
+
+impl Sampler {
+    pub fn new(config: SamplerConfig) -> Sampler {
+        Sampler {
+            config,
+            progress: 0.,
+        }
+    }
+
+    pub fn uniform() -> Sampler {
+        Sampler::new(SamplerConfig::default())
+    }
+
+    fn is_uniform(&self) -> bool {
+        self.config == SamplerConfig::default()
+    }
+
+    // (係数, 0..1の値)の組
+    fn features(&self, grid: &Grid, pos: &Pos) -> [(f32, f32); 3] {
+        let config = &self.config;
+        let center = ((grid.width - 1) as f32 / 2., (grid.height - 1) as f32 / 2.);
+        let distance = |x: f32, y: f32| (x * x + y * y).sqrt();
+        let max_distance = f32::max(1., distance(center.0, center.1));
+        let center_distance = distance(pos.x as f32 - center.0, pos.y as f32 - center.1);
+
+        let point = grid.points[pos.y as usize][pos.x as usize]
+            .as_ref()
+            .unwrap();
+        let depth = usize::min(point.depth, DEPTH_SCALE);
+        let free_degree = (0..DIR_MAX)
+            .filter(|&i| !grid.edges[pos.y as usize][pos.x as usize][i])
+            .count();
+
+        let center_coef = config.center_distance_start
+            + (config.center_distance_end - config.center_distance_start) * self.progress;
+        [
+            (center_coef, f32::min(1., center_distance / max_distance)),
+            (config.depth, depth as f32 / DEPTH_SCALE as f32),
+            (config.free_degree, free_degree as f32 / DIR_MAX as f32),
+        ]
+    }
+
+    // 重みexp(Σ係数*値)を、取りうる最大値が1になるように割ったもの
+    pub fn acceptance(&self, grid: &Grid, pos: &Pos) -> f32 {
+        self.features(grid, pos)
+            .iter()
+            .map(|&(coef, value)| {
+                if coef > 0. {
+                    coef * (value - 1.)
+                } else {
+                    coef * value
+                }
+            })
+            .sum::<f32>()
+            .exp()
+    }
+
+    // 一様に引いた候補を、位置の重みに比例する確率で受理する
+    pub fn sample<T: Copy>(&self, grid: &Grid, candidates: &[T], pos_of: impl Fn(&T) -> Pos) -> T {
+        let mut candidate = candidates[rnd::gen_range(0, candidates.len())];
+        if self.is_uniform() {
+            return candidate;
+        }
+        for _ in 1..MAX_TRIALS {
+            if rnd::nextf() < self.acceptance(grid, &pos_of(&candidate)) {
+                break;
+            }
+            candidate = candidates[rnd::gen_range(0, candidates.len())];
+        }
+        candidate
+    }
+}
+
+#[test]
+fn test_sampler() {
+    let mut grid = Grid::new(9);
+    let (center, corner) = (Pos { x: 4, y: 4 }, Pos { x: 0, y: 8 });
+    for pos in [center, corner] {
+        grid.add_point(&pos, Point::new(&pos), None);
+    }
+    let mut sampler = Sampler::new(SamplerConfig {
+        center_distance_start: -3.,
+        center_distance_end: 3.,
+        ..Default::default()
+    });
+    assert_eq!(sampler.acceptance(&grid, &center), 1.);
+    assert!((sampler.acceptance(&grid, &corner) - (-3f32).exp()).abs() < 1e-6);
+
+    // 最初は中心、最後は外側を選びやすい
+    let count_center = |sampler: &Sampler| {
+        rnd::seed(0);
+        (0..1000)
+            .filter(|_| sampler.sample(&grid, &[center, corner], |pos| *pos) == center)
+            .count()
+    };
+    assert!(count_center(&sampler) > 900);
+    sampler.progress = 0.5;
+    assert!((400..600).contains(&count_center(&sampler)));
+    sampler.progress = 1.;
+    assert!(count_center(&sampler) < 100);
+}
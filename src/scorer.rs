@@ -0,0 +1,23 @@
+use crate::state::*;
+use crate::util::*;
+use crate::validator::*;
+
+// round(10^6 * N^2 / M * Σw(q) / Σw(p))
+pub fn score_state(state: &State) -> i32 {
+    let m = state.initial_points().len();
+    calc_real_score(&state.weight_map, m, state.score.base)
+}
+
+// 公式の盤面と重みで採点する。出力が不正な場合はエラーを返す
+pub fn compute_score(input: &str, output: &str) -> Result<i32, ValidationError> {
+    let state = validate_official(input, output)?;
+    Ok(score_state(&state))
+}
+
+#[test]
+fn test_compute_score() {
+    let input = "5 4\n0 0\n2 0\n0 2\n2 4\n";
+    assert_eq!(compute_score(input, "0\n"), Ok(1_200_000));
+    assert_eq!(compute_score(input, "1\n2 2 2 0 0 0 0 2\n"), Ok(1_250_000));
+    assert!(compute_score(input, "1\n2 0 2 2 0 2 0 0\n").is_err());
+}
@@ -0,0 +1,327 @@
+use crate::config::*;
+use crate::def::*;
+use crate::framework::*;
+use crate::grid::*;
+use crate::input::*;
+use crate::neighborhood::*;
+use crate::report::*;
+use crate::sampler::*;
+use crate::state::*;
+use crate::telemetry::*;
+use crate::util::*;
+use crate::weight::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub struct NeighborhoodSelector {
+    // 累積確率と近傍。どれにも当たらなければAdd
+    thresholds: Vec<(f32, Neighborhood)>,
+    total_cnt: Vec<i32>,
+    adopted_cnt: Vec<i32>,
+}
+
+impl NeighborhoodSelector {
+    pub fn new(probs: &[f32]) -> NeighborhoodSelector {
+        let total: f32 = probs.iter().sum();
+        let mut cumulative = 0.;
+        let thresholds = [
+            Neighborhood::Delete,
+            Neighborhood::ChangeSquare,
+            Neighborhood::SplitSquare,
+            Neighborhood::MultipleAdd,
+            Neighborhood::OutwardGrowth,
+        ]
+        .into_iter()
+        .map(|neighborhood| {
+            cumulative += probs[neighborhood as usize] / total;
+            (cumulative, neighborhood)
+        })
+        .collect();
+        NeighborhoodSelector {
+            thresholds,
+            total_cnt: vec![0; probs.len()],
+            adopted_cnt: vec![0; probs.len()],
+        }
+    }
+
+    pub fn report(&self) -> Vec<NeighborhoodReport> {
+        (0..self.total_cnt.len())
+            .map(|i| NeighborhoodReport {
+                name: format!("{:?}", Neighborhood::from_i32(i as i32)),
+                total: self.total_cnt[i],
+                adopted: self.adopted_cnt[i],
+            })
+            .collect()
+    }
+}
+
+impl INeighborhoodSelector for NeighborhoodSelector {
+    fn select(&self) -> Neighborhood {
+        let p = rnd::nextf();
+        for (threshold, neighborhood) in &self.thresholds {
+            if p < *threshold {
+                return *neighborhood;
+            }
+        }
+        Neighborhood::Add
+    }
+
+    fn step(&mut self, neighborhood: &Neighborhood, adopted: bool) {
+        self.total_cnt[*neighborhood as usize] += 1;
+        if adopted {
+            self.adopted_cnt[*neighborhood as usize] += 1;
+        }
+    }
+}
+
+pub struct Optimizer {
+    start_temp: f32,
+    end_temp: f32,
+    pub current_temp: f32,
+}
+
+impl IOptimizer for Optimizer {
+    fn update_temp(&mut self, progress: f32) {
+        self.current_temp = self.start_temp + (self.end_temp - self.start_temp) * progress;
+    }
+
+    fn should_adopt_new_state(&self, score_diff: f32) -> bool {
+        let prob = (score_diff / self.current_temp).exp();
+        prob > rnd::nextf()
+    }
+}
+
+impl Optimizer {
+    pub fn new(start_temp: f32, end_temp: f32) -> Optimizer {
+        let mut optimizer = Optimizer {
+            start_temp,
+            end_temp,
+            current_temp: 0.,
+        };
+        optimizer.update_temp(0.);
+        optimizer
+    }
+}
+
+pub struct Solver {
+    pub state: State,
+    pub neighborhood_selector: NeighborhoodSelector,
+    pub optimizer: Optimizer,
+    // --score-log、--chartで書き出すときだけ記録する
+    pub score_log: Option<ScoreLog>,
+    // 四角のidからその四角を作った近傍
+    pub square_origins: HashMap<i32, Neighborhood>,
+    pub trajectory: Option<Trajectory>,
+    pub config: SolverConfig,
+    pub loop_count: usize,
+    pub final_score: i32,
+}
+
+impl ISolver for Solver {
+    fn solve(&mut self, time_limit: f32) {
+        let mut loop_count = 0;
+        let mut best_state = self.state.clone();
+        let m = self.state.initial_points().len();
+        let mut progress = self.progress(loop_count, time_limit);
+        // ループ回数で打ち切るときは、進み具合の更新の間隔に関係なくちょうど止める
+        let iteration_limit = self.config.iteration_limit.unwrap_or(usize::MAX);
+        while progress < 1. && loop_count < iteration_limit {
+            let is_interval = (loop_count % self.config.loop_interval) == 0;
+            if is_interval {
+                progress = self.progress(loop_count, time_limit);
+                self.optimizer.update_temp(progress);
+                self.state.sampler.progress = progress;
+            }
+
+            if let Some(trajectory) = &mut self.trajectory {
+                if trajectory.should_record(loop_count) {
+                    trajectory.record(loop_count, self.optimizer.current_temp, &self.state);
+                }
+            }
+
+            let neighborhood = self.neighborhood_selector.select();
+
+            let current_score = self.state.get_score(progress);
+
+            self.state.begin();
+            let performed_commands = neighborhood.perform(&mut self.state, &self.config);
+
+            let new_score = self.state.get_score(progress);
+
+            let adopt_new_state = self
+                .optimizer
+                .should_adopt_new_state(new_score - current_score)
+                && !performed_commands.is_empty();
+
+            if adopt_new_state {
+                self.state.commit();
+                for command in &performed_commands {
+                    if let Command::Add { square } = command {
+                        self.square_origins.insert(square.id, neighborhood);
+                    }
+                }
+            } else {
+                self.state.rollback();
+            }
+
+            self.neighborhood_selector
+                .step(&neighborhood, adopt_new_state);
+
+            if is_interval && self.state.get_score(1.) > best_state.get_score(1.) {
+                best_state = self.state.clone();
+            }
+            if let (true, Some(score_log)) = (is_interval, &mut self.score_log) {
+                let weight_map = &self.state.weight_map;
+                score_log.record(
+                    loop_count,
+                    self.optimizer.current_temp,
+                    calc_real_score(weight_map, m, self.state.score.base),
+                    calc_real_score(weight_map, m, best_state.score.base),
+                    &self.neighborhood_selector.total_cnt,
+                    &self.neighborhood_selector.adopted_cnt,
+                );
+            }
+            loop_count += 1;
+        }
+        self.loop_count = loop_count;
+        self.final_score = self.state.score.base;
+        self.state = best_state.clone();
+    }
+}
+
+impl Solver {
+    pub fn new(
+        state: State,
+        neighborhood_selector: NeighborhoodSelector,
+        optimizer: Optimizer,
+        config: SolverConfig,
+    ) -> Solver {
+        let telemetry = &config.telemetry;
+        let score_log = (telemetry.score_log.is_some() || telemetry.chart.is_some()).then(|| {
+            ScoreLog::new(
+                Neighborhood::all()
+                    .iter()
+                    .map(|neighborhood| format!("{:?}", neighborhood))
+                    .collect(),
+            )
+        });
+        Solver {
+            state: state.clone(),
+            neighborhood_selector,
+            optimizer,
+            score_log,
+            square_origins: HashMap::new(),
+            trajectory: None,
+            config,
+            loop_count: 0,
+            final_score: 0,
+        }
+    }
+
+    // 入力の点と盤面、重みから、設定した温度で解き始める
+    pub fn from_input(
+        input: &Input,
+        grid: Grid,
+        weight_map: WeightMap,
+        config: SolverConfig,
+    ) -> Solver {
+        let (n, m) = (input.n, input.m());
+        let optimizer = Optimizer::new(config.calc_start_temp(n, m), config.calc_end_temp(n, m));
+        let mut state = State::from_grid(grid, input.points.clone(), Rc::new(weight_map));
+        state.sampler = Sampler::new(config.sampler.clone());
+        Solver::new(
+            state,
+            NeighborhoodSelector::new(&config.neighborhood_probs),
+            optimizer,
+            config,
+        )
+    }
+
+    fn progress(&self, loop_count: usize, time_limit: f32) -> f32 {
+        match self.config.iteration_limit {
+            Some(limit) => loop_count as f32 / usize::max(1, limit) as f32,
+            None => time::elapsed_seconds() as f32 / time_limit,
+        }
+    }
+
+    pub fn output(&self) -> String {
+        // 依存関係の順に並べ替えて、初期状態から作り直せることを確認する
+        let ordered_squares = self.state.ordered_squares();
+        let replayed_state = self.state.replay(&ordered_squares);
+        if replayed_state.squares.len() != ordered_squares.len() {
+            eprintln!(
+                "failed to replay squares: {} / {}",
+                replayed_state.squares.len(),
+                ordered_squares.len()
+            );
+        }
+
+        let mut output = format!("{}\n", replayed_state.squares.len());
+        for Square {
+            id: _,
+            new_pos,
+            diagonal,
+            connect,
+        } in &replayed_state.squares
+        {
+            output += &format!(
+                "{} {} {} {} {} {} {} {}\n",
+                new_pos.x,
+                new_pos.y,
+                connect[0].x,
+                connect[0].y,
+                diagonal.x,
+                diagonal.y,
+                connect[1].x,
+                connect[1].y
+            );
+        }
+        output
+    }
+
+    pub fn report(&self, m: usize) -> SolveReport {
+        SolveReport {
+            loop_count: self.loop_count,
+            final_score: self.final_score,
+            best_score: self.state.score.base,
+            real_score: calc_real_score(&self.state.weight_map, m, self.state.score.base),
+            elapsed_seconds: time::elapsed_seconds(),
+            neighborhoods: self.neighborhood_selector.report(),
+        }
+    }
+}
+
+// 公式の盤面と重みで解いて、出力を返す。時間はまだ測り始めていなければここから測る
+pub fn solve(input: &Input, config: SolverConfig) -> String {
+    time::start_clock();
+    let time_limit = config.time_limit;
+    let mut solver = Solver::from_input(
+        input,
+        Grid::new(input.n),
+        WeightMap::center_distance(input.n, input.n),
+        config,
+    );
+    solver.solve(time_limit);
+    solver.output()
+}
+
+#[test]
+fn test_score_log_only_when_requested() {
+    let input = Input::parse("5 4\n1 1\n3 1\n1 3\n2 2\n").unwrap();
+    let mut config = SolverConfig::new();
+    config.iteration_limit = Some(1000);
+    let solve = |config: &SolverConfig| {
+        let mut solver = Solver::from_input(
+            &input,
+            Grid::new(input.n),
+            WeightMap::center_distance(input.n, input.n),
+            config.clone(),
+        );
+        solver.solve(config.time_limit);
+        solver.score_log
+    };
+    assert!(solve(&config).is_none());
+    config.telemetry.chart = Some("chart.svg".to_string());
+    let score_log = solve(&config).unwrap();
+    assert_eq!(score_log.entries.len(), 1000 / config.loop_interval);
+}
@@ -1,23 +1,72 @@
+use crate::def::*;
+use crate::framework::*;
 use crate::grid::*;
-use crate::*;
+use crate::journal::*;
+use crate::sampler::*;
+use crate::weight::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::rc::Rc;
+
+// 四角を作れない理由
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddError {
+    OutOfBoard(Pos),
+    Blocked(Pos),
+    Occupied(Pos),
+    CornerMissing(Pos),
+    Connect(ConnectError),
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddError::OutOfBoard(pos) => write!(f, "({}, {}) is out of the board", pos.x, pos.y),
+            AddError::Blocked(pos) => write!(f, "({}, {}) is a blocked cell", pos.x, pos.y),
+            AddError::Occupied(pos) => {
+                write!(f, "new point ({}, {}) is already occupied", pos.x, pos.y)
+            }
+            AddError::CornerMissing(pos) => {
+                write!(f, "corner ({}, {}) has no point", pos.x, pos.y)
+            }
+            AddError::Connect(e) => e.fmt(f),
+        }
+    }
+}
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct State {
     pub grid: Grid,
     pub squares: Vec<Square>,
     pub score: Score,
+    pub weight_map: Rc<WeightMap>,
+    pub sampler: Sampler,
 }
 
 impl State {
     pub fn new(n: usize, p: Vec<Pos>) -> State {
+        State::with_weight_map(n, p, Rc::new(WeightMap::center_distance(n, n)))
+    }
+
+    pub fn with_weight_map(n: usize, p: Vec<Pos>, weight_map: Rc<WeightMap>) -> State {
+        State::from_grid(Grid::new(n), p, weight_map)
+    }
+
+    pub fn from_grid(grid: Grid, p: Vec<Pos>, weight_map: Rc<WeightMap>) -> State {
+        debug_assert_eq!(weight_map.width, grid.width);
+        debug_assert_eq!(weight_map.height, grid.height);
         let mut state = State {
-            grid: Grid::new(n),
+            grid,
             squares: vec![],
             score: Score::new(),
+            weight_map,
+            sampler: Sampler::uniform(),
         };
         for pos in p.iter() {
-            state.grid.add_point(pos, Point::new(&pos), None);
-            state.score.base += state.weight(&pos);
+            debug_assert!(state.grid.is_valid(pos));
+            state.grid.add_point(pos, Point::new(pos), None);
+            state.score.base += state.weight(pos);
         }
         state
     }
@@ -25,27 +74,53 @@ impl State {
 
 impl State {
     pub fn can_perform_add(&mut self, square: &Square, is_reverse: bool) -> bool {
+        self.check_add(square, is_reverse).is_ok()
+    }
+
+    pub fn check_add(&self, square: &Square, is_reverse: bool) -> Result<(), AddError> {
         debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[0]));
         debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[1]));
         debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[0]));
         debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[1]));
 
+        for pos in square.all_pos() {
+            if !self.grid.is_inside(pos) {
+                return Err(AddError::OutOfBoard(*pos));
+            }
+        }
+
         // new_posに既に点がないか確認
+        if self.grid.is_blocked(&square.new_pos) {
+            return Err(AddError::Blocked(square.new_pos));
+        }
         if self.grid.has_point(&square.new_pos) {
-            return false;
+            return Err(AddError::Occupied(square.new_pos));
         }
 
-        // 作ろうとしてる四角の辺に既に点、辺がないか確認する
-        if !is_reverse
-            && (!self.grid.can_connect(&square.connect[0], &square.new_pos)
-                || !self.grid.can_connect(&square.connect[1], &square.new_pos)
-                || !self.grid.can_connect(&square.connect[0], &square.diagonal)
-                || !self.grid.can_connect(&square.connect[1], &square.diagonal))
-        {
-            return false;
+        if is_reverse {
+            return Ok(());
+        }
+
+        // 残りの頂点に点があるか確認
+        for pos in &[square.connect[0], square.diagonal, square.connect[1]] {
+            if !self.grid.has_point(pos) {
+                return Err(AddError::CornerMissing(*pos));
+            }
         }
 
-        true
+        // 作ろうとしてる四角の辺に既に点、辺がないか確認する
+        self.grid
+            .check_connect(&square.connect[0], &square.new_pos)
+            .and_then(|_| self.grid.check_connect(&square.connect[1], &square.new_pos))
+            .and_then(|_| {
+                self.grid
+                    .check_connect(&square.connect[0], &square.diagonal)
+            })
+            .and_then(|_| {
+                self.grid
+                    .check_connect(&square.connect[1], &square.diagonal)
+            })
+            .map_err(AddError::Connect)
     }
 
     pub fn perform_add(&mut self, square: &Square, is_reverse: bool) -> Vec<Command> {
@@ -53,12 +128,13 @@ impl State {
             return vec![];
         }
 
-        self.grid.create_square(&square, is_reverse);
+        self.grid.create_square(square, is_reverse);
 
-        self.squares.push(square.clone());
+        self.squares.push(*square);
+        self.grid.journal.record(Change::SquarePush);
 
         // スコアの更新
-        self.score.base += self.weight(&square.new_pos);
+        self.add_score(self.weight(&square.new_pos));
 
         vec![Command::Add { square: *square }]
     }
@@ -82,44 +158,42 @@ impl State {
         for created_point in &created_points {
             // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
             // TODO: 正当性の確認
-            if !self.grid.has_point(&created_point) {
+            if !self.grid.has_point(created_point) {
                 continue;
             }
-            let created_square = self
+            let created_square = *self
                 .grid
                 .point(created_point)
                 .as_ref()
                 .unwrap()
                 .added_info
                 .as_ref()
-                .unwrap()
-                .clone();
+                .unwrap();
             self.perform_delete(&created_square, performed_commands);
         }
 
-        self.grid.delete_square(&square);
+        self.grid.delete_square(square);
 
         // FIXME: O(n)
-        self.squares
-            .remove(self.squares.iter().position(|x| *x == *square).unwrap());
-        self.score.base -= self.weight(&square.new_pos);
+        let index = self.squares.iter().position(|x| *x == *square).unwrap();
+        self.squares.remove(index);
+        self.grid.journal.record(Change::SquareRemove {
+            index,
+            square: *square,
+        });
+        self.add_score(-self.weight(&square.new_pos));
         performed_commands.push(Command::Delete { square: *square });
     }
 
     pub fn sample_point_pos(&self) -> Pos {
-        loop {
-            let pos = Pos {
-                x: rnd::gen_range(0, self.grid.size) as i32,
-                y: rnd::gen_range(0, self.grid.size) as i32,
-            };
-            if self.grid.has_point(&pos) {
-                return pos;
-            }
-        }
+        self.sampler
+            .sample(&self.grid, self.grid.point_set.positions(), |pos| *pos)
     }
 
+    // 四角は作った点の位置で重みを付ける
     pub fn sample_square(&self) -> Square {
-        self.squares[rnd::gen_range(0, self.squares.len()) as usize]
+        self.sampler
+            .sample(&self.grid, &self.squares, |square| square.new_pos)
     }
 
     pub fn calc_deletion_size(
@@ -132,7 +206,7 @@ impl State {
 
         let created_points = self
             .grid
-            .point(&new_pos)
+            .point(new_pos)
             .as_ref()
             .unwrap()
             .created_points
@@ -144,10 +218,10 @@ impl State {
 
             // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
             // TODO: 正当性の確認
-            if !self.grid.has_point(&created_point) {
+            if !self.grid.has_point(created_point) {
                 continue;
             }
-            dep_size += self.calc_deletion_size(&created_point, recursion_limit, dep_size);
+            dep_size += self.calc_deletion_size(created_point, recursion_limit, dep_size);
         }
 
         dep_size - parent_dep_size
@@ -155,9 +229,156 @@ impl State {
 }
 
 impl State {
+    fn add_score(&mut self, delta: i32) {
+        self.grid.journal.record(Change::Score {
+            old: self.score.base,
+        });
+        self.score.base += delta;
+    }
+
+    // 以降の変更を記録し、rollbackで元に戻せるようにする
+    // 入れ子にでき、内側のcommitは外側のrollbackで戻る
+    pub fn begin(&mut self) {
+        self.grid.journal.begin();
+    }
+
+    pub fn commit(&mut self) {
+        self.grid.journal.commit();
+    }
+
+    // 合法性を確認せずに、記録した変更を逆順に戻す
+    pub fn rollback(&mut self) {
+        for change in self.grid.journal.pop_transaction() {
+            match change {
+                Change::SquarePush => {
+                    self.squares.pop();
+                }
+                Change::SquareRemove { index, square } => {
+                    self.squares.insert(index, square);
+                }
+                Change::Score { old } => {
+                    self.score.base = old;
+                }
+                _ => self.grid.undo(change),
+            }
+        }
+    }
+
+    pub fn initial_points(&self) -> Vec<Pos> {
+        let mut initial_points = vec![];
+        for row in &self.grid.points {
+            for point in row.iter().flatten() {
+                if point.added_info.is_none() {
+                    initial_points.push(point.pos);
+                }
+            }
+        }
+        initial_points
+    }
+
+    // idは作成順を保証しない（reverse_commandで古いidが戻ってくる）ので、
+    // 依存関係からトポロジカルソートして出力順を決める
+    pub fn ordered_squares(&self) -> Vec<Square> {
+        let square_count = self.squares.len();
+        let mut index_of_new_pos = vec![vec![None; self.grid.width]; self.grid.height];
+        for (i, square) in self.squares.iter().enumerate() {
+            index_of_new_pos[square.new_pos.y as usize][square.new_pos.x as usize] = Some(i);
+        }
+
+        // edges[i]に含まれる四角は、i番目の四角より後に作る必要がある
+        let mut edges: Vec<Vec<usize>> = vec![vec![]; square_count];
+        let mut in_degree: Vec<usize> = vec![0; square_count];
+        for (i, square) in self.squares.iter().enumerate() {
+            // 四角の頂点を作った四角が先
+            for pos in &[square.connect[0], square.diagonal, square.connect[1]] {
+                if let Some(j) = index_of_new_pos[pos.y as usize][pos.x as usize] {
+                    edges[j].push(i);
+                    in_degree[i] += 1;
+                }
+            }
+
+            // 辺上に点が作られた場合は、辺を作った四角が先
+            let corners = square.all_pos();
+            for k in 0..4 {
+                for pos in Pos::between(corners[k], corners[(k + 1) % 4]) {
+                    if let Some(j) = index_of_new_pos[pos.y as usize][pos.x as usize] {
+                        edges[i].push(j);
+                        in_degree[j] += 1;
+                    }
+                }
+            }
+        }
+
+        // 同じ順位の中ではidが小さいものを先に出す
+        let mut queue = BinaryHeap::new();
+        for (i, square) in self.squares.iter().enumerate() {
+            if in_degree[i] == 0 {
+                queue.push(Reverse((square.id, i)));
+            }
+        }
+        let mut ordered_squares = Vec::with_capacity(square_count);
+        while let Some(Reverse((_, i))) = queue.pop() {
+            ordered_squares.push(self.squares[i]);
+            for &j in &edges[i] {
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    queue.push(Reverse((self.squares[j].id, j)));
+                }
+            }
+        }
+        debug_assert_eq!(ordered_squares.len(), square_count);
+        ordered_squares
+    }
+
+    // 初期状態からsquaresの順に四角を作り直す
+    // 作れなかった四角（とそれに依存する四角）は飛ばされる
+    pub fn replay(&self, squares: &[Square]) -> State {
+        let mut state = State::from_grid(
+            self.grid.cleared(),
+            self.initial_points(),
+            self.weight_map.clone(),
+        );
+        state.sampler = self.sampler.clone();
+        for square in squares {
+            state.perform_add(square, false);
+        }
+        state
+    }
+}
+
+impl State {
+    #[inline]
     pub fn weight(&self, pos: &Pos) -> i32 {
-        let c = ((self.grid.size - 1) / 2) as i32;
-        (pos.y as i32 - c) * (pos.y as i32 - c) + (pos.x as i32 - c) * (pos.x as i32 - c) + 1
+        self.weight_map.get(pos)
+    }
+}
+
+impl IState for State {
+    #[allow(unused_variables)]
+    fn get_score(&self, progress: f32) -> f32 {
+        self.score.base as f32
+    }
+
+    fn perform_command(&mut self, command: &Command) -> Vec<Command> {
+        match command {
+            Command::Add { square } => self.perform_add(square, false),
+            Command::Delete { square } => {
+                let mut performed_commands: Vec<Command> = vec![];
+                self.perform_delete(square, &mut performed_commands);
+                performed_commands
+            }
+        }
+    }
+
+    fn reverse_command(&mut self, command: &Command) {
+        match command {
+            Command::Add { square } => {
+                let mut performed_commands: Vec<Command> = vec![];
+                self.perform_delete(square, &mut performed_commands);
+                performed_commands
+            }
+            Command::Delete { square } => self.perform_add(square, true),
+        };
     }
 }
 
@@ -175,9 +396,10 @@ fn test_calc_point_closeness() {
     state.grid.remove_point(&new_pos2);
     state.grid.remove_point(&new_pos);
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_add_point_on_square_edge() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -203,6 +425,7 @@ fn test_add_point_on_square_edge() {
     assert_eq!(state.perform_add(&square2, false).len(), 1);
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_perform_single_delete() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -216,9 +439,10 @@ fn test_perform_single_delete() {
     state.perform_add(&square, false);
     state.perform_delete(&square, &mut vec![]);
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_perform_recursive_delete() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -242,9 +466,10 @@ fn test_perform_recursive_delete() {
     state.perform_add(&square2, false);
     state.perform_delete(&square, &mut vec![]);
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
 }
 
+#[allow(clippy::assertions_on_constants, clippy::clone_on_copy)]
 #[test]
 fn test_perform_add() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -308,6 +533,7 @@ fn test_perform_add() {
     }
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_reverse_command() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -326,7 +552,7 @@ fn test_reverse_command() {
         square: square.clone(),
     });
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
 
     state.perform_command(&Command::Add {
         square: square.clone(),
@@ -340,9 +566,10 @@ fn test_reverse_command() {
         square: square.clone(),
     });
 
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
 }
 
+#[allow(clippy::clone_on_copy)]
 #[test]
 fn test_reverse_recursive_delete_command() {
     let diagonal = Pos { x: 0, y: 0 };
@@ -384,5 +611,156 @@ fn test_reverse_recursive_delete_command() {
         state.reverse_command(command);
     }
     assert_eq!(performed_commands.len(), 3);
-    assert_eq!(state, copied_state);
+    crate::ascii::assert_state_eq(&state, &copied_state);
+}
+
+#[test]
+fn test_ordered_squares() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let new_pos = Pos { x: 2, y: 2 };
+    let diagonal2 = Pos { x: 4, y: 1 };
+    let connect2: [Pos; 2] = [Pos { x: 3, y: 0 }, Pos { x: 3, y: 2 }];
+    let new_pos2 = Pos { x: 2, y: 1 };
+    let diagonal3 = Pos { x: 1, y: 3 };
+    let connect3: [Pos; 2] = [Pos { x: 2, y: 2 }, Pos { x: 0, y: 2 }];
+    let new_pos3 = Pos { x: 1, y: 1 };
+    let n: usize = 5;
+    let p = vec![
+        diagonal,
+        connect[0],
+        connect[1],
+        diagonal2,
+        connect2[0],
+        connect2[1],
+        diagonal3,
+    ];
+    let mut state = State::new(n, p);
+
+    // idの順番と作成順が逆になるようにする
+    let square3 = Square::new(new_pos3, diagonal3, connect3);
+    let square2 = Square::new(new_pos2, diagonal2, connect2);
+    let square = Square::new(new_pos, diagonal, connect);
+    assert_eq!(state.perform_add(&square, false).len(), 1);
+    assert_eq!(state.perform_add(&square2, false).len(), 1);
+    assert_eq!(state.perform_add(&square3, false).len(), 1);
+
+    let ordered_squares = state.ordered_squares();
+    assert_eq!(ordered_squares[0], square);
+
+    let replayed_state = state.replay(&ordered_squares);
+    assert_eq!(replayed_state.squares.len(), 3);
+    assert_eq!(replayed_state.score, state.score);
+}
+
+#[test]
+fn test_rollback() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let connect2: [Pos; 2] = [Pos { x: 2, y: 4 }, Pos { x: 4, y: 2 }];
+    let connect3: [Pos; 2] = [Pos { x: 0, y: 4 }, Pos { x: 4, y: 4 }];
+    let new_pos = Pos { x: 2, y: 2 };
+    let new_pos2 = Pos { x: 4, y: 4 };
+    let new_pos3 = Pos { x: 2, y: 6 };
+    let n: usize = 10;
+    let p = vec![
+        diagonal,
+        connect[0],
+        connect[1],
+        connect2[0],
+        connect2[1],
+        connect3[0],
+    ];
+    let mut state = State::new(n, p);
+    let square = Square::new(new_pos, diagonal, connect);
+    let square2 = Square::new(new_pos2, new_pos, connect2);
+    let square3 = Square::new(new_pos3, new_pos, connect3);
+    let copied_state = state.clone();
+
+    state.begin();
+    state.perform_add(&square, false);
+    state.perform_add(&square2, false);
+    state.perform_add(&square3, false);
+    state.rollback();
+    crate::ascii::assert_state_eq(&state, &copied_state);
+
+    state.begin();
+    state.perform_add(&square, false);
+    state.perform_add(&square2, false);
+    state.commit();
+    let copied_state = state.clone();
+
+    // 入れ子にしたトランザクション
+    state.begin();
+    state.perform_add(&square3, false);
+    state.begin();
+    state.perform_delete(&square, &mut vec![]);
+    assert_eq!(state.squares.len(), 0);
+    state.rollback();
+    assert_eq!(state.squares.len(), 3);
+    state.begin();
+    state.perform_delete(&square2, &mut vec![]);
+    state.commit();
+    assert_eq!(state.squares.len(), 1);
+    state.rollback();
+    crate::ascii::assert_state_eq(&state, &copied_state);
+}
+
+#[test]
+fn test_uniform_weight() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let new_pos = Pos { x: 2, y: 2 };
+    let n: usize = 5;
+    let p = vec![diagonal, connect[0], connect[1]];
+    let mut state = State::with_weight_map(n, p, Rc::new(WeightMap::uniform(n, n)));
+    assert_eq!(state.score.base, 3);
+
+    let square = Square::new(new_pos, diagonal, connect);
+    state.perform_add(&square, false);
+    assert_eq!(state.score.base, 4);
+    crate::ascii::assert_state_eq(&state.replay(&state.ordered_squares()), &state);
+    assert_eq!(
+        crate::util::calc_real_score(&state.weight_map, 3, state.score.base),
+        1_333_333
+    );
+}
+
+#[test]
+fn test_blocked_board() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 3, y: 0 }, Pos { x: 0, y: 2 }];
+    let new_pos = Pos { x: 3, y: 2 };
+    let blocked_pos = Pos { x: 3, y: 1 };
+    let (width, height): (usize, usize) = (5, 3);
+    let p = vec![diagonal, connect[0], connect[1]];
+    let weight_map = Rc::new(WeightMap::center_distance(width, height));
+    let square = Square::new(new_pos, diagonal, connect);
+
+    let mut state = State::from_grid(
+        Grid::with_board(width, height, &[]),
+        p.clone(),
+        weight_map.clone(),
+    );
+    assert_eq!(state.perform_add(&square, false).len(), 1);
+    crate::ascii::assert_state_eq(&state.replay(&state.ordered_squares()), &state);
+
+    // 置けないマスを辺が通る
+    let mut state = State::from_grid(
+        Grid::with_board(width, height, &[blocked_pos]),
+        p.clone(),
+        weight_map.clone(),
+    );
+    assert_eq!(state.perform_add(&square, false).len(), 0);
+
+    // 置けないマスの先にある点は見えない
+    let other = Pos { x: 4, y: 0 };
+    let state = State::from_grid(
+        Grid::with_board(width, height, &[connect[0]]),
+        vec![diagonal, other],
+        weight_map.clone(),
+    );
+    assert_eq!(state.grid.nearest_point_pos(&diagonal, &Dir::Right), None);
+    assert!(!state.grid.is_valid(&Pos { x: 5, y: 0 }));
+    assert!(!state.grid.is_valid(&Pos { x: 0, y: 3 }));
 }
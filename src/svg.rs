@@ -0,0 +1,361 @@
+use crate::def::*;
+use crate::neighborhood::*;
+use crate::state::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// 四角の色の付け方
+#[derive(Clone, Copy, Debug)]
+pub enum ColorBy<'a> {
+    Uniform,
+    // 依存している四角の深さ
+    Depth,
+    // 四角を作った近傍
+    Neighborhood(&'a HashMap<i32, Neighborhood>),
+    // 作った点の重み
+    Weight,
+}
+
+impl<'a> ColorBy<'a> {
+    // neighborhoodは、四角を作った近傍が分かるとき(originsがSome)だけ使える
+    pub fn parse(
+        s: &str,
+        origins: Option<&'a HashMap<i32, Neighborhood>>,
+    ) -> Result<ColorBy<'a>, String> {
+        match (s, origins) {
+            ("uniform", _) => Ok(ColorBy::Uniform),
+            ("depth", _) => Ok(ColorBy::Depth),
+            ("weight", _) => Ok(ColorBy::Weight),
+            ("neighborhood", Some(origins)) => Ok(ColorBy::Neighborhood(origins)),
+            ("neighborhood", None) => {
+                Err("color mode \"neighborhood\" is only available when solving".to_string())
+            }
+            _ => Err(format!("unknown color mode {:?}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SvgOptions<'a> {
+    pub color_by: ColorBy<'a>,
+    // マスの重みを背景に描く
+    pub heatmap: bool,
+    pub cell_size: f64,
+}
+
+impl SvgOptions<'_> {
+    pub fn new() -> SvgOptions<'static> {
+        SvgOptions {
+            color_by: ColorBy::Depth,
+            heatmap: false,
+            cell_size: 12.,
+        }
+    }
+}
+
+const MARGIN: f64 = 10.;
+const CATEGORY_COLORS: [&str; 8] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+// 0なら青、1なら赤
+pub fn gradient(t: f64) -> String {
+    let hue = 240. * (1. - t.clamp(0., 1.));
+    format!("hsl({:.0},75%,45%)", hue)
+}
+
+pub fn category_color(i: usize) -> &'static str {
+    CATEGORY_COLORS[i % CATEGORY_COLORS.len()]
+}
+
+pub fn neighborhood_color(neighborhood: &Neighborhood) -> &'static str {
+    category_color(*neighborhood as usize)
+}
+
+// 四角のidから、その四角が作った点の深さ
+pub fn square_depths(state: &State) -> HashMap<i32, usize> {
+    state
+        .squares
+        .iter()
+        .map(|square| {
+            let pos = &square.new_pos;
+            let point = state.grid.points[pos.y as usize][pos.x as usize].as_ref();
+            (square.id, point.unwrap().depth)
+        })
+        .collect()
+}
+
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: f64,
+}
+
+impl Canvas {
+    pub fn new(state: &State, cell_size: f64) -> Canvas {
+        Canvas {
+            width: state.grid.width,
+            height: state.grid.height,
+            cell_size,
+        }
+    }
+
+    // y軸は上向きなので反転する
+    pub fn x(&self, x: i32) -> f64 {
+        MARGIN + x as f64 * self.cell_size
+    }
+
+    pub fn y(&self, y: i32) -> f64 {
+        MARGIN + (self.height as i32 - 1 - y) as f64 * self.cell_size
+    }
+
+    pub fn pixel_width(&self) -> f64 {
+        2. * MARGIN + (self.width - 1) as f64 * self.cell_size
+    }
+
+    pub fn pixel_height(&self) -> f64 {
+        2. * MARGIN + (self.height - 1) as f64 * self.cell_size
+    }
+
+    pub fn points_attr(&self, square: &Square) -> String {
+        square
+            .all_pos()
+            .iter()
+            .map(|pos| format!("{:.1},{:.1}", self.x(pos.x), self.y(pos.y)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+// 格子、置けないマス、重み（任意）を描く
+pub fn render_board(state: &State, canvas: &Canvas, heatmap: bool, svg: &mut String) {
+    let cell = canvas.cell_size;
+    if heatmap {
+        let max_weight = (0..state.grid.height as i32)
+            .flat_map(|y| (0..state.grid.width as i32).map(move |x| Pos { x, y }))
+            .map(|pos| state.weight(&pos))
+            .max()
+            .unwrap_or(1);
+        for y in 0..state.grid.height as i32 {
+            for x in 0..state.grid.width as i32 {
+                let t = state.weight(&Pos { x, y }) as f64 / max_weight as f64;
+                writeln!(
+                    svg,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="rgb(255,{:.0},{:.0})"/>"#,
+                    canvas.x(x) - cell / 2.,
+                    canvas.y(y) - cell / 2.,
+                    cell,
+                    cell,
+                    255. - 120. * t,
+                    255. - 200. * t,
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    for x in 0..state.grid.width as i32 {
+        writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#ddd" stroke-width="0.5"/>"##,
+            canvas.x(x),
+            canvas.y(0),
+            canvas.x(x),
+            canvas.y(state.grid.height as i32 - 1),
+        )
+        .unwrap();
+    }
+    for y in 0..state.grid.height as i32 {
+        writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#ddd" stroke-width="0.5"/>"##,
+            canvas.x(0),
+            canvas.y(y),
+            canvas.x(state.grid.width as i32 - 1),
+            canvas.y(y),
+        )
+        .unwrap();
+    }
+
+    for pos in state.grid.blocked_cells() {
+        writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#444"/>"##,
+            canvas.x(pos.x) - cell / 2.,
+            canvas.y(pos.y) - cell / 2.,
+            cell,
+            cell,
+        )
+        .unwrap();
+    }
+}
+
+pub fn render_points(state: &State, canvas: &Canvas, svg: &mut String) {
+    let r = canvas.cell_size * 0.25;
+    for row in &state.grid.points {
+        for point in row.iter().flatten() {
+            let (fill, stroke) = if point.added_info.is_none() {
+                ("#000", "#000")
+            } else {
+                ("#fff", "#333")
+            };
+            writeln!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="{}" stroke="{}" stroke-width="1"/>"#,
+                canvas.x(point.pos.x),
+                canvas.y(point.pos.y),
+                r,
+                fill,
+                stroke,
+            )
+            .unwrap();
+        }
+    }
+}
+
+pub fn square_colors(state: &State, color_by: &ColorBy) -> HashMap<i32, String> {
+    match color_by {
+        ColorBy::Uniform => state
+            .squares
+            .iter()
+            .map(|square| (square.id, "#1f77b4".to_string()))
+            .collect(),
+        ColorBy::Depth => {
+            let depths = square_depths(state);
+            let max_depth = depths.values().copied().max().unwrap_or(1);
+            depths
+                .iter()
+                .map(|(&id, &depth)| (id, gradient(depth as f64 / max_depth as f64)))
+                .collect()
+        }
+        ColorBy::Neighborhood(origins) => state
+            .squares
+            .iter()
+            .map(|square| {
+                let color = origins.get(&square.id).map_or("#888", neighborhood_color);
+                (square.id, color.to_string())
+            })
+            .collect(),
+        ColorBy::Weight => {
+            let max_weight = state
+                .squares
+                .iter()
+                .map(|square| state.weight(&square.new_pos))
+                .max()
+                .unwrap_or(1);
+            state
+                .squares
+                .iter()
+                .map(|square| {
+                    let t = state.weight(&square.new_pos) as f64 / max_weight as f64;
+                    (square.id, gradient(t))
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn render_squares(
+    squares: &[Square],
+    colors: &HashMap<i32, String>,
+    canvas: &Canvas,
+    svg: &mut String,
+) {
+    for square in squares {
+        let color = colors.get(&square.id).map_or("#888", |c| c.as_str());
+        writeln!(
+            svg,
+            r#"<polygon points="{}" fill="{}" fill-opacity="0.08" stroke="{}" stroke-width="1.5"/>"#,
+            canvas.points_attr(square),
+            color,
+            color,
+        )
+        .unwrap();
+    }
+}
+
+pub fn render_legend(color_by: &ColorBy, canvas: &Canvas, svg: &mut String) {
+    if let ColorBy::Neighborhood(_) = color_by {
+        for (i, neighborhood) in Neighborhood::all().iter().enumerate() {
+            writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-size="10" fill="{}">{:?}</text>"#,
+                canvas.pixel_width() + 4.,
+                MARGIN + 12. * (i + 1) as f64,
+                neighborhood_color(neighborhood),
+                neighborhood,
+            )
+            .unwrap();
+        }
+    }
+}
+
+pub fn render_state(state: &State, options: &SvgOptions) -> String {
+    let canvas = Canvas::new(state, options.cell_size);
+    let legend_width = match options.color_by {
+        ColorBy::Neighborhood(_) => 90.,
+        _ => 0.,
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {:.0} {:.0}">"#,
+        canvas.pixel_width() + legend_width,
+        canvas.pixel_height(),
+        canvas.pixel_width() + legend_width,
+        canvas.pixel_height(),
+    )
+    .unwrap();
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    render_board(state, &canvas, options.heatmap, &mut svg);
+    let colors = square_colors(state, &options.color_by);
+    render_squares(&state.squares, &colors, &canvas, &mut svg);
+    render_points(state, &canvas, &mut svg);
+    render_legend(&options.color_by, &canvas, &mut svg);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[test]
+fn test_render_state() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let connect2: [Pos; 2] = [Pos { x: 2, y: 4 }, Pos { x: 4, y: 2 }];
+    let new_pos = Pos { x: 2, y: 2 };
+    let new_pos2 = Pos { x: 4, y: 4 };
+    let n: usize = 5;
+    let p = vec![diagonal, connect[0], connect[1], connect2[0], connect2[1]];
+    let mut state = State::new(n, p);
+    let square = Square::new(new_pos, diagonal, connect);
+    let square2 = Square::new(new_pos2, new_pos, connect2);
+    state.perform_add(&square, false);
+    state.perform_add(&square2, false);
+
+    let depths = square_depths(&state);
+    assert_eq!(depths[&square.id], 1);
+    assert_eq!(depths[&square2.id], 2);
+
+    let mut options = SvgOptions::new();
+    options.heatmap = true;
+    let svg = render_state(&state, &options);
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<polygon").count(), 2);
+    assert_eq!(svg.matches("<circle").count(), 7);
+    assert_eq!(svg.matches(r#"<rect x="#).count(), 25);
+
+    let origins: HashMap<i32, Neighborhood> = [(square.id, Neighborhood::ChangeSquare)]
+        .into_iter()
+        .collect();
+    options.color_by = ColorBy::Neighborhood(&origins);
+    let svg = render_state(&state, &options);
+    assert!(svg.contains(neighborhood_color(&Neighborhood::ChangeSquare)));
+    assert!(svg.contains(">ChangeSquare</text>"));
+
+    assert!(matches!(
+        ColorBy::parse("neighborhood", Some(&origins)),
+        Ok(ColorBy::Neighborhood(_))
+    ));
+    assert!(ColorBy::parse("neighborhood", None).is_err());
+    assert!(matches!(ColorBy::parse("depth", None), Ok(ColorBy::Depth)));
+}
@@ -0,0 +1,187 @@
+use crate::def::*;
+use crate::state::*;
+use std::fmt::Write;
+
+// 焼きなまし途中のある時点の状態
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub iteration: usize,
+    pub temp: f32,
+    // 内部スコア（重みの和）
+    pub score: i32,
+    // 初期状態から作り直せる順に並べた四角
+    pub squares: Vec<Square>,
+}
+
+// interval回ごとにSnapshotを記録する
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    pub interval: usize,
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl Trajectory {
+    pub fn new(interval: usize) -> Trajectory {
+        Trajectory {
+            interval: usize::max(1, interval),
+            snapshots: vec![],
+        }
+    }
+
+    pub fn should_record(&self, iteration: usize) -> bool {
+        iteration % self.interval == 0
+    }
+
+    pub fn record(&mut self, iteration: usize, temp: f32, state: &State) {
+        self.snapshots.push(Snapshot {
+            iteration,
+            temp,
+            score: state.score.base,
+            squares: state.ordered_squares(),
+        });
+    }
+}
+
+#[test]
+fn test_trajectory() {
+    let diagonal = Pos { x: 0, y: 0 };
+    let connect: [Pos; 2] = [Pos { x: 2, y: 0 }, Pos { x: 0, y: 2 }];
+    let new_pos = Pos { x: 2, y: 2 };
+    let p = vec![diagonal, connect[0], connect[1]];
+    let mut state = State::new(5, p);
+
+    let mut trajectory = Trajectory::new(10);
+    assert!(trajectory.should_record(0));
+    assert!(!trajectory.should_record(5));
+    trajectory.record(0, 100., &state);
+    state.perform_add(&Square::new(new_pos, diagonal, connect), false);
+    trajectory.record(10, 50., &state);
+    assert_eq!(trajectory.snapshots[0].squares.len(), 0);
+    assert_eq!(trajectory.snapshots[1].squares.len(), 1);
+    assert_eq!(trajectory.snapshots[1].score, state.score.base);
+}
+
+// LOOP_INTERVAL回ごとのスコア、温度、近傍ごとの累計試行・採用回数
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub iteration: usize,
+    pub temp: f32,
+    pub score: i32,
+    pub best_score: i32,
+    pub total: Vec<i32>,
+    pub adopted: Vec<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreLog {
+    pub neighborhoods: Vec<String>,
+    pub entries: Vec<LogEntry>,
+}
+
+impl ScoreLog {
+    pub fn new(neighborhoods: Vec<String>) -> ScoreLog {
+        ScoreLog {
+            neighborhoods,
+            entries: vec![],
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        iteration: usize,
+        temp: f32,
+        score: i32,
+        best_score: i32,
+        total: &[i32],
+        adopted: &[i32],
+    ) {
+        debug_assert_eq!(total.len(), self.neighborhoods.len());
+        self.entries.push(LogEntry {
+            iteration,
+            temp,
+            score,
+            best_score,
+            total: total.to_vec(),
+            adopted: adopted.to_vec(),
+        });
+    }
+
+    // タブ区切りで、1行目はヘッダ
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from("iteration\ttemp\tscore\tbest_score");
+        for name in &self.neighborhoods {
+            write!(tsv, "\t{}.total\t{}.adopted", name, name).unwrap();
+        }
+        tsv.push('\n');
+        for entry in &self.entries {
+            write!(
+                tsv,
+                "{}\t{}\t{}\t{}",
+                entry.iteration, entry.temp, entry.score, entry.best_score
+            )
+            .unwrap();
+            for (total, adopted) in entry.total.iter().zip(&entry.adopted) {
+                write!(tsv, "\t{}\t{}", total, adopted).unwrap();
+            }
+            tsv.push('\n');
+        }
+        tsv
+    }
+
+    pub fn parse_tsv(s: &str) -> Result<ScoreLog, String> {
+        let mut lines = s.lines().enumerate();
+        let header: Vec<&str> = match lines.next() {
+            Some((_, line)) => line.split('\t').collect(),
+            None => return Err("empty score log".to_string()),
+        };
+        if header.len() < 4 || (header.len() - 4) % 2 != 0 {
+            return Err(format!("invalid header {:?}", header.join("\t")));
+        }
+        let neighborhoods = header[4..]
+            .chunks(2)
+            .map(|names| names[0].trim_end_matches(".total").to_string())
+            .collect();
+        let mut log = ScoreLog::new(neighborhoods);
+
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values: Vec<&str> = line.split('\t').collect();
+            if values.len() != header.len() {
+                return Err(format!(
+                    "line {}: expected {} columns, found {}",
+                    i + 1,
+                    header.len(),
+                    values.len()
+                ));
+            }
+            let invalid = |v: &str| format!("line {}: invalid value {:?}", i + 1, v);
+            let int = |v: &str| v.parse::<i32>().map_err(|_| invalid(v));
+            let counts = values[4..]
+                .iter()
+                .map(|v| int(v))
+                .collect::<Result<Vec<i32>, _>>()?;
+            log.entries.push(LogEntry {
+                iteration: values[0].parse().map_err(|_| invalid(values[0]))?,
+                temp: values[1].parse().map_err(|_| invalid(values[1]))?,
+                score: int(values[2])?,
+                best_score: int(values[3])?,
+                total: counts.iter().step_by(2).copied().collect(),
+                adopted: counts.iter().skip(1).step_by(2).copied().collect(),
+            });
+        }
+        Ok(log)
+    }
+}
+
+#[test]
+fn test_score_log_tsv() {
+    let mut log = ScoreLog::new(vec!["Add".to_string(), "Delete".to_string()]);
+    log.record(0, 100., 1000, 1000, &[0, 0], &[0, 0]);
+    log.record(100, 99.5, 1200, 1300, &[90, 10], &[40, 2]);
+    let tsv = log.to_tsv();
+    assert!(tsv.starts_with("iteration\ttemp\tscore\tbest_score\tAdd.total\tAdd.adopted\t"));
+    assert_eq!(ScoreLog::parse_tsv(&tsv), Ok(log));
+    assert!(ScoreLog::parse_tsv("iteration\ttemp\tscore\tbest_score\n1\t2\n").is_err());
+}
@@ -1,17 +1,29 @@
-use crate::Pos;
+use crate::weight::WeightMap;
 
 pub mod rnd {
-    #[allow(unused)]
-    static mut S: usize = 88172645463325252;
+    use std::cell::Cell;
+
+    // 並列に解くときに干渉しないように、スレッドごとに持つ
+    thread_local! {
+        static S: Cell<usize> = const { Cell::new(88172645463325252) };
+    }
 
     #[allow(unused)]
     #[inline]
     pub fn next() -> usize {
-        unsafe {
-            S = S ^ S << 7;
-            S = S ^ S >> 9;
-            S
-        }
+        S.with(|s| {
+            let mut x = s.get();
+            x = x ^ x << 7;
+            x = x ^ x >> 9;
+            s.set(x);
+            x
+        })
+    }
+
+    // 再現できるように、このスレッドの乱数を初期化する
+    #[allow(unused)]
+    pub fn seed(seed: u64) {
+        S.with(|s| s.set(Xorshift::new(seed).s));
     }
 
     #[allow(unused)]
@@ -25,15 +37,69 @@ pub mod rnd {
     pub fn gen_range(low: usize, high: usize) -> usize {
         (next() % (high - low)) + low
     }
+
+    // グローバルな乱数とは独立した、シード付きの乱数
+    #[derive(Clone, Debug)]
+    pub struct Xorshift {
+        s: usize,
+    }
+
+    impl Xorshift {
+        pub fn new(seed: u64) -> Xorshift {
+            // 近いシードでも列がばらけるようにsplitmix64で混ぜる
+            let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+            Xorshift {
+                s: if z == 0 {
+                    88172645463325252
+                } else {
+                    z as usize
+                },
+            }
+        }
+
+        #[inline]
+        #[allow(clippy::should_implement_trait)]
+        pub fn next(&mut self) -> usize {
+            self.s = self.s ^ self.s << 7;
+            self.s = self.s ^ self.s >> 9;
+            self.s
+        }
+
+        #[inline]
+        pub fn nextf(&mut self) -> f64 {
+            (self.next() & 4294967295) as f64 / 4294967296.
+        }
+
+        // [low, high]
+        #[inline]
+        pub fn gen_range_inclusive(&mut self, low: usize, high: usize) -> usize {
+            (self.next() % (high - low + 1)) + low
+        }
+    }
 }
 
 pub mod time {
-    static mut START: f64 = -1.;
+    use std::cell::Cell;
+
+    thread_local! {
+        static START: Cell<f64> = const { Cell::new(-1.) };
+    }
+
     #[allow(unused)]
     pub fn start_clock() {
         let _ = elapsed_seconds();
     }
 
+    // 複数の入力を続けて解くときに、時間を測り直す
+    #[allow(unused)]
+    pub fn restart_clock() {
+        START.with(|start| start.set(-1.));
+        start_clock();
+    }
+
     #[allow(unused)]
     #[inline]
     pub fn elapsed_seconds() -> f64 {
@@ -41,33 +107,18 @@ pub mod time {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs_f64();
-        unsafe {
-            if START < 0. {
-                START = t;
+        START.with(|start| {
+            if start.get() < 0. {
+                start.set(t);
             }
-            t - START
-        }
+            t - start.get()
+        })
     }
 }
 
-pub fn calc_weight(n: i32, pos: &Pos) -> i32 {
-    let c = ((n - 1) / 2) as i32;
-    (pos.y as i32 - c) * (pos.y as i32 - c) + (pos.x as i32 - c) * (pos.x as i32 - c) + 1
-}
-
-pub fn calc_real_score(n: usize, m: usize, score: i32) -> i32 {
-    let mut s = 0;
-    for i in 0..n {
-        for j in 0..n {
-            s += calc_weight(
-                n as i32,
-                &Pos {
-                    x: i as i32,
-                    y: j as i32,
-                },
-            );
-        }
-    }
-    let result = 1e6 * (n as f32 * n as f32) * score as f32 / (m as f32 * s as f32);
+pub fn calc_real_score(weight_map: &WeightMap, m: usize, score: i32) -> i32 {
+    let cell_count = (weight_map.width * weight_map.height) as f64;
+    let s = weight_map.total();
+    let result = 1e6 * cell_count * score as f64 / (m as f64 * s as f64);
     result.round() as i32
 }
@@ -0,0 +1,293 @@
+use crate::def::*;
+use crate::grid::*;
+use crate::input::*;
+use crate::state::*;
+use crate::weight::*;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    Malformed(String),
+    WrongGeometry,
+    Add(AddError),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Malformed(message) => write!(f, "malformed line: {}", message),
+            Violation::WrongGeometry => write!(
+                f,
+                "points are not the corners of an axis-aligned or 45-degree rectangle in order"
+            ),
+            Violation::Add(e) => e.fmt(f),
+        }
+    }
+}
+
+// 入力、出力のどちらの何行目で、何が起きたか
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    Input(InputError),
+    // 入力の点が盤面の外か、置けないマスにある
+    BlockedPoint(Pos),
+    Output { line: usize, violation: Violation },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Input(e) => write!(f, "input {}", e),
+            ValidationError::BlockedPoint(pos) => {
+                write!(f, "input point ({}, {}) is on a blocked cell", pos.x, pos.y)
+            }
+            ValidationError::Output { line, violation } => {
+                write!(f, "output line {}: {}", line, violation)
+            }
+        }
+    }
+}
+
+fn output_error(line: usize, violation: Violation) -> ValidationError {
+    ValidationError::Output { line, violation }
+}
+
+// 頂点が順に並んでいて、各辺が縦横か斜め45度の長方形になっているか
+pub fn is_rectangle(p: &[Pos; 4]) -> bool {
+    for i in 0..4 {
+        if !Pos::is_aligned(&p[i], &p[(i + 1) % 4]) {
+            return false;
+        }
+    }
+    let a = &p[1] - &p[0];
+    let b = &p[3] - &p[0];
+    let is_parallelogram = &p[0] + &p[2] == &p[1] + &p[3];
+    let is_perpendicular = a.x * b.x + a.y * b.y == 0;
+    is_parallelogram && is_perpendicular
+}
+
+// 盤面の外の頂点は、座標の引き算があふれないように形を確かめる前に弾く
+pub fn parse_square(values: &[i32], grid: &Grid) -> Result<Square, Violation> {
+    if values.len() != 8 {
+        return Err(Violation::Malformed(format!(
+            "expected 8 integers, found {}",
+            values.len()
+        )));
+    }
+    let p: [Pos; 4] = [
+        Pos {
+            x: values[0],
+            y: values[1],
+        },
+        Pos {
+            x: values[2],
+            y: values[3],
+        },
+        Pos {
+            x: values[4],
+            y: values[5],
+        },
+        Pos {
+            x: values[6],
+            y: values[7],
+        },
+    ];
+    if let Some(pos) = p.iter().find(|pos| !grid.is_inside(pos)) {
+        return Err(Violation::Add(AddError::OutOfBoard(*pos)));
+    }
+    if !is_rectangle(&p) {
+        return Err(Violation::WrongGeometry);
+    }
+    Ok(Square::new(p[0], p[2], [p[1], p[3]]))
+}
+
+// 初期状態から出力の四角を順に作り、最初に見つかった違反を返す
+pub fn validate_output(mut state: State, output: &str) -> Result<State, ValidationError> {
+    let mut lines = parse_lines(output);
+    let (line, header) = lines
+        .next()
+        .ok_or_else(|| output_error(1, Violation::Malformed("missing K".to_string())))?;
+    let header = header.map_err(|e| output_error(line, Violation::Malformed(e)))?;
+    if header.len() != 1 || header[0] < 0 {
+        return Err(output_error(
+            line,
+            Violation::Malformed("expected a single non-negative K".to_string()),
+        ));
+    }
+    let k = header[0] as usize;
+
+    let mut last_line = line;
+    for (line, values) in lines {
+        last_line = line;
+        if state.squares.len() == k {
+            return Err(output_error(
+                line,
+                Violation::Malformed(format!("more than K = {} squares", k)),
+            ));
+        }
+        let values = values.map_err(|e| output_error(line, Violation::Malformed(e)))?;
+        let square = parse_square(&values, &state.grid).map_err(|e| output_error(line, e))?;
+        state
+            .check_add(&square, false)
+            .map_err(|e| output_error(line, Violation::Add(e)))?;
+        state.perform_add(&square, false);
+    }
+    if state.squares.len() != k {
+        return Err(output_error(
+            last_line + 1,
+            Violation::Malformed(format!(
+                "expected K = {} squares, found {}",
+                k,
+                state.squares.len()
+            )),
+        ));
+    }
+    Ok(state)
+}
+
+// 入力の点と盤面、重みから初期状態を作って、出力を確かめる
+pub fn validate(
+    input: &Input,
+    grid: Grid,
+    weight_map: WeightMap,
+    output: &str,
+) -> Result<State, ValidationError> {
+    if let Some(pos) = input.points.iter().find(|pos| !grid.is_valid(pos)) {
+        return Err(ValidationError::BlockedPoint(*pos));
+    }
+    let state = State::from_grid(grid, input.points.clone(), Rc::new(weight_map));
+    validate_output(state, output)
+}
+
+// 公式の盤面と重みで確かめる
+pub fn validate_official(input: &str, output: &str) -> Result<State, ValidationError> {
+    let input = Input::parse(input).map_err(ValidationError::Input)?;
+    let (grid, weight_map) = (
+        Grid::new(input.n),
+        WeightMap::center_distance(input.n, input.n),
+    );
+    validate(&input, grid, weight_map, output)
+}
+
+#[test]
+fn test_validate() {
+    let input = "5 4\n0 0\n2 0\n0 2\n2 4\n";
+    let state = validate_official(input, "1\n2 2 2 0 0 0 0 2\n").unwrap();
+    assert_eq!(state.squares.len(), 1);
+    assert!(validate_official(input, "0\n").is_ok());
+
+    let check = |output: &str, line: usize, violation: Violation| {
+        assert_eq!(
+            validate_official(input, output),
+            Err(ValidationError::Output { line, violation })
+        );
+    };
+    check(
+        "1\n2 0 2 2 0 2 0 0\n",
+        2,
+        Violation::Add(AddError::Occupied(Pos { x: 2, y: 0 })),
+    );
+    check(
+        "1\n4 4 4 2 2 2 2 4\n",
+        2,
+        Violation::Add(AddError::CornerMissing(Pos { x: 2, y: 2 })),
+    );
+    check(
+        "2\n2 2 2 0 0 0 0 2\n4 2 4 0 2 0 2 2\n",
+        3,
+        Violation::Add(AddError::CornerMissing(Pos { x: 4, y: 0 })),
+    );
+    check(
+        "1\n2 5 2 4 0 4 0 5\n",
+        2,
+        Violation::Add(AddError::OutOfBoard(Pos { x: 2, y: 5 })),
+    );
+    check(
+        "1\n2147483647 2147483647 -2147483648 -2147483648 0 0 0 0\n",
+        2,
+        Violation::Add(AddError::OutOfBoard(Pos {
+            x: 2147483647,
+            y: 2147483647,
+        })),
+    );
+    check("1\n2 2 2 0 0 0 0 1\n", 2, Violation::WrongGeometry);
+    check("1\n2 2 0 0 2 0 0 2\n", 2, Violation::WrongGeometry);
+    check(
+        "1\n2 2 2 0 0 0\n",
+        2,
+        Violation::Malformed("expected 8 integers, found 6".to_string()),
+    );
+    check(
+        "2\n2 2 2 0 0 0 0 2\n",
+        3,
+        Violation::Malformed("expected K = 2 squares, found 1".to_string()),
+    );
+    check(
+        "0\n2 2 2 0 0 0 0 2\n",
+        2,
+        Violation::Malformed("more than K = 0 squares".to_string()),
+    );
+
+    assert!(matches!(
+        validate_official("5 2\n0 0\n", "0\n"),
+        Err(ValidationError::Input(InputError::CountMismatch { .. }))
+    ));
+}
+
+#[test]
+fn test_validate_side() {
+    // (1, 0)が辺上にある
+    let input = "5 4\n0 0\n1 0\n2 0\n0 2\n";
+    assert_eq!(
+        validate_official(input, "1\n2 2 2 0 0 0 0 2\n"),
+        Err(ValidationError::Output {
+            line: 2,
+            violation: Violation::Add(AddError::Connect(crate::grid::ConnectError::PointOnSide(
+                Pos { x: 1, y: 0 }
+            )))
+        })
+    );
+
+    // 2つ目の四角の辺が1つ目の四角の辺(0, 2)-(2, 2)と重なる
+    let input = "5 4\n0 0\n2 0\n0 2\n2 4\n";
+    assert_eq!(
+        validate_official(input, "2\n2 2 2 0 0 0 0 2\n0 4 0 2 2 2 2 4\n"),
+        Err(ValidationError::Output {
+            line: 3,
+            violation: Violation::Add(AddError::Connect(crate::grid::ConnectError::EdgeOverlap(
+                Pos { x: 0, y: 2 }
+            )))
+        })
+    );
+}
+
+#[test]
+fn test_validate_board() {
+    // (1, 1)は置けないので、(0, 1)-(2, 1)を辺にする四角は作れない
+    let grid = Grid::parse_board("4 3\n....\n.#..\n....\n").unwrap();
+    let input = Input::parse_within("3 4\n0 2\n2 2\n2 1\n3 1\n", 4, 3).unwrap();
+    let validate_on_board = |output: &str| {
+        validate(
+            &input,
+            grid.clone(),
+            WeightMap::center_distance(4, 3),
+            output,
+        )
+    };
+    let state = validate_on_board("1\n3 2 2 2 2 1 3 1\n").unwrap();
+    assert_eq!(state.grid.blocked_cells(), vec![Pos { x: 1, y: 1 }]);
+    assert_eq!(
+        validate_on_board("1\n0 1 2 1 2 2 0 2\n"),
+        Err(ValidationError::Output {
+            line: 2,
+            violation: Violation::Add(AddError::Connect(ConnectError::Blocked(Pos { x: 1, y: 1 })))
+        })
+    );
+
+    let input = Input::parse_within("3 1\n1 1\n", 4, 3).unwrap();
+    assert_eq!(
+        validate(&input, grid, WeightMap::center_distance(4, 3), "0\n"),
+        Err(ValidationError::BlockedPoint(Pos { x: 1, y: 1 }))
+    );
+}
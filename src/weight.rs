@@ -0,0 +1,202 @@
+use crate::def::*;
+use std::fmt;
+use std::fs;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WeightError {
+    Malformed(String),
+    // 重みの和で正規化するので、和が正でないとスコアが求まらない
+    NonPositiveTotal(i64),
+}
+
+impl fmt::Display for WeightError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeightError::Malformed(message) => write!(f, "{}", message),
+            WeightError::NonPositiveTotal(total) => {
+                write!(f, "total weight must be positive, found {}", total)
+            }
+        }
+    }
+}
+
+// 各マスの点の重み
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeightMap {
+    pub width: usize,
+    pub height: usize,
+    weights: Vec<Vec<i32>>,
+    total: i64,
+}
+
+impl WeightMap {
+    pub fn from_fn<F: Fn(&Pos) -> i32>(width: usize, height: usize, f: F) -> WeightMap {
+        let weights: Vec<Vec<i32>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        f(&Pos {
+                            x: x as i32,
+                            y: y as i32,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        let total = weights.iter().flatten().map(|&w| w as i64).sum();
+        WeightMap {
+            width,
+            height,
+            weights,
+            total,
+        }
+    }
+
+    // AHC014の重み (x-c)^2+(y-c)^2+1
+    pub fn center_distance(width: usize, height: usize) -> WeightMap {
+        let center = Pos {
+            x: ((width - 1) / 2) as i32,
+            y: ((height - 1) / 2) as i32,
+        };
+        WeightMap::off_center(width, height, center)
+    }
+
+    // 中心をcenterにずらした重み
+    pub fn off_center(width: usize, height: usize, center: Pos) -> WeightMap {
+        WeightMap::from_fn(width, height, |pos| Pos::weight(pos, &center) + 1)
+    }
+
+    // 点の数を最大化する
+    pub fn uniform(width: usize, height: usize) -> WeightMap {
+        WeightMap::from_fn(width, height, |_| 1)
+    }
+
+    // height行、各行width個の整数で、i行目がy=iの重みを表す
+    pub fn parse_matrix(width: usize, height: usize, s: &str) -> Result<WeightMap, WeightError> {
+        let mut rows = vec![];
+        for line in s.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = line
+                .split_whitespace()
+                .map(|v| v.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|e| WeightError::Malformed(format!("line {}: {}", rows.len() + 1, e)))?;
+            if row.len() != width {
+                return Err(WeightError::Malformed(format!(
+                    "line {}: expected {} weights, found {}",
+                    rows.len() + 1,
+                    width,
+                    row.len()
+                )));
+            }
+            rows.push(row);
+        }
+        if rows.len() != height {
+            return Err(WeightError::Malformed(format!(
+                "expected {} rows, found {}",
+                height,
+                rows.len()
+            )));
+        }
+        let weight_map =
+            WeightMap::from_fn(width, height, |pos| rows[pos.y as usize][pos.x as usize]);
+        if weight_map.total <= 0 {
+            return Err(WeightError::NonPositiveTotal(weight_map.total));
+        }
+        Ok(weight_map)
+    }
+
+    // center | uniform | center:x,y | file:path
+    pub fn from_spec(width: usize, height: usize, spec: &str) -> Result<WeightMap, WeightError> {
+        if spec == "center" {
+            return Ok(WeightMap::center_distance(width, height));
+        }
+        if spec == "uniform" {
+            return Ok(WeightMap::uniform(width, height));
+        }
+        if let Some(center) = spec.strip_prefix("center:") {
+            let v = center
+                .split(',')
+                .map(|v| v.trim().parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|e| {
+                    WeightError::Malformed(format!("invalid center {:?}: {}", center, e))
+                })?;
+            if v.len() != 2 {
+                return Err(WeightError::Malformed(format!(
+                    "invalid center {:?}",
+                    center
+                )));
+            }
+            return Ok(WeightMap::off_center(
+                width,
+                height,
+                Pos { x: v[0], y: v[1] },
+            ));
+        }
+        if let Some(path) = spec.strip_prefix("file:") {
+            let s = fs::read_to_string(path)
+                .map_err(|e| WeightError::Malformed(format!("{}: {}", path, e)))?;
+            return WeightMap::parse_matrix(width, height, &s).map_err(|e| match e {
+                WeightError::Malformed(message) => {
+                    WeightError::Malformed(format!("{}: {}", path, message))
+                }
+                e => e,
+            });
+        }
+        Err(WeightError::Malformed(format!(
+            "unknown weight spec {:?}",
+            spec
+        )))
+    }
+
+    #[inline]
+    pub fn get(&self, pos: &Pos) -> i32 {
+        self.weights[pos.y as usize][pos.x as usize]
+    }
+
+    // 全マスの重みの和
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+}
+
+#[test]
+fn test_center_distance() {
+    let weight_map = WeightMap::center_distance(5, 5);
+    assert_eq!(weight_map.get(&Pos { x: 2, y: 2 }), 1);
+    assert_eq!(weight_map.get(&Pos { x: 0, y: 4 }), 9);
+    assert_eq!(weight_map.get(&Pos { x: 3, y: 0 }), 6);
+    assert_eq!(weight_map.total(), 125);
+
+    assert_eq!(
+        WeightMap::from_spec(5, 5, "center:2,2").unwrap(),
+        WeightMap::center_distance(5, 5)
+    );
+
+    let weight_map = WeightMap::center_distance(4, 3);
+    assert_eq!(weight_map.get(&Pos { x: 1, y: 1 }), 1);
+    assert_eq!(weight_map.get(&Pos { x: 3, y: 0 }), 6);
+}
+
+#[test]
+fn test_parse_matrix() {
+    let weight_map = WeightMap::parse_matrix(3, 2, "1 2 3\n4 5 6\n").unwrap();
+    assert_eq!(weight_map.get(&Pos { x: 1, y: 0 }), 2);
+    assert_eq!(weight_map.get(&Pos { x: 0, y: 1 }), 4);
+    assert_eq!(weight_map.total(), 21);
+
+    assert!(WeightMap::parse_matrix(2, 2, "1 2\n3\n").is_err());
+    assert!(WeightMap::parse_matrix(2, 2, "1 2\n").is_err());
+    assert!(WeightMap::from_spec(2, 2, "unknown").is_err());
+    assert_eq!(
+        WeightMap::parse_matrix(2, 2, "0 0\n0 0\n"),
+        Err(WeightError::NonPositiveTotal(0))
+    );
+    assert_eq!(
+        WeightMap::parse_matrix(2, 2, "1 2\n-5 1\n"),
+        Err(WeightError::NonPositiveTotal(-1))
+    );
+}
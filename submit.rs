@@ -1,1257 +1,3227 @@
-const TIME_LIMIT: f32 = 4.97;
-const LOOP_INTERVAL: usize = 100;
-const WRITE_SCORE_LOG: bool = false;
+// 提出用の入口。標準入力の問題を既定の設定で解いて、標準出力に書く
+// bundleはこのファイルから辿れるモジュールだけをsubmit.rsにまとめる
+//
+// usage: cargo run --release --bin solve < in.txt
+use crate::ahc014::config::SolverConfig;
+use crate::ahc014::Input;
+use std::io::Read;
 
-const MULTIPLE_ADD_RECURSION_LIMIT: usize = 20;
-const DELETION_RECURSION_LIMIT: usize = 10;
-
-pub mod def {
-    use proconio::derive_readable;
-    use std::ops;
-
-    pub const DIR_MAX: usize = 8;
-
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    pub struct Score {
-        pub base: i32,
-    }
-
-    impl Score {
-        pub fn new() -> Score {
-            Score { base: 0 }
-        }
-    }
-
-    impl ops::AddAssign<&Score> for Score {
-        fn add_assign(&mut self, rhs: &Score) {
-            self.base += rhs.base;
-        }
-    }
-
-    impl ops::SubAssign<&Score> for Score {
-        fn sub_assign(&mut self, rhs: &Score) {
-            self.base -= rhs.base;
-        }
-    }
-
-    #[derive(Clone, Debug, PartialEq, Eq, Copy)]
-    pub struct Square {
-        pub id: i32,
-        pub new_pos: Pos,
-        pub diagonal: Pos,
-        pub connect: [Pos; 2],
-    }
+fn main() {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).unwrap();
+    let input = Input::parse(&text).unwrap_or_else(|e| panic!("input: {}", e));
+    print!("{}", crate::ahc014::solve(&input, SolverConfig::new()));
+}
 
-    impl Square {
-        pub fn new(new_pos: Pos, diagonal: Pos, connect: [Pos; 2]) -> Square {
-            static mut SQUARE_COUNTER: i32 = 0;
-            unsafe {
-                SQUARE_COUNTER += 1;
-            }
-            Square {
-                id: unsafe { SQUARE_COUNTER },
-                new_pos,
-                diagonal,
-                connect: [
-                    std::cmp::min(connect[0], connect[1]),
-                    std::cmp::max(connect[0], connect[1]),
-                ],
+#[allow(dead_code, unused_imports)]
+mod ahc014 {
+    // クレートの外からは、解く入口とCLI、ベンチマーク、設定、採点と検証だけを使う
+    pub mod config {
+        use crate::ahc014::neighborhood::Neighborhood;
+        use std::fmt::Write;
+
+        pub const TIME_LIMIT: f32 = 4.97;
+        pub const LOOP_INTERVAL: usize = 100;
+
+        pub const MULTIPLE_ADD_RECURSION_LIMIT: usize = 20;
+        pub const DELETION_RECURSION_LIMIT: usize = 10;
+
+        // OutwardGrowthで起点の候補にする外周の点の数と、続けて外側に足す回数
+        pub const OUTWARD_GROWTH_SAMPLES: usize = 8;
+        pub const OUTWARD_GROWTH_CHAIN_LIMIT: usize = 3;
+
+        // N = 30のときの温度。Nに応じて(N/30)^2倍する
+        pub const START_TEMP: f32 = 500.;
+        pub const END_TEMP: f32 = 25.;
+
+        // Neighborhood::all()の順の、近傍を選ぶ確率
+        pub const NEIGHBORHOOD_PROBS: [f32; 6] = [0.75, 0.05, 0.1, 0.1, 0., 0.];
+
+        pub const TRAJECTORY_INTERVAL: usize = 10000;
+
+        // 解いた結果の書き出し先。指定がなければ書き出さない
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct TelemetryConfig {
+            // 指定しなければ標準エラー出力
+            pub report: Option<String>,
+            pub svg: Option<String>,
+            pub score_log: Option<String>,
+            pub chart: Option<String>,
+            pub trajectory: Option<String>,
+            pub trajectory_interval: usize,
+        }
+
+        // 点と四角を選ぶときの重み付け。係数が正なら値の大きいものを、負なら小さいものを選びやすくする
+        // すべて0なら一様に選ぶ
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct SamplerConfig {
+            // 中心からの距離の係数。進み具合に応じてstartからendへ線形に変える
+            pub center_distance_start: f32,
+            pub center_distance_end: f32,
+            // 作るのに必要な四角の段数
+            pub depth: f32,
+            // 辺が出ていない方向の数
+            pub free_degree: f32,
+        }
+
+        // 焼きなましのパラメータ
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct SolverConfig {
+            pub time_limit: f32,
+            // 指定すると、時間ではなくループ回数で進み具合を決める。同じ入力なら同じ結果になる
+            pub iteration_limit: Option<usize>,
+            // 時間と温度を更新する間隔
+            pub loop_interval: usize,
+            pub multiple_add_recursion_limit: usize,
+            pub deletion_recursion_limit: usize,
+            pub outward_growth_samples: usize,
+            pub outward_growth_chain_limit: usize,
+            pub start_temp: f32,
+            pub end_temp: f32,
+            pub neighborhood_probs: [f32; 6],
+            pub sampler: SamplerConfig,
+            pub telemetry: TelemetryConfig,
+        }
+
+        // "ChangeSquare" -> "change_square"
+        fn snake_case(name: &str) -> String {
+            let mut s = String::new();
+            for (i, c) in name.chars().enumerate() {
+                if c.is_uppercase() && i > 0 {
+                    s.push('_');
+                }
+                s.push(c.to_ascii_lowercase());
             }
+            s
         }
-    }
-
-    #[derive(Debug, Clone)]
-    pub enum Command {
-        Add { square: Square },
-        Delete { square: Square },
-    }
-
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub enum Dir {
-        Up = 0,
-        UpRight = 1,
-        Right = 2,
-        DownRight = 3,
-        Down = 4,
-        DownLeft = 5,
-        Left = 6,
-        UpLeft = 7,
-    }
 
-    impl Dir {
-        pub fn from_i32(v: i32) -> Dir {
-            match v {
-                0 => Dir::Up,
-                1 => Dir::UpRight,
-                2 => Dir::Right,
-                3 => Dir::DownRight,
-                4 => Dir::Down,
-                5 => Dir::DownLeft,
-                6 => Dir::Left,
-                7 => Dir::UpLeft,
-                _ => panic!("Dir value {} is invalid.", v),
-            }
+        fn neighborhood_key(neighborhood: &Neighborhood) -> String {
+            snake_case(&format!("{:?}", neighborhood))
         }
 
-        pub fn rev(self) -> Dir {
-            let a = (self as i32 + 4) % 8;
-            Dir::from_i32(a)
+        // '#'以降をコメントとして取り除く。ただし文字列の中の'#'は残す
+        fn strip_comment(line: &str) -> &str {
+            let mut in_string = false;
+            for (i, c) in line.char_indices() {
+                match c {
+                    '"' => in_string = !in_string,
+                    '#' if !in_string => return &line[..i],
+                    _ => {}
+                }
+            }
+            line
         }
 
-        pub fn val(self) -> i32 {
-            self as i32
+        fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse::<T>()
+                .map_err(|_| format!("invalid value {:?} for {}", value, key))
         }
 
-        pub fn to_pos(self) -> Pos {
-            match self {
-                Dir::Up => Pos { x: 0, y: 1 },
-                Dir::UpRight => Pos { x: 1, y: 1 },
-                Dir::Right => Pos { x: 1, y: 0 },
-                Dir::DownRight => Pos { x: 1, y: -1 },
-                Dir::Down => Pos { x: 0, y: -1 },
-                Dir::DownLeft => Pos { x: -1, y: -1 },
-                Dir::Left => Pos { x: -1, y: 0 },
-                Dir::UpLeft => Pos { x: -1, y: 1 },
+        fn parse_string(key: &str, value: &str) -> Result<String, String> {
+            match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(s) => Ok(s.to_string()),
+                None => Err(format!("{} must be a quoted string", key)),
             }
         }
 
-        pub fn is_diagonal(self) -> bool {
-            if self == Dir::Up || self == Dir::Right || self == Dir::Down || self == Dir::Left {
-                return false;
+        impl Default for SolverConfig {
+            fn default() -> SolverConfig {
+                SolverConfig::new()
             }
-            true
-        }
-
-        pub fn next(self) -> Dir {
-            Dir::from_i32((self.val() + 1) % DIR_MAX as i32)
-        }
-
-        pub fn prev(self) -> Dir {
-            Dir::from_i32((self.val() + (DIR_MAX - 1) as i32) % DIR_MAX as i32)
         }
-    }
 
-    #[derive_readable]
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-    pub struct Pos {
-        pub x: i32,
-        pub y: i32,
-    }
-
-    impl Pos {
-        pub fn is_aligned(a: &Pos, b: &Pos) -> bool {
-            if a == b {
-                return false;
+        impl SolverConfig {
+            pub fn new() -> SolverConfig {
+                SolverConfig {
+                    time_limit: TIME_LIMIT,
+                    iteration_limit: None,
+                    loop_interval: LOOP_INTERVAL,
+                    multiple_add_recursion_limit: MULTIPLE_ADD_RECURSION_LIMIT,
+                    deletion_recursion_limit: DELETION_RECURSION_LIMIT,
+                    outward_growth_samples: OUTWARD_GROWTH_SAMPLES,
+                    outward_growth_chain_limit: OUTWARD_GROWTH_CHAIN_LIMIT,
+                    start_temp: START_TEMP,
+                    end_temp: END_TEMP,
+                    neighborhood_probs: NEIGHBORHOOD_PROBS,
+                    sampler: SamplerConfig::default(),
+                    telemetry: TelemetryConfig {
+                        trajectory_interval: TRAJECTORY_INTERVAL,
+                        ..Default::default()
+                    },
+                }
             }
-            if a.y == b.y || a.x == b.x {
-                return true;
+
+            fn temp_scale(n: usize) -> f32 {
+                (n as f32 / 30.).powf(2.)
             }
-            if i32::abs(a.x - b.x) == i32::abs(a.y - b.y) {
-                return true;
+
+            #[allow(unused_variables)]
+            pub fn calc_start_temp(&self, n: usize, m: usize) -> f32 {
+                self.start_temp * SolverConfig::temp_scale(n)
             }
-            return false;
-        }
 
-        pub fn get_dir(from: &Pos, to: &Pos) -> Dir {
-            debug_assert!(Pos::is_aligned(from, to));
+            #[allow(unused_variables)]
+            pub fn calc_end_temp(&self, n: usize, m: usize) -> f32 {
+                self.end_temp * SolverConfig::temp_scale(n)
+            }
 
-            let delta = to - from;
-            if delta.y > 0 {
-                if delta.x > 0 {
-                    Dir::UpRight
-                } else if delta.x == 0 {
-                    Dir::Up
-                } else {
-                    Dir::UpLeft
+            // 設定ファイルの1項目を反映する。keyは"section.key"
+            pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+                let sampler = &mut self.sampler;
+                let telemetry = &mut self.telemetry;
+                match key {
+                    "time_limit" => self.time_limit = parse_value(key, value)?,
+                    "iteration_limit" => self.iteration_limit = Some(parse_value(key, value)?),
+                    "loop_interval" => self.loop_interval = usize::max(1, parse_value(key, value)?),
+                    "multiple_add_recursion_limit" => {
+                        self.multiple_add_recursion_limit = parse_value(key, value)?
+                    }
+                    "deletion_recursion_limit" => self.deletion_recursion_limit = parse_value(key, value)?,
+                    "outward_growth_samples" => {
+                        self.outward_growth_samples = usize::max(1, parse_value(key, value)?)
+                    }
+                    "outward_growth_chain_limit" => {
+                        self.outward_growth_chain_limit = parse_value(key, value)?
+                    }
+                    "start_temp" => self.start_temp = parse_value(key, value)?,
+                    "end_temp" => self.end_temp = parse_value(key, value)?,
+                    "sampler.center_distance_start" => {
+                        sampler.center_distance_start = parse_value(key, value)?
+                    }
+                    "sampler.center_distance_end" => sampler.center_distance_end = parse_value(key, value)?,
+                    "sampler.depth" => sampler.depth = parse_value(key, value)?,
+                    "sampler.free_degree" => sampler.free_degree = parse_value(key, value)?,
+                    "telemetry.report" => telemetry.report = Some(parse_string(key, value)?),
+                    "telemetry.svg" => telemetry.svg = Some(parse_string(key, value)?),
+                    "telemetry.score_log" => telemetry.score_log = Some(parse_string(key, value)?),
+                    "telemetry.chart" => telemetry.chart = Some(parse_string(key, value)?),
+                    "telemetry.trajectory" => telemetry.trajectory = Some(parse_string(key, value)?),
+                    "telemetry.trajectory_interval" => {
+                        telemetry.trajectory_interval = usize::max(1, parse_value(key, value)?)
+                    }
+                    _ => {
+                        let neighborhood = key.strip_prefix("neighborhood.").and_then(|name| {
+                            Neighborhood::all()
+                                .into_iter()
+                                .find(|neighborhood| neighborhood_key(neighborhood) == name)
+                        });
+                        match neighborhood {
+                            Some(neighborhood) => {
+                                let prob: f32 = parse_value(key, value)?;
+                                if prob < 0. || prob.is_nan() {
+                                    return Err(format!("{} must not be negative", key));
+                                }
+                                self.neighborhood_probs[neighborhood as usize] = prob;
+                            }
+                            None => return Err(format!("unknown key {:?}", key)),
+                        }
+                    }
                 }
-            } else if delta.y == 0 {
-                if delta.x > 0 {
-                    Dir::Right
-                } else {
-                    Dir::Left
+                Ok(())
+            }
+
+            // key = value の形式。[section]以下のkeyは"section.key"として扱う
+            pub fn parse_config_file(s: &str) -> Result<SolverConfig, String> {
+                let mut config = SolverConfig::new();
+                let mut section = String::new();
+                for (i, line) in s.lines().enumerate() {
+                    let line = strip_comment(line).trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let result =
+                        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                            section = name.trim().to_string();
+                            Ok(())
+                        } else if let Some((key, value)) = line.split_once('=') {
+                            let (key, value) = (key.trim(), value.trim());
+                            if section.is_empty() {
+                                config.set(key, value)
+                            } else {
+                                config.set(&format!("{}.{}", section, key), value)
+                            }
+                        } else {
+                            Err(format!("expected \"key = value\", found {:?}", line))
+                        };
+                    result.map_err(|e| format!("line {}: {}", i + 1, e))?;
                 }
-            } else {
-                if delta.x > 0 {
-                    Dir::DownRight
-                } else if delta.x == 0 {
-                    Dir::Down
-                } else {
-                    Dir::DownLeft
+                if config.neighborhood_probs.iter().sum::<f32>() <= 0. {
+                    return Err("neighborhood probabilities must not all be zero".to_string());
                 }
+                Ok(config)
             }
-        }
 
-        pub fn between(from: &Pos, to: &Pos) -> Vec<Pos> {
-            debug_assert!(Pos::is_aligned(from, to));
-
-            let mut cur = from.clone();
-            let mut ret: Vec<Pos> = vec![];
+            // parse_config_fileで読める形式で書き出す
+            pub fn to_config_file(&self) -> String {
+                let mut s = String::new();
+                writeln!(s, "time_limit = {}", self.time_limit).unwrap();
+                match self.iteration_limit {
+                    Some(limit) => writeln!(s, "iteration_limit = {}", limit).unwrap(),
+                    None => writeln!(s, "# iteration_limit = 100000    # overrides time_limit").unwrap(),
+                }
+                writeln!(s, "loop_interval = {}", self.loop_interval).unwrap();
+                writeln!(
+                    s,
+                    "multiple_add_recursion_limit = {}",
+                    self.multiple_add_recursion_limit
+                )
+                .unwrap();
+                writeln!(
+                    s,
+                    "deletion_recursion_limit = {}",
+                    self.deletion_recursion_limit
+                )
+                .unwrap();
+                writeln!(
+                    s,
+                    "outward_growth_samples = {}",
+                    self.outward_growth_samples
+                )
+                .unwrap();
+                writeln!(
+                    s,
+                    "outward_growth_chain_limit = {}",
+                    self.outward_growth_chain_limit
+                )
+                .unwrap();
+                writeln!(s, "# temperatures at N = 30, scaled by (N/30)^2").unwrap();
+                writeln!(s, "start_temp = {}", self.start_temp).unwrap();
+                writeln!(s, "end_temp = {}", self.end_temp).unwrap();
+
+                writeln!(s, "\n[neighborhood]").unwrap();
+                for neighborhood in Neighborhood::all() {
+                    writeln!(
+                        s,
+                        "{} = {}",
+                        neighborhood_key(&neighborhood),
+                        self.neighborhood_probs[neighborhood as usize]
+                    )
+                    .unwrap();
+                }
 
-            let dir = Pos::get_dir(from, to);
+                writeln!(s, "\n[sampler]").unwrap();
+                writeln!(
+                    s,
+                    "# > 0 prefers larger values, < 0 smaller ones, 0 samples uniformly"
+                )
+                .unwrap();
+                let sampler = &self.sampler;
+                for (key, coef) in [
+                    ("center_distance_start", sampler.center_distance_start),
+                    ("center_distance_end", sampler.center_distance_end),
+                    ("depth", sampler.depth),
+                    ("free_degree", sampler.free_degree),
+                ] {
+                    writeln!(s, "{} = {}", key, coef).unwrap();
+                }
 
-            // is_alignedなら必ず見つかる（はず）
-            loop {
-                cur += &dir.to_pos();
-                if &cur == to {
-                    break;
+                writeln!(s, "\n[telemetry]").unwrap();
+                let telemetry = &self.telemetry;
+                for (key, path) in [
+                    ("report", &telemetry.report),
+                    ("svg", &telemetry.svg),
+                    ("score_log", &telemetry.score_log),
+                    ("chart", &telemetry.chart),
+                    ("trajectory", &telemetry.trajectory),
+                ] {
+                    match path {
+                        Some(path) => writeln!(s, "{} = \"{}\"", key, path).unwrap(),
+                        None => writeln!(s, "# {} = \"\"", key).unwrap(),
+                    }
                 }
-                ret.push(cur.clone());
+                writeln!(s, "trajectory_interval = {}", telemetry.trajectory_interval).unwrap();
+                s
             }
-
-            return ret;
         }
 
-        pub fn dist(a: &Pos, b: &Pos) -> i32 {
-            i32::max(i32::abs(a.x - b.x), i32::abs(a.y - b.y))
-        }
-
-        pub fn weight(a: &Pos, b: &Pos) -> i32 {
-            (a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)
-        }
     }
+    mod def {
+        use std::ops;
+
+        pub const DIR_MAX: usize = 8;
 
-    impl ops::AddAssign<&Pos> for Pos {
-        fn add_assign(&mut self, rhs: &Pos) {
-            self.x += rhs.x;
-            self.y += rhs.y;
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct Score {
+            pub base: i32,
         }
-    }
 
-    impl ops::Add<&Pos> for &Pos {
-        type Output = Pos;
-        fn add(self, rhs: &Pos) -> Pos {
-            Pos {
-                x: self.x + rhs.x,
-                y: self.y + rhs.y,
+        impl Default for Score {
+            fn default() -> Score {
+                Score::new()
             }
         }
-    }
 
-    impl ops::Sub<&Pos> for &Pos {
-        type Output = Pos;
-        fn sub(self, rhs: &Pos) -> Pos {
-            Pos {
-                x: self.x - rhs.x,
-                y: self.y - rhs.y,
+        impl Score {
+            pub fn new() -> Score {
+                Score { base: 0 }
             }
         }
-    }
 
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    pub struct Point {
-        pub pos: Pos,
-        // 各方向にある最も近い点
-        pub nearest_points: [Option<Pos>; DIR_MAX],
-        // その点を使って作った点
-        pub created_points: Vec<Pos>,
-        // 各方向が長方形の辺に使われているか
-        pub used_dir: [bool; DIR_MAX],
-        // 追加されたときに使われた点の情報
-        pub added_info: Option<Square>,
-    }
+        impl ops::AddAssign<&Score> for Score {
+            fn add_assign(&mut self, rhs: &Score) {
+                self.base += rhs.base;
+            }
+        }
 
-    impl Point {
-        pub fn new(pos: &Pos) -> Point {
-            Point {
-                pos: pos.clone(),
-                nearest_points: [None; DIR_MAX],
-                created_points: vec![],
-                used_dir: [false; DIR_MAX],
-                added_info: None,
+        impl ops::SubAssign<&Score> for Score {
+            fn sub_assign(&mut self, rhs: &Score) {
+                self.base -= rhs.base;
             }
         }
-    }
-}
-pub mod framework {
-    use crate::def::Command;
-    use crate::neighborhood::Neighborhood;
-
-    pub trait IState {
-        fn get_score(&self, progress: f32) -> f32;
-        fn perform_command(&mut self, command: &Command) -> Vec<Command>;
-        fn reverse_command(&mut self, command: &Command);
-    }
 
-    pub trait INeighborhoodSelector {
-        fn select(&self) -> Neighborhood;
-        fn step(&mut self, neighborhood: &Neighborhood, adopted: bool);
-    }
+        #[derive(Clone, Debug, PartialEq, Eq, Copy)]
+        pub struct Square {
+            pub id: i32,
+            pub new_pos: Pos,
+            pub diagonal: Pos,
+            pub connect: [Pos; 2],
+        }
 
-    pub trait IOptimizer {
-        fn update_temp(&mut self, progress: f32);
-        fn should_adopt_new_state(&self, score_diff: f32) -> bool;
-    }
+        impl Square {
+            pub fn new(new_pos: Pos, diagonal: Pos, connect: [Pos; 2]) -> Square {
+                thread_local! {
+                    static SQUARE_COUNTER: std::cell::Cell<i32> = const { std::cell::Cell::new(0) };
+                }
+                let id = SQUARE_COUNTER.with(|counter| {
+                    counter.set(counter.get() + 1);
+                    counter.get()
+                });
+                Square {
+                    id,
+                    new_pos,
+                    diagonal,
+                    connect: [
+                        std::cmp::min(connect[0], connect[1]),
+                        std::cmp::max(connect[0], connect[1]),
+                    ],
+                }
+            }
 
-    pub trait ISolver {
-        fn solve(&mut self, time_limit: f32);
-    }
-}
-pub mod grid {
-    use crate::def::*;
-
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    pub struct Grid {
-        pub size: usize,
-        pub points: Vec<Vec<Option<Point>>>,
-        pub edges: Vec<Vec<Vec<bool>>>,
-    }
+            #[allow(dead_code)]
+            pub fn size(&self) -> i32 {
+                Pos::dist(&self.new_pos, &self.connect[0]) + Pos::dist(&self.new_pos, &self.connect[1])
+            }
 
-    impl Grid {
-        pub fn new(n: usize) -> Grid {
-            Grid {
-                size: n,
-                points: vec![vec![None; n]; n],
-                edges: vec![vec![vec![false; DIR_MAX]; n]; n],
+            #[allow(dead_code)]
+            pub fn all_pos(&self) -> [&Pos; 4] {
+                [
+                    &self.new_pos,
+                    &self.connect[0],
+                    &self.diagonal,
+                    &self.connect[1],
+                ]
             }
-        }
 
-        pub fn point(&mut self, pos: &Pos) -> &mut Option<Point> {
-            &mut self.points[pos.y as usize][pos.x as usize]
+            #[allow(dead_code)]
+            pub fn get_corners(&self) -> (i32, i32, i32, i32) {
+                let mut min_x = 100;
+                let mut max_x = -1;
+                let mut min_y = 100;
+                let mut max_y = -1;
+                for pos in &self.all_pos() {
+                    min_x = i32::min(min_x, pos.x);
+                    max_x = i32::max(max_x, pos.x);
+                    min_y = i32::min(min_y, pos.y);
+                    max_y = i32::max(max_y, pos.y);
+                }
+                (min_x, max_x, min_y, max_y)
+            }
         }
 
-        pub fn can_connect(&self, a: &Pos, b: &Pos) -> bool {
-            debug_assert!(Pos::is_aligned(a, b));
-            let dir = Pos::get_dir(a, b);
-            if self.has_edge(a, &dir) {
-                return false;
+        #[derive(Debug, Clone)]
+        pub enum Command {
+            Add { square: Square },
+            Delete { square: Square },
+        }
+
+        #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+        pub enum Dir {
+            Up = 0,
+            UpRight = 1,
+            Right = 2,
+            DownRight = 3,
+            Down = 4,
+            DownLeft = 5,
+            Left = 6,
+            UpLeft = 7,
+        }
+
+        impl Dir {
+            pub fn from_i32(v: i32) -> Dir {
+                match v {
+                    0 => Dir::Up,
+                    1 => Dir::UpRight,
+                    2 => Dir::Right,
+                    3 => Dir::DownRight,
+                    4 => Dir::Down,
+                    5 => Dir::DownLeft,
+                    6 => Dir::Left,
+                    7 => Dir::UpLeft,
+                    _ => panic!("Dir value {} is invalid.", v),
+                }
             }
-            for p in Pos::between(a, b) {
-                if self.has_point(&p) {
-                    return false;
+
+            pub fn rev(self) -> Dir {
+                let a = (self as i32 + 4) % 8;
+                Dir::from_i32(a)
+            }
+
+            pub fn val(self) -> i32 {
+                self as i32
+            }
+
+            pub fn to_pos(self) -> Pos {
+                match self {
+                    Dir::Up => Pos { x: 0, y: 1 },
+                    Dir::UpRight => Pos { x: 1, y: 1 },
+                    Dir::Right => Pos { x: 1, y: 0 },
+                    Dir::DownRight => Pos { x: 1, y: -1 },
+                    Dir::Down => Pos { x: 0, y: -1 },
+                    Dir::DownLeft => Pos { x: -1, y: -1 },
+                    Dir::Left => Pos { x: -1, y: 0 },
+                    Dir::UpLeft => Pos { x: -1, y: 1 },
                 }
-                if self.has_edge(&p, &dir) || self.has_edge(&p, &dir.rev()) {
+            }
+
+            pub fn is_diagonal(self) -> bool {
+                if self == Dir::Up || self == Dir::Right || self == Dir::Down || self == Dir::Left {
                     return false;
                 }
+                true
             }
-            if self.has_edge(b, &dir.rev()) {
-                return false;
+
+            pub fn next(self) -> Dir {
+                Dir::from_i32((self.val() + 1) % DIR_MAX as i32)
             }
-            return true;
-        }
 
-        fn connect(&mut self, a: &Pos, b: &Pos, is_reverse: bool) {
-            let dir = Pos::get_dir(a, b);
-            self.add_edge(a, &dir);
-            for p in Pos::between(a, b) {
-                debug_assert!(!self.has_edge(&p, &dir));
-                if !is_reverse {
-                    debug_assert!(!self.has_point(&p));
-                }
-                self.add_edge(&p, &dir);
-                self.add_edge(&p, &dir.rev());
+            pub fn prev(self) -> Dir {
+                Dir::from_i32((self.val() + (DIR_MAX - 1) as i32) % DIR_MAX as i32)
             }
-            self.add_edge(b, &dir.rev());
         }
 
-        fn disconnect(&mut self, a: &Pos, b: &Pos) {
-            let dir = Pos::get_dir(a, b);
-            self.remove_edge(a, &dir);
-            for p in Pos::between(a, b) {
-                debug_assert!(self.has_edge(&p, &dir));
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct Pos {
+            pub x: i32,
+            pub y: i32,
+        }
 
-                self.remove_edge(&p, &dir);
-                self.remove_edge(&p, &dir.rev());
+        impl Pos {
+            pub fn is_aligned(a: &Pos, b: &Pos) -> bool {
+                if a == b {
+                    return false;
+                }
+                if a.y == b.y || a.x == b.x {
+                    return true;
+                }
+                if i32::abs(a.x - b.x) == i32::abs(a.y - b.y) {
+                    return true;
+                }
+                false
             }
-            self.remove_edge(b, &dir.rev());
-        }
 
-        pub fn create_square(&mut self, square: &Square, is_reverse: bool) {
-            // 点を追加する
-            self.add_point(
-                &square.new_pos,
-                Point::new(&square.new_pos),
-                Some(square.clone()),
-            );
+            pub fn get_dir(from: &Pos, to: &Pos) -> Dir {
+                debug_assert!(Pos::is_aligned(from, to));
 
-            // 辺を追加する
-            self.connect(&square.connect[0], &square.new_pos, is_reverse);
-            self.connect(&square.connect[1], &square.new_pos, is_reverse);
-            self.connect(&square.connect[0], &square.diagonal, is_reverse);
-            self.connect(&square.connect[1], &square.diagonal, is_reverse);
-
-            // 使った点を登録する
-            self.register_created_points(&square.connect[0], &square.new_pos);
-            self.register_created_points(&square.connect[1], &square.new_pos);
-            self.register_created_points(&square.diagonal, &square.new_pos);
-        }
-
-        pub fn delete_square(&mut self, square: &Square) {
-            // 点を削除する
-            self.remove_point(&square.new_pos);
-
-            // 辺を削除する
-            self.disconnect(&square.connect[0], &square.new_pos);
-            self.disconnect(&square.connect[1], &square.new_pos);
-            self.disconnect(&square.connect[0], &square.diagonal);
-            self.disconnect(&square.connect[1], &square.diagonal);
-
-            // 使った点の登録を消す
-            self.unregister_created_points(&square.connect[0], &square.new_pos);
-            self.unregister_created_points(&square.connect[1], &square.new_pos);
-            self.unregister_created_points(&square.diagonal, &square.new_pos);
-        }
-
-        pub fn remove_point(&mut self, pos: &Pos) {
-            debug_assert!(self.has_point(&pos));
-            let nearest_points = self.point(&pos).as_ref().unwrap().nearest_points.clone();
-            for i in 0..DIR_MAX {
-                let dir = Dir::from_i32(i as i32);
-                if let Some(nearest_pos) = &nearest_points[dir.val() as usize] {
-                    debug_assert!(self.has_point(&nearest_pos));
-
-                    if let Some(opposite_nearest_pos) = &nearest_points[dir.rev().val() as usize] {
-                        self.point(nearest_pos).as_mut().unwrap().nearest_points
-                            [dir.rev().val() as usize] = Some(opposite_nearest_pos.clone());
+                let delta = to - from;
+                if delta.y > 0 {
+                    if delta.x > 0 {
+                        Dir::UpRight
+                    } else if delta.x == 0 {
+                        Dir::Up
+                    } else {
+                        Dir::UpLeft
+                    }
+                } else if delta.y == 0 {
+                    if delta.x > 0 {
+                        Dir::Right
+                    } else {
+                        Dir::Left
+                    }
+                } else {
+                    if delta.x > 0 {
+                        Dir::DownRight
+                    } else if delta.x == 0 {
+                        Dir::Down
                     } else {
-                        self.point(nearest_pos).as_mut().unwrap().nearest_points
-                            [dir.rev().val() as usize] = None;
+                        Dir::DownLeft
                     }
                 }
             }
-            self.points[pos.y as usize][pos.x as usize] = None;
-        }
 
-        pub fn add_point(&mut self, pos: &Pos, mut point: Point, square: Option<Square>) {
-            debug_assert!(!self.has_point(&pos));
+            pub fn between(from: &Pos, to: &Pos) -> Vec<Pos> {
+                debug_assert!(Pos::is_aligned(from, to));
+
+                let mut cur = *from;
+                let mut ret: Vec<Pos> = vec![];
 
-            for i in 0..DIR_MAX {
-                let dir = Dir::from_i32(i as i32);
-                if let Some(nearest_pos) = self.nearest_point_pos(&pos, &dir) {
-                    let nearest_point = self.point(&nearest_pos).as_mut().unwrap();
+                let dir = Pos::get_dir(from, to);
 
-                    nearest_point.nearest_points[dir.rev().val() as usize] = Some(pos.clone());
-                    point.nearest_points[dir.val() as usize] = Some(nearest_pos.clone());
+                // is_alignedなら必ず見つかる（はず）
+                loop {
+                    cur += &dir.to_pos();
+                    if &cur == to {
+                        break;
+                    }
+                    ret.push(cur);
                 }
+
+                ret
             }
-            point.added_info = square;
-            self.points[pos.y as usize][pos.x as usize] = Some(point);
-        }
 
-        fn add_edge(&mut self, pos: &Pos, dir: &Dir) {
-            self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = true;
-        }
+            pub fn dist(a: &Pos, b: &Pos) -> i32 {
+                i32::max(i32::abs(a.x - b.x), i32::abs(a.y - b.y))
+            }
 
-        fn remove_edge(&mut self, pos: &Pos, dir: &Dir) {
-            self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = false;
+            pub fn weight(a: &Pos, b: &Pos) -> i32 {
+                (a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)
+            }
         }
 
-        pub fn has_point(&self, pos: &Pos) -> bool {
-            self.points[pos.y as usize][pos.x as usize].is_some()
-        }
 
-        pub fn has_edge(&self, pos: &Pos, dir: &Dir) -> bool {
-            self.edges[pos.y as usize][pos.x as usize][dir.val() as usize].clone()
-        }
 
-        pub fn nearest_point_pos(&self, from: &Pos, dir: &Dir) -> Option<Pos> {
-            let mut cur = from.clone();
+        impl ops::AddAssign<&Pos> for Pos {
+            fn add_assign(&mut self, rhs: &Pos) {
+                self.x += rhs.x;
+                self.y += rhs.y;
+            }
+        }
 
-            // 最大でもself.size回loopを回せばいい
-            for _ in 0..self.size {
-                cur += &dir.to_pos();
-                if !self.is_valid(&cur) {
-                    break;
-                }
-                if self.has_point(&cur) {
-                    return Some(cur);
+        impl ops::Add<&Pos> for &Pos {
+            type Output = Pos;
+            fn add(self, rhs: &Pos) -> Pos {
+                Pos {
+                    x: self.x + rhs.x,
+                    y: self.y + rhs.y,
                 }
             }
-            return None;
         }
 
-        fn unregister_created_points(&mut self, a: &Pos, target: &Pos) {
-            let created_points = &self.point(&a).as_mut().unwrap().created_points;
-            let target_index = created_points.iter().position(|x| *x == *target).unwrap();
-            self.point(&a)
-                .as_mut()
-                .unwrap()
-                .created_points
-                .remove(target_index);
+        impl ops::Sub<&Pos> for &Pos {
+            type Output = Pos;
+            fn sub(self, rhs: &Pos) -> Pos {
+                Pos {
+                    x: self.x - rhs.x,
+                    y: self.y - rhs.y,
+                }
+            }
         }
 
-        fn register_created_points(&mut self, a: &Pos, target: &Pos) {
-            self.point(&a)
-                .as_mut()
-                .unwrap()
-                .created_points
-                .push(target.clone());
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct Point {
+            pub pos: Pos,
+            // 各方向にある最も近い点
+            pub nearest_points: [Option<Pos>; DIR_MAX],
+            // その点を使って作った点
+            pub created_points: Vec<Pos>,
+            // 各方向が長方形の辺に使われているか
+            pub used_dir: [bool; DIR_MAX],
+            // 追加されたときに使われた点の情報
+            pub added_info: Option<Square>,
+            // 初期点を0として、四角の頂点の深さの最大+1
+            pub depth: usize,
+        }
+
+        impl Point {
+            pub fn new(pos: &Pos) -> Point {
+                Point {
+                    pos: *pos,
+                    nearest_points: [None; DIR_MAX],
+                    created_points: vec![],
+                    used_dir: [false; DIR_MAX],
+                    added_info: None,
+                    depth: 0,
+                }
+            }
         }
+    }
+    mod framework {
+        use crate::ahc014::def::Command;
+        use crate::ahc014::neighborhood::Neighborhood;
 
-        pub fn is_valid(&self, pos: &Pos) -> bool {
-            pos.x >= 0 && pos.y >= 0 && pos.x < self.size as i32 && pos.y < self.size as i32
+        pub trait IState {
+            fn get_score(&self, progress: f32) -> f32;
+            fn perform_command(&mut self, command: &Command) -> Vec<Command>;
+            #[allow(dead_code)]
+            fn reverse_command(&mut self, command: &Command);
         }
-    }
-}
-pub mod neighborhood {
-    use crate::*;
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum Neighborhood {
-        Add = 0,
-        Delete = 1,
-        ChangeSquare = 2,
-        SplitSquare = 3,
-        MultipleAdd = 4,
-    }
 
-    impl Neighborhood {
-        pub fn perform(&self, state: &mut State) -> Vec<Command> {
-            match self {
-                Neighborhood::Add => Neighborhood::perform_add(state),
-                Neighborhood::Delete => Neighborhood::perform_delete(state),
-                Neighborhood::ChangeSquare => Neighborhood::perform_change_square(state),
-                Neighborhood::SplitSquare => Neighborhood::perform_split_square(state),
-                Neighborhood::MultipleAdd => Neighborhood::perform_multiple_add(state),
-            }
-        }
-
-        fn perform_multiple_add(state: &mut State) -> Vec<Command> {
-            let selected_p = state.sample_point_pos();
-            let mut performed_commands = vec![];
-            let mut recursion_count = 0;
-            Neighborhood::attempt_multiple_add(
-                state,
-                &selected_p,
-                &mut recursion_count,
-                &MULTIPLE_ADD_RECURSION_LIMIT,
-                &mut performed_commands,
-                state.get_score(1.),
-            );
-            performed_commands
-        }
-
-        fn attempt_multiple_add(
-            state: &mut State,
-            pos: &Pos,
-            recursion_count: &mut usize,
-            recursion_limit: &usize,
-            performed_commands: &mut Vec<Command>,
-            start_score: f32,
-        ) {
-            if state.get_score(1.) > start_score {
-                return;
-            }
-            if *recursion_count >= *recursion_limit {
-                return;
-            }
-            *recursion_count += 1;
-            let mut used_bits: usize = 0;
-            for _ in 0..DIR_MAX {
-                let i = rnd::gen_range(0, DIR_MAX);
-                if used_bits & (1 << i) > 0 {
-                    continue;
-                }
-                used_bits |= 1 << i;
-                let dir = Dir::from_i32(i as i32);
-                if let Some(nearest_pos) =
-                    state.grid.point(&pos).as_ref().unwrap().nearest_points[dir.val() as usize]
-                {
-                    let mut add = Neighborhood::attempt_add(state, &nearest_pos, None);
-                    performed_commands.append(&mut add);
-                    Neighborhood::attempt_multiple_add(
-                        state,
-                        &nearest_pos,
-                        recursion_count,
-                        recursion_limit,
-                        performed_commands,
-                        start_score,
-                    );
-                }
-            }
+        pub trait INeighborhoodSelector {
+            fn select(&self) -> Neighborhood;
+            fn step(&mut self, neighborhood: &Neighborhood, adopted: bool);
         }
 
-        fn perform_add(state: &mut State) -> Vec<Command> {
-            let selected_p = state.sample_point_pos();
-            Neighborhood::attempt_add(state, &selected_p, None)
+        pub trait IOptimizer {
+            fn update_temp(&mut self, progress: f32);
+            fn should_adopt_new_state(&self, score_diff: f32) -> bool;
         }
 
-        fn attempt_add(state: &mut State, pos: &Pos, ignore_dir: Option<&Dir>) -> Vec<Command> {
-            debug_assert!(state.grid.has_point(&pos));
-            let nearest_points = state
-                .grid
-                .point(&pos)
-                .as_ref()
-                .unwrap()
-                .nearest_points
-                .clone();
-            let mut used_bits: usize = 0;
-            for _ in 0..DIR_MAX {
-                let i = rnd::gen_range(0, DIR_MAX);
-                if used_bits & (1 << i) > 0 {
-                    continue;
-                }
-                used_bits |= 1 << i;
-                let diagonal_dir = Dir::from_i32(i as i32);
-                if let Some(ignore_dir) = ignore_dir {
-                    if ignore_dir == &diagonal_dir {
-                        continue;
+        pub trait ISolver {
+            fn solve(&mut self, time_limit: f32);
+        }
+    }
+    mod grid {
+        use crate::ahc014::def::*;
+        use crate::ahc014::journal::*;
+        use crate::ahc014::point_set::*;
+        use std::fmt;
+
+        // 二点を繋げない理由
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ConnectError {
+            Blocked(Pos),
+            PointOnSide(Pos),
+            EdgeOverlap(Pos),
+        }
+
+        impl fmt::Display for ConnectError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    ConnectError::Blocked(pos) => {
+                        write!(f, "side passes blocked cell ({}, {})", pos.x, pos.y)
+                    }
+                    ConnectError::PointOnSide(pos) => {
+                        write!(f, "point ({}, {}) lies on a side", pos.x, pos.y)
+                    }
+                    ConnectError::EdgeOverlap(pos) => {
+                        write!(
+                            f,
+                            "side overlaps an existing edge at ({}, {})",
+                            pos.x, pos.y
+                        )
                     }
-                }
-                let performed_commands =
-                    Neighborhood::attempt_add_dir(state, &pos, &nearest_points, &diagonal_dir);
-                if performed_commands.len() > 0 {
-                    return performed_commands;
                 }
             }
-            return vec![];
         }
 
-        fn attempt_add_dir(
-            state: &mut State,
-            pos: &Pos,
-            nearest_points: &[Option<Pos>; DIR_MAX],
-            dir: &Dir,
-        ) -> Vec<Command> {
-            let dir_next = dir.next();
-            let dir_prev = dir.prev();
-
-            if let (Some(pos_prev), Some(pos_next)) = (
-                &nearest_points[dir_prev.val() as usize],
-                &nearest_points[dir_next.val() as usize],
-            ) {
-                let new_pos = pos_next + &(pos_prev - &pos);
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct Grid {
+            pub width: usize,
+            pub height: usize,
+            pub points: Vec<Vec<Option<Point>>>,
+            pub edges: Vec<Vec<Vec<bool>>>,
+            // pointsで点があるマスの一覧
+            pub point_set: PointSet,
+            // 各行、各列の両端にある点の座標。点がなければNone
+            pub row_ends: Vec<Option<(i32, i32)>>,
+            pub column_ends: Vec<Option<(i32, i32)>>,
+            // 点も辺も置けないマス
+            pub blocked: Vec<Vec<bool>>,
+            pub journal: Journal,
+        }
+
+        impl Grid {
+            pub fn new(n: usize) -> Grid {
+                Grid::with_board(n, n, &[])
+            }
 
-                if !state.grid.is_valid(&new_pos) {
-                    return vec![];
+            pub fn with_board(width: usize, height: usize, blocked_cells: &[Pos]) -> Grid {
+                let mut blocked = vec![vec![false; width]; height];
+                for pos in blocked_cells {
+                    blocked[pos.y as usize][pos.x as usize] = true;
                 }
-                if !state.grid.has_point(&pos)
-                    || !state.grid.has_point(&pos_prev)
-                    || !state.grid.has_point(&pos_next)
-                    || state.grid.has_point(&new_pos)
-                {
-                    return vec![];
+                Grid {
+                    width,
+                    height,
+                    points: vec![vec![None; width]; height],
+                    edges: vec![vec![vec![false; DIR_MAX]; width]; height],
+                    point_set: PointSet::new(width, height),
+                    row_ends: vec![None; height],
+                    column_ends: vec![None; width],
+                    blocked,
+                    journal: Journal::new(),
                 }
+            }
 
-                let connect: [Pos; 2] = [pos_prev.clone(), pos_next.clone()];
-                let square = Square::new(new_pos, pos.clone(), connect);
-
-                let performed_commands = state.perform_command(&Command::Add { square });
-                if performed_commands.len() > 0 {
-                    return performed_commands;
+            // 1行目に"W H"、続くH行にW文字の'.'(置ける)か'#'(置けない)で、i行目がy=iを表す
+            pub fn parse_board(s: &str) -> Result<Grid, String> {
+                let mut lines = s.lines().filter(|line| !line.trim().is_empty());
+                let header = lines
+                    .next()
+                    .ok_or_else(|| "missing board header".to_string())?;
+                let v = header
+                    .split_whitespace()
+                    .map(|v| v.parse::<usize>())
+                    .collect::<Result<Vec<usize>, _>>()
+                    .map_err(|e| format!("line 1: {}", e))?;
+                if v.len() != 2 || v[0] == 0 || v[1] == 0 {
+                    return Err(format!("line 1: invalid board size {:?}", header));
+                }
+                let (width, height) = (v[0], v[1]);
+
+                let mut blocked_cells = vec![];
+                for y in 0..height {
+                    let line = lines
+                        .next()
+                        .ok_or_else(|| format!("expected {} rows, found {}", height, y))?
+                        .trim();
+                    if line.chars().count() != width {
+                        return Err(format!("line {}: expected {} cells", y + 2, width));
+                    }
+                    for (x, c) in line.chars().enumerate() {
+                        match c {
+                            '.' => {}
+                            '#' => blocked_cells.push(Pos {
+                                x: x as i32,
+                                y: y as i32,
+                            }),
+                            _ => return Err(format!("line {}: invalid cell {:?}", y + 2, c)),
+                        }
+                    }
                 }
+                Ok(Grid::with_board(width, height, &blocked_cells))
             }
 
-            vec![]
-        }
-
-        fn perform_delete(state: &mut State) -> Vec<Command> {
-            if state.squares.len() == 0 {
-                return vec![];
+            // 同じ盤面で、点と辺がない状態
+            pub fn cleared(&self) -> Grid {
+                Grid::with_board(self.width, self.height, &self.blocked_cells())
             }
-            let square = state.sample_square();
-            Neighborhood::attempt_delete(state, &square)
-        }
 
-        fn attempt_delete(state: &mut State, square: &Square) -> Vec<Command> {
-            state.perform_command(&Command::Delete { square: *square })
-        }
+            pub fn blocked_cells(&self) -> Vec<Pos> {
+                let mut blocked_cells = vec![];
+                for (y, row) in self.blocked.iter().enumerate() {
+                    for (x, &is_blocked) in row.iter().enumerate() {
+                        if is_blocked {
+                            blocked_cells.push(Pos {
+                                x: x as i32,
+                                y: y as i32,
+                            });
+                        }
+                    }
+                }
+                blocked_cells
+            }
 
-        fn perform_change_square(state: &mut State) -> Vec<Command> {
-            // 四角を作っている点を探す
-            if state.squares.len() == 0 {
-                return vec![];
+            pub fn point(&mut self, pos: &Pos) -> &mut Option<Point> {
+                &mut self.points[pos.y as usize][pos.x as usize]
             }
-            let square = state.sample_square();
-            Neighborhood::attempt_change_square(state, &square)
-        }
 
-        fn attempt_change_square(state: &mut State, square: &Square) -> Vec<Command> {
-            let start_score = state.get_score(1.);
-            let mut performed_commands =
-                state.perform_command(&Command::Delete { square: *square });
+            pub fn can_connect(&self, a: &Pos, b: &Pos) -> bool {
+                self.check_connect(a, b).is_ok()
+            }
 
-            // 四角を消せなかったら中止
-            if performed_commands.len() == 0 {
-                return performed_commands;
+            pub fn check_connect(&self, a: &Pos, b: &Pos) -> Result<(), ConnectError> {
+                debug_assert!(Pos::is_aligned(a, b));
+                let dir = Pos::get_dir(a, b);
+                if self.has_edge(a, &dir) {
+                    return Err(ConnectError::EdgeOverlap(*a));
+                }
+                for p in Pos::between(a, b) {
+                    if self.is_blocked(&p) {
+                        return Err(ConnectError::Blocked(p));
+                    }
+                    if self.has_point(&p) {
+                        return Err(ConnectError::PointOnSide(p));
+                    }
+                    if self.has_edge(&p, &dir) || self.has_edge(&p, &dir.rev()) {
+                        return Err(ConnectError::EdgeOverlap(p));
+                    }
+                }
+                if self.has_edge(b, &dir.rev()) {
+                    return Err(ConnectError::EdgeOverlap(*b));
+                }
+                Ok(())
             }
 
-            // 再帰的にposの点も消してしまった時は中止
-            if !state.grid.has_point(&square.diagonal) {
-                return performed_commands;
+            fn connect(&mut self, a: &Pos, b: &Pos, is_reverse: bool) {
+                let dir = Pos::get_dir(a, b);
+                self.add_edge(a, &dir);
+                for p in Pos::between(a, b) {
+                    debug_assert!(!self.has_edge(&p, &dir));
+                    if !is_reverse {
+                        debug_assert!(!self.has_point(&p));
+                    }
+                    self.add_edge(&p, &dir);
+                    self.add_edge(&p, &dir.rev());
+                }
+                self.add_edge(b, &dir.rev());
             }
 
-            let mut recursion_count: usize = 0;
-            Neighborhood::attempt_multiple_add(
-                state,
-                &square.diagonal,
-                &mut recursion_count,
-                &MULTIPLE_ADD_RECURSION_LIMIT,
-                &mut performed_commands,
-                start_score,
-            );
-            return performed_commands;
-        }
+            fn disconnect(&mut self, a: &Pos, b: &Pos) {
+                let dir = Pos::get_dir(a, b);
+                self.remove_edge(a, &dir);
+                for p in Pos::between(a, b) {
+                    debug_assert!(self.has_edge(&p, &dir));
 
-        fn perform_split_square(state: &mut State) -> Vec<Command> {
-            if state.squares.len() == 0 {
-                return vec![];
+                    self.remove_edge(&p, &dir);
+                    self.remove_edge(&p, &dir.rev());
+                }
+                self.remove_edge(b, &dir.rev());
             }
-            let selected_square = state.sample_square();
-            Neighborhood::attempt_split_square(state, &selected_square)
-        }
 
-        fn attempt_split_square(state: &mut State, square: &Square) -> Vec<Command> {
-            let nearest_points = state
-                .grid
-                .point(&square.diagonal)
-                .as_ref()
-                .unwrap()
-                .nearest_points
-                .clone();
-            let dir1 = Pos::get_dir(&square.diagonal, &square.connect[0]);
-            let dir2 = Pos::get_dir(&square.diagonal, &square.connect[1]);
-
-            if nearest_points[dir1.val() as usize] != Some(square.connect[0])
-                || nearest_points[dir2.val() as usize] != Some(square.connect[1])
-            {
-                let start_score = state.get_score(1.);
-                let mut performed_commands = state.perform_command(&Command::Delete {
-                    square: square.clone(),
-                });
+            pub fn create_square(&mut self, square: &Square, is_reverse: bool) {
+                // 点を追加する
+                self.add_point(&square.new_pos, Point::new(&square.new_pos), Some(*square));
 
-                if performed_commands.len() == 0 {
-                    return vec![];
-                }
+                // 辺を追加する
+                self.connect(&square.connect[0], &square.new_pos, is_reverse);
+                self.connect(&square.connect[1], &square.new_pos, is_reverse);
+                self.connect(&square.connect[0], &square.diagonal, is_reverse);
+                self.connect(&square.connect[1], &square.diagonal, is_reverse);
 
-                let mut recursion_count: usize = 0;
-                Neighborhood::attempt_multiple_add(
-                    state,
-                    &square.diagonal,
-                    &mut recursion_count,
-                    &MULTIPLE_ADD_RECURSION_LIMIT,
-                    &mut performed_commands,
-                    start_score,
-                );
-                return performed_commands;
+                // 使った点を登録する
+                self.register_created_points(&square.connect[0], &square.new_pos);
+                self.register_created_points(&square.connect[1], &square.new_pos);
+                self.register_created_points(&square.diagonal, &square.new_pos);
             }
 
-            vec![]
-        }
-    }
+            pub fn delete_square(&mut self, square: &Square) {
+                // 点を削除する
+                self.remove_point(&square.new_pos);
 
-    impl Neighborhood {
-        pub fn all() -> [Neighborhood; 5] {
-            [
-                Neighborhood::Add,
-                Neighborhood::Delete,
-                Neighborhood::ChangeSquare,
-                Neighborhood::SplitSquare,
-                Neighborhood::MultipleAdd,
-            ]
-        }
+                // 辺を削除する
+                self.disconnect(&square.connect[0], &square.new_pos);
+                self.disconnect(&square.connect[1], &square.new_pos);
+                self.disconnect(&square.connect[0], &square.diagonal);
+                self.disconnect(&square.connect[1], &square.diagonal);
 
-        pub fn from_i32(v: i32) -> Neighborhood {
-            match v {
-                0 => Neighborhood::Add,
-                1 => Neighborhood::Delete,
-                2 => Neighborhood::ChangeSquare,
-                3 => Neighborhood::SplitSquare,
-                4 => Neighborhood::MultipleAdd,
-                _ => panic!("Neighborhood value {} is invalid.", v),
+                // 使った点の登録を消す
+                self.unregister_created_points(&square.connect[0], &square.new_pos);
+                self.unregister_created_points(&square.connect[1], &square.new_pos);
+                self.unregister_created_points(&square.diagonal, &square.new_pos);
             }
-        }
-    }
-}
-pub mod state {
-    use crate::grid::*;
-    use crate::*;
-
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    pub struct State {
-        pub grid: Grid,
-        pub squares: Vec<Square>,
-        pub score: Score,
-    }
 
-    impl State {
-        pub fn new(n: usize, p: Vec<Pos>) -> State {
-            let mut state = State {
-                grid: Grid::new(n),
-                squares: vec![],
-                score: Score::new(),
-            };
-            for pos in p.iter() {
-                state.grid.add_point(pos, Point::new(&pos), None);
-                state.score.base += state.weight(&pos);
+            pub fn remove_point(&mut self, pos: &Pos) {
+                debug_assert!(self.has_point(pos));
+                let nearest_points = self.point(pos).as_ref().unwrap().nearest_points;
+                for i in 0..DIR_MAX {
+                    let dir = Dir::from_i32(i as i32);
+                    if let Some(nearest_pos) = &nearest_points[dir.val() as usize] {
+                        debug_assert!(self.has_point(nearest_pos));
+
+                        self.set_nearest_point(
+                            nearest_pos,
+                            &dir.rev(),
+                            nearest_points[dir.rev().val() as usize],
+                        );
+                    }
+                }
+                let old = self.points[pos.y as usize][pos.x as usize].take();
+                let index = self.point_set.remove(pos);
+                self.journal.record(Change::Point {
+                    pos: *pos,
+                    old,
+                    index,
+                });
+                self.update_ends_on_remove(pos);
             }
-            state
-        }
-    }
 
-    impl State {
-        pub fn can_perform_add(&mut self, square: &Square, is_reverse: bool) -> bool {
-            debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[0]));
-            debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[1]));
-            debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[0]));
-            debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[1]));
+            pub fn add_point(&mut self, pos: &Pos, mut point: Point, square: Option<Square>) {
+                debug_assert!(!self.has_point(pos));
 
-            // new_posに既に点がないか確認
-            if self.grid.has_point(&square.new_pos) {
-                return false;
+                for i in 0..DIR_MAX {
+                    let dir = Dir::from_i32(i as i32);
+                    if let Some(nearest_pos) = self.nearest_point_pos(pos, &dir) {
+                        self.set_nearest_point(&nearest_pos, &dir.rev(), Some(*pos));
+                        point.nearest_points[dir.val() as usize] = Some(nearest_pos);
+                    }
+                }
+                if let Some(square) = &square {
+                    // is_reverseのときは頂点があるか確かめていないので、ある点だけ見る
+                    point.depth = [square.connect[0], square.diagonal, square.connect[1]]
+                        .iter()
+                        .filter_map(|pos| self.points[pos.y as usize][pos.x as usize].as_ref())
+                        .map(|point| point.depth + 1)
+                        .max()
+                        .unwrap_or(1);
+                }
+                point.added_info = square;
+                self.points[pos.y as usize][pos.x as usize] = Some(point);
+                self.point_set.insert(pos);
+                self.journal.record(Change::Point {
+                    pos: *pos,
+                    old: None,
+                    index: self.point_set.len() - 1,
+                });
+                self.update_ends_on_add(pos);
             }
 
-            // 作ろうとしてる四角の辺に既に点、辺がないか確認する
-            if !is_reverse
-                && (!self.grid.can_connect(&square.connect[0], &square.new_pos)
-                    || !self.grid.can_connect(&square.connect[1], &square.new_pos)
-                    || !self.grid.can_connect(&square.connect[0], &square.diagonal)
-                    || !self.grid.can_connect(&square.connect[1], &square.diagonal))
-            {
-                return false;
+            fn update_ends_on_add(&mut self, pos: &Pos) {
+                let extend = |ends: Option<(i32, i32)>, v: i32| match ends {
+                    Some((low, high)) => Some((i32::min(low, v), i32::max(high, v))),
+                    None => Some((v, v)),
+                };
+                self.set_row_ends(pos.y as usize, extend(self.row_ends[pos.y as usize], pos.x));
+                self.set_column_ends(
+                    pos.x as usize,
+                    extend(self.column_ends[pos.x as usize], pos.y),
+                );
             }
 
-            true
-        }
-
-        pub fn perform_add(&mut self, square: &Square, is_reverse: bool) -> Vec<Command> {
-            if !self.can_perform_add(square, is_reverse) {
-                return vec![];
+            // 端の点が消えたときだけ、その行と列を見直す
+            fn update_ends_on_remove(&mut self, pos: &Pos) {
+                let (x, y) = (pos.x as usize, pos.y as usize);
+                if let Some((left, right)) = self.row_ends[y] {
+                    if pos.x == left || pos.x == right {
+                        let row = &self.points[y];
+                        let ends = row
+                            .iter()
+                            .position(Option::is_some)
+                            .zip(row.iter().rposition(Option::is_some))
+                            .map(|(left, right)| (left as i32, right as i32));
+                        self.set_row_ends(y, ends);
+                    }
+                }
+                if let Some((top, bottom)) = self.column_ends[x] {
+                    if pos.y == top || pos.y == bottom {
+                        let has_point = |y: &usize| self.points[*y][x].is_some();
+                        let ends = (0..self.height)
+                            .find(has_point)
+                            .zip((0..self.height).rev().find(has_point))
+                            .map(|(top, bottom)| (top as i32, bottom as i32));
+                        self.set_column_ends(x, ends);
+                    }
+                }
             }
 
-            self.grid.create_square(&square, is_reverse);
-
-            self.squares.push(square.clone());
+            fn set_row_ends(&mut self, y: usize, ends: Option<(i32, i32)>) {
+                let old = std::mem::replace(&mut self.row_ends[y], ends);
+                self.journal.record(Change::RowEnds { y, old });
+            }
 
-            // スコアの更新
-            self.score.base += self.weight(&square.new_pos);
+            fn set_column_ends(&mut self, x: usize, ends: Option<(i32, i32)>) {
+                let old = std::mem::replace(&mut self.column_ends[x], ends);
+                self.journal.record(Change::ColumnEnds { x, old });
+            }
 
-            vec![Command::Add { square: *square }]
-        }
+            fn set_nearest_point(&mut self, pos: &Pos, dir: &Dir, nearest_pos: Option<Pos>) {
+                let nearest_points = &mut self.point(pos).as_mut().unwrap().nearest_points;
+                let old = nearest_points[dir.val() as usize];
+                nearest_points[dir.val() as usize] = nearest_pos;
+                self.journal.record(Change::NearestPoint {
+                    pos: *pos,
+                    dir: *dir,
+                    old,
+                });
+            }
 
-        pub fn perform_delete(&mut self, square: &Square, performed_commands: &mut Vec<Command>) {
-            debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[0]));
-            debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[1]));
-            debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[0]));
-            debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[1]));
+            fn add_edge(&mut self, pos: &Pos, dir: &Dir) {
+                self.set_edge(pos, dir, true);
+            }
 
-            debug_assert!(self.grid.has_point(&square.new_pos));
+            fn remove_edge(&mut self, pos: &Pos, dir: &Dir) {
+                self.set_edge(pos, dir, false);
+            }
 
-            // new_posの点を使って作られた四角を再帰的に消す
-            let created_points = self
-                .grid
-                .point(&square.new_pos)
-                .as_ref()
-                .unwrap()
-                .created_points
-                .clone();
-            for created_point in &created_points {
-                // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
-                // TODO: 正当性の確認
-                if !self.grid.has_point(&created_point) {
-                    continue;
-                }
-                let created_square = self
-                    .grid
-                    .point(created_point)
-                    .as_ref()
-                    .unwrap()
-                    .added_info
-                    .as_ref()
-                    .unwrap()
-                    .clone();
-                self.perform_delete(&created_square, performed_commands);
+            fn set_edge(&mut self, pos: &Pos, dir: &Dir, value: bool) {
+                let edge = &mut self.edges[pos.y as usize][pos.x as usize][dir.val() as usize];
+                let old = *edge;
+                *edge = value;
+                self.journal.record(Change::Edge {
+                    pos: *pos,
+                    dir: *dir,
+                    old,
+                });
             }
 
-            self.grid.delete_square(&square);
+            pub fn has_point(&self, pos: &Pos) -> bool {
+                self.points[pos.y as usize][pos.x as usize].is_some()
+            }
 
-            // FIXME: O(n)
-            self.squares
-                .remove(self.squares.iter().position(|x| *x == *square).unwrap());
-            self.score.base -= self.weight(&square.new_pos);
-            performed_commands.push(Command::Delete { square: *square });
-        }
+            pub fn has_edge(&self, pos: &Pos, dir: &Dir) -> bool {
+                self.edges[pos.y as usize][pos.x as usize][dir.val() as usize]
+            }
 
-        pub fn sample_point_pos(&self) -> Pos {
-            loop {
-                let pos = Pos {
-                    x: rnd::gen_range(0, self.grid.size) as i32,
-                    y: rnd::gen_range(0, self.grid.size) as i32,
-                };
-                if self.grid.has_point(&pos) {
-                    return pos;
+            // 各行、各列の両端にある点。これより外側に点はない
+            pub fn frontier_points(&self) -> Vec<Pos> {
+                let mut frontier = vec![];
+                for (y, ends) in self.row_ends.iter().enumerate() {
+                    if let Some((left, right)) = *ends {
+                        let y = y as i32;
+                        frontier.push(Pos { x: left, y });
+                        frontier.push(Pos { x: right, y });
+                    }
                 }
+                for (x, ends) in self.column_ends.iter().enumerate() {
+                    if let Some((top, bottom)) = *ends {
+                        let x = x as i32;
+                        frontier.push(Pos { x, y: top });
+                        frontier.push(Pos { x, y: bottom });
+                    }
+                }
+                frontier.sort();
+                frontier.dedup();
+                frontier
             }
-        }
-
-        pub fn sample_square(&self) -> Square {
-            self.squares[rnd::gen_range(0, self.squares.len()) as usize]
-        }
 
-        pub fn calc_deletion_size(
-            &mut self,
-            new_pos: &Pos,
-            recursion_limit: usize,
-            parent_dep_size: usize,
-        ) -> usize {
-            let mut dep_size: usize = 1 + parent_dep_size;
+            pub fn nearest_point_pos(&self, from: &Pos, dir: &Dir) -> Option<Pos> {
+                let mut cur = *from;
 
-            let created_points = self
-                .grid
-                .point(&new_pos)
-                .as_ref()
-                .unwrap()
-                .created_points
-                .clone();
-            for created_point in &created_points {
-                if dep_size >= recursion_limit {
-                    return dep_size - parent_dep_size;
+                // 最大でもmax(width, height)回loopを回せばいい
+                // 置けないマスより先の点は繋げないので、見えないものとする
+                for _ in 0..usize::max(self.width, self.height) {
+                    cur += &dir.to_pos();
+                    if !self.is_valid(&cur) {
+                        break;
+                    }
+                    if self.has_point(&cur) {
+                        return Some(cur);
+                    }
                 }
+                None
+            }
 
-                // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
-                // TODO: 正当性の確認
-                if !self.grid.has_point(&created_point) {
-                    continue;
-                }
-                dep_size += self.calc_deletion_size(&created_point, recursion_limit, dep_size);
+            fn unregister_created_points(&mut self, a: &Pos, target: &Pos) {
+                let created_points = &self.point(a).as_mut().unwrap().created_points;
+                let target_index = created_points.iter().position(|x| *x == *target).unwrap();
+                self.point(a)
+                    .as_mut()
+                    .unwrap()
+                    .created_points
+                    .remove(target_index);
+                self.journal.record(Change::CreatedPointRemove {
+                    pos: *a,
+                    index: target_index,
+                    target: *target,
+                });
             }
 
-            dep_size - parent_dep_size
-        }
-    }
+            fn register_created_points(&mut self, a: &Pos, target: &Pos) {
+                self.point(a).as_mut().unwrap().created_points.push(*target);
+                self.journal.record(Change::CreatedPointPush { pos: *a });
+            }
 
-    impl State {
-        pub fn weight(&self, pos: &Pos) -> i32 {
-            let c = ((self.grid.size - 1) / 2) as i32;
-            (pos.y as i32 - c) * (pos.y as i32 - c) + (pos.x as i32 - c) * (pos.x as i32 - c) + 1
-        }
-    }
-}
-pub mod util {
-    use crate::Pos;
+            // 記録された変更を一つ戻す
+            pub fn undo(&mut self, change: Change) {
+                match change {
+                    Change::Edge { pos, dir, old } => {
+                        self.edges[pos.y as usize][pos.x as usize][dir.val() as usize] = old;
+                    }
+                    Change::Point { pos, old, index } => {
+                        match (self.has_point(&pos), old.is_some()) {
+                            (true, false) => {
+                                // 追加した点は最後にあるので、取り除いても他の点の並び順は変わらない
+                                let removed = self.point_set.remove(&pos);
+                                debug_assert_eq!(removed, index);
+                            }
+                            (false, true) => self.point_set.insert_at(&pos, index),
+                            _ => {}
+                        }
+                        self.points[pos.y as usize][pos.x as usize] = old;
+                    }
+                    Change::RowEnds { y, old } => {
+                        self.row_ends[y] = old;
+                    }
+                    Change::ColumnEnds { x, old } => {
+                        self.column_ends[x] = old;
+                    }
+                    Change::NearestPoint { pos, dir, old } => {
+                        self.point(&pos).as_mut().unwrap().nearest_points[dir.val() as usize] = old;
+                    }
+                    Change::CreatedPointPush { pos } => {
+                        self.point(&pos).as_mut().unwrap().created_points.pop();
+                    }
+                    Change::CreatedPointRemove { pos, index, target } => {
+                        self.point(&pos)
+                            .as_mut()
+                            .unwrap()
+                            .created_points
+                            .insert(index, target);
+                    }
+                    Change::SquarePush | Change::SquareRemove { .. } | Change::Score { .. } => {
+                        panic!("Change {:?} is not a grid change.", change)
+                    }
+                }
+            }
+
+            pub fn is_inside(&self, pos: &Pos) -> bool {
+                pos.x >= 0 && pos.y >= 0 && pos.x < self.width as i32 && pos.y < self.height as i32
+            }
 
-    pub mod rnd {
-        #[allow(unused)]
-        static mut S: usize = 88172645463325252;
+            pub fn is_blocked(&self, pos: &Pos) -> bool {
+                self.blocked[pos.y as usize][pos.x as usize]
+            }
 
-        #[allow(unused)]
-        #[inline]
-        pub fn next() -> usize {
-            unsafe {
-                S = S ^ S << 7;
-                S = S ^ S >> 9;
-                S
+            // 盤面の中で、置けないマスではない
+            pub fn is_valid(&self, pos: &Pos) -> bool {
+                self.is_inside(pos) && !self.is_blocked(pos)
             }
         }
 
-        #[allow(unused)]
-        #[inline]
-        pub fn nextf() -> f32 {
-            (next() & 4294967295) as f32 / 4294967296.
+
+
+    }
+    mod input {
+        use crate::ahc014::def::*;
+        use std::fmt;
+
+        // Nの上限。Nから配列を確保するので、壊れたヘッダで巨大な確保をしないようにする
+        pub const MAX_BOARD_SIZE: usize = 1000;
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct Input {
+            pub n: usize,
+            pub points: Vec<Pos>,
+        }
+
+        // 入力のどの行が、なぜ読めなかったか
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum InputError {
+            MissingHeader,
+            BadHeader {
+                line: usize,
+                message: String,
+            },
+            Malformed {
+                line: usize,
+                message: String,
+            },
+            // 1行目のMと点の数が合わない
+            CountMismatch {
+                line: usize,
+                expected: usize,
+                found: usize,
+            },
+            OutOfRange {
+                line: usize,
+                pos: Pos,
+                width: usize,
+                height: usize,
+            },
+            Duplicate {
+                line: usize,
+                pos: Pos,
+                first_line: usize,
+            },
+        }
+
+        impl InputError {
+            pub fn line(&self) -> usize {
+                match self {
+                    InputError::MissingHeader => 1,
+                    InputError::BadHeader { line, .. }
+                    | InputError::Malformed { line, .. }
+                    | InputError::CountMismatch { line, .. }
+                    | InputError::OutOfRange { line, .. }
+                    | InputError::Duplicate { line, .. } => *line,
+                }
+            }
         }
 
-        #[allow(unused)]
-        #[inline]
-        pub fn gen_range(low: usize, high: usize) -> usize {
-            (next() % (high - low)) + low
+        impl fmt::Display for InputError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "line {}: ", self.line())?;
+                match self {
+                    InputError::MissingHeader => write!(f, "missing header \"N M\""),
+                    InputError::BadHeader { message, .. } => {
+                        write!(f, "invalid header: {}", message)
+                    }
+                    InputError::Malformed { message, .. } => write!(f, "{}", message),
+                    InputError::CountMismatch {
+                        expected, found, ..
+                    } => write!(f, "expected M = {} points, found {}", expected, found),
+                    InputError::OutOfRange {
+                        pos, width, height, ..
+                    } => write!(
+                        f,
+                        "({}, {}) is outside the {}x{} board",
+                        pos.x, pos.y, width, height
+                    ),
+                    InputError::Duplicate {
+                        pos, first_line, ..
+                    } => write!(
+                        f,
+                        "({}, {}) is duplicated (first on line {})",
+                        pos.x, pos.y, first_line
+                    ),
+                }
+            }
         }
-    }
 
-    pub mod time {
-        static mut START: f64 = -1.;
-        #[allow(unused)]
-        pub fn start_clock() {
-            let _ = elapsed_seconds();
+        // 空行を飛ばして、(行番号, 整数列)を返す
+        pub fn parse_lines(s: &str) -> impl Iterator<Item = (usize, Result<Vec<i32>, String>)> + '_ {
+            s.lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    let values = line
+                        .split_whitespace()
+                        .map(|v| {
+                            v.parse::<i32>()
+                                .map_err(|_| format!("{:?} is not an integer", v))
+                        })
+                        .collect::<Result<Vec<i32>, String>>();
+                    (i + 1, values)
+                })
+        }
+
+        impl Input {
+            pub fn m(&self) -> usize {
+                self.points.len()
+            }
+
+            // 点はN×Nの盤面に収まっている必要がある
+            pub fn parse(s: &str) -> Result<Input, InputError> {
+                Input::parse_with_bounds(s, None)
+            }
+
+            // 盤面を別に与える場合は、点はwidth×heightに収まっていればいい
+            pub fn parse_within(s: &str, width: usize, height: usize) -> Result<Input, InputError> {
+                Input::parse_with_bounds(s, Some((width, height)))
+            }
+
+            fn parse_with_bounds(s: &str, bounds: Option<(usize, usize)>) -> Result<Input, InputError> {
+                let mut lines = parse_lines(s);
+                let (line, header) = lines.next().ok_or(InputError::MissingHeader)?;
+                let header = header.map_err(|message| InputError::BadHeader { line, message })?;
+                if header.len() != 2 {
+                    return Err(InputError::BadHeader {
+                        line,
+                        message: format!("expected 2 integers, found {}", header.len()),
+                    });
+                }
+                if header[0] <= 0 || header[1] <= 0 {
+                    return Err(InputError::BadHeader {
+                        line,
+                        message: format!("N = {} and M = {} must be positive", header[0], header[1]),
+                    });
+                }
+                let (n, m) = (header[0] as usize, header[1] as usize);
+                if n > MAX_BOARD_SIZE {
+                    return Err(InputError::BadHeader {
+                        line,
+                        message: format!("N = {} must be at most {}", n, MAX_BOARD_SIZE),
+                    });
+                }
+                let (width, height) = bounds.unwrap_or((n, n));
+                if m > width * height {
+                    return Err(InputError::BadHeader {
+                        line,
+                        message: format!(
+                            "M = {} points do not fit on the {}x{} board",
+                            m, width, height
+                        ),
+                    });
+                }
+
+                let mut points: Vec<Pos> = vec![];
+                // 重複を見つけるための、各マスの点がある行番号
+                let mut lines_of_pos = vec![vec![0; width]; height];
+                let mut last_line = line;
+                for (line, values) in lines {
+                    last_line = line;
+                    let values = values.map_err(|message| InputError::Malformed { line, message })?;
+                    if values.len() != 2 {
+                        return Err(InputError::Malformed {
+                            line,
+                            message: format!("expected \"x y\", found {} integers", values.len()),
+                        });
+                    }
+                    if points.len() == m {
+                        return Err(InputError::CountMismatch {
+                            line,
+                            expected: m,
+                            found: m + 1,
+                        });
+                    }
+                    let pos = Pos {
+                        x: values[0],
+                        y: values[1],
+                    };
+                    if pos.x < 0 || pos.y < 0 || pos.x >= width as i32 || pos.y >= height as i32 {
+                        return Err(InputError::OutOfRange {
+                            line,
+                            pos,
+                            width,
+                            height,
+                        });
+                    }
+                    let first_line = &mut lines_of_pos[pos.y as usize][pos.x as usize];
+                    if *first_line != 0 {
+                        return Err(InputError::Duplicate {
+                            line,
+                            pos,
+                            first_line: *first_line,
+                        });
+                    }
+                    *first_line = line;
+                    points.push(pos);
+                }
+                if points.len() != m {
+                    return Err(InputError::CountMismatch {
+                        line: last_line,
+                        expected: m,
+                        found: points.len(),
+                    });
+                }
+                Ok(Input { n, points })
+            }
         }
 
-        #[allow(unused)]
-        #[inline]
-        pub fn elapsed_seconds() -> f64 {
-            let t = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs_f64();
-            unsafe {
-                if START < 0. {
-                    START = t;
+        // 問題の入力形式で書き出す
+        impl fmt::Display for Input {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                writeln!(f, "{} {}", self.n, self.m())?;
+                for pos in &self.points {
+                    writeln!(f, "{} {}", pos.x, pos.y)?;
                 }
-                t - START
+                Ok(())
             }
         }
-    }
 
-    pub fn calc_weight(n: i32, pos: &Pos) -> i32 {
-        let c = ((n - 1) / 2) as i32;
-        (pos.y as i32 - c) * (pos.y as i32 - c) + (pos.x as i32 - c) * (pos.x as i32 - c) + 1
     }
+    mod journal {
+        use crate::ahc014::def::*;
+
+        // Grid、Stateに対する低レベルな変更の記録
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum Change {
+            Edge {
+                pos: Pos,
+                dir: Dir,
+                old: bool,
+            },
+            // indexはPointSetでのposの添字。戻すときに並び順も元に戻す
+            Point {
+                pos: Pos,
+                old: Option<Point>,
+                index: usize,
+            },
+            NearestPoint {
+                pos: Pos,
+                dir: Dir,
+                old: Option<Pos>,
+            },
+            // 行yにある点のxの最小値と最大値
+            RowEnds {
+                y: usize,
+                old: Option<(i32, i32)>,
+            },
+            // 列xにある点のyの最小値と最大値
+            ColumnEnds {
+                x: usize,
+                old: Option<(i32, i32)>,
+            },
+            CreatedPointPush {
+                pos: Pos,
+            },
+            CreatedPointRemove {
+                pos: Pos,
+                index: usize,
+                target: Pos,
+            },
+            SquarePush,
+            SquareRemove {
+                index: usize,
+                square: Square,
+            },
+            Score {
+                old: i32,
+            },
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct Journal {
+            changes: Vec<Change>,
+            // 各トランザクションの開始位置
+            marks: Vec<usize>,
+        }
+
+        impl Default for Journal {
+            fn default() -> Journal {
+                Journal::new()
+            }
+        }
 
-    pub fn calc_real_score(n: usize, m: usize, score: i32) -> i32 {
-        let mut s = 0;
-        for i in 0..n {
-            for j in 0..n {
-                s += calc_weight(
-                    n as i32,
-                    &Pos {
-                        x: i as i32,
-                        y: j as i32,
-                    },
-                );
+        impl Journal {
+            pub fn new() -> Journal {
+                Journal {
+                    changes: vec![],
+                    marks: vec![],
+                }
+            }
+
+            // トランザクション中のみ記録する
+            #[inline]
+            pub fn is_recording(&self) -> bool {
+                !self.marks.is_empty()
+            }
+
+            #[inline]
+            pub fn record(&mut self, change: Change) {
+                if self.is_recording() {
+                    self.changes.push(change);
+                }
+            }
+
+            pub fn begin(&mut self) {
+                self.marks.push(self.changes.len());
+            }
+
+            pub fn commit(&mut self) {
+                debug_assert!(self.is_recording());
+                self.marks.pop();
+                // 一番外側のトランザクションが確定したら、もう戻す必要はない
+                if self.marks.is_empty() {
+                    self.changes.clear();
+                }
+            }
+
+            // 最後に開始したトランザクションの変更を、新しい順に取り出す
+            pub fn pop_transaction(&mut self) -> Vec<Change> {
+                debug_assert!(self.is_recording());
+                let mark = self.marks.pop().unwrap();
+                let mut changes = self.changes.split_off(mark);
+                changes.reverse();
+                changes
             }
         }
-        let result = 1e6 * (n as f32 * n as f32) * score as f32 / (m as f32 * s as f32);
-        result.round() as i32
     }
-}
+    mod neighborhood {
+        use crate::ahc014::config::*;
+        use crate::ahc014::def::*;
+        use crate::ahc014::framework::*;
+        use crate::ahc014::state::*;
+        use crate::ahc014::util::*;
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Neighborhood {
+            Add = 0,
+            Delete = 1,
+            ChangeSquare = 2,
+            SplitSquare = 3,
+            MultipleAdd = 4,
+            OutwardGrowth = 5,
+        }
+
+        impl Neighborhood {
+            pub fn perform(&self, state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                match self {
+                    Neighborhood::Add => Neighborhood::perform_add(state),
+                    Neighborhood::Delete => Neighborhood::perform_delete(state, config),
+                    Neighborhood::ChangeSquare => Neighborhood::perform_change_square(state, config),
+                    Neighborhood::SplitSquare => Neighborhood::perform_split_square(state, config),
+                    Neighborhood::MultipleAdd => Neighborhood::perform_multiple_add(state, config),
+                    Neighborhood::OutwardGrowth => Neighborhood::perform_outward_growth(state, config),
+                }
+            }
 
-use std::{fs, io::Write};
+            fn perform_multiple_add(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                let selected_p = state.sample_point_pos();
+                let mut performed_commands = vec![];
+                let mut recursion_count = 0;
+                Neighborhood::attempt_multiple_add(
+                    state,
+                    &selected_p,
+                    &mut recursion_count,
+                    &config.multiple_add_recursion_limit,
+                    &mut performed_commands,
+                    state.get_score(1.),
+                );
+                performed_commands
+            }
 
-use def::*;
-use framework::*;
-use neighborhood::*;
-use proconio::input;
-use state::*;
-use util::*;
+            fn attempt_multiple_add(
+                state: &mut State,
+                pos: &Pos,
+                recursion_count: &mut usize,
+                recursion_limit: &usize,
+                performed_commands: &mut Vec<Command>,
+                start_score: f32,
+            ) {
+                if state.get_score(1.) > start_score {
+                    return;
+                }
+                if *recursion_count >= *recursion_limit {
+                    return;
+                }
+                *recursion_count += 1;
+                let mut used_bits: usize = 0;
+                for _ in 0..DIR_MAX {
+                    let i = rnd::gen_range(0, DIR_MAX);
+                    if used_bits & (1 << i) > 0 {
+                        continue;
+                    }
+                    used_bits |= 1 << i;
+                    let dir = Dir::from_i32(i as i32);
+                    if let Some(nearest_pos) =
+                        state.grid.point(pos).as_ref().unwrap().nearest_points[dir.val() as usize]
+                    {
+                        let mut add = Neighborhood::attempt_add(state, &nearest_pos, None);
+                        performed_commands.append(&mut add);
+                        Neighborhood::attempt_multiple_add(
+                            state,
+                            &nearest_pos,
+                            recursion_count,
+                            recursion_limit,
+                            performed_commands,
+                            start_score,
+                        );
+                    }
+                }
+            }
 
-#[allow(unused_variables)]
-fn calc_start_temp(n: usize, m: usize) -> f32 {
-    500. * (n as f32 / 30.).powf(2.)
-}
+            // 外周の点から、なるべく遠くに点を作る。作った点からさらに外側へ少しずつ伸ばす
+            fn perform_outward_growth(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                let frontier = state.grid.frontier_points();
+                if frontier.is_empty() {
+                    return vec![];
+                }
+                let starts: Vec<Pos> = (0..config.outward_growth_samples)
+                    .map(|_| frontier[rnd::gen_range(0, frontier.len())])
+                    .collect();
+                let mut square = match Neighborhood::find_outward_square(state, &starts, i32::MIN) {
+                    Some(square) => square,
+                    None => return vec![],
+                };
+                let mut performed_commands = state.perform_command(&Command::Add { square });
+
+                for _ in 0..config.outward_growth_chain_limit {
+                    // 作った点と、その点から見える点を起点にする
+                    let pos = square.new_pos;
+                    let mut starts = vec![pos];
+                    starts.extend(
+                        state
+                            .grid
+                            .point(&pos)
+                            .as_ref()
+                            .unwrap()
+                            .nearest_points
+                            .iter()
+                            .flatten(),
+                    );
+                    let min_distance = Neighborhood::center_distance(state, &pos);
+                    square = match Neighborhood::find_outward_square(state, &starts, min_distance) {
+                        Some(square) => square,
+                        None => break,
+                    };
+                    performed_commands.append(&mut state.perform_command(&Command::Add { square }));
+                }
+                performed_commands
+            }
 
-#[allow(unused_variables)]
-fn calc_end_temp(n: usize, m: usize) -> f32 {
-    25. * (n as f32 / 30.).powf(2.)
-}
+            // 盤面の中心からの距離の2乗の4倍。中心が半端な座標でも整数で比べられるようにする
+            fn center_distance(state: &State, pos: &Pos) -> i32 {
+                let dx = 2 * pos.x - (state.grid.width as i32 - 1);
+                let dy = 2 * pos.y - (state.grid.height as i32 - 1);
+                dx * dx + dy * dy
+            }
 
-struct NeighborhoodSelector {
-    total_cnt: Vec<i32>,
-    adopted_cnt: Vec<i32>,
-}
+            // startsのどれかを対角の点として作れる四角のうち、新しい点が盤面の中心から最も遠いもの
+            // 重みの設定に関係なく、最も外側に伸ばせる四角になる
+            fn find_outward_square(state: &mut State, starts: &[Pos], min_distance: i32) -> Option<Square> {
+                let mut best: Option<(i32, Square)> = None;
+                for pos in starts {
+                    let nearest_points = state.grid.point(pos).as_ref().unwrap().nearest_points;
+                    for i in 0..DIR_MAX {
+                        let dir = Dir::from_i32(i as i32);
+                        if let (Some(pos_prev), Some(pos_next)) = (
+                            nearest_points[dir.prev().val() as usize],
+                            nearest_points[dir.next().val() as usize],
+                        ) {
+                            let new_pos = &pos_next + &(&pos_prev - pos);
+                            if !state.grid.is_valid(&new_pos) {
+                                continue;
+                            }
+                            let distance = Neighborhood::center_distance(state, &new_pos);
+                            if distance <= min_distance
+                                || best.is_some_and(|(best_distance, _)| distance <= best_distance)
+                            {
+                                continue;
+                            }
+                            let square = Square::new(new_pos, *pos, [pos_prev, pos_next]);
+                            if state.check_add(&square, false).is_ok() {
+                                best = Some((distance, square));
+                            }
+                        }
+                    }
+                }
+                best.map(|(_, square)| square)
+            }
 
-impl NeighborhoodSelector {
-    fn new(neighborhood_count: usize) -> NeighborhoodSelector {
-        NeighborhoodSelector {
-            total_cnt: vec![0; neighborhood_count],
-            adopted_cnt: vec![0; neighborhood_count],
+            fn perform_add(state: &mut State) -> Vec<Command> {
+                let selected_p = state.sample_point_pos();
+                Neighborhood::attempt_add(state, &selected_p, None)
+            }
+
+            fn attempt_add(state: &mut State, pos: &Pos, ignore_dir: Option<&Dir>) -> Vec<Command> {
+                debug_assert!(state.grid.has_point(pos));
+                let nearest_points = state.grid.point(pos).as_ref().unwrap().nearest_points;
+                let mut used_bits: usize = 0;
+                for _ in 0..DIR_MAX {
+                    let i = rnd::gen_range(0, DIR_MAX);
+                    if used_bits & (1 << i) > 0 {
+                        continue;
+                    }
+                    used_bits |= 1 << i;
+                    let diagonal_dir = Dir::from_i32(i as i32);
+                    if let Some(ignore_dir) = ignore_dir {
+                        if ignore_dir == &diagonal_dir {
+                            continue;
+                        }
+                    }
+                    let performed_commands =
+                        Neighborhood::attempt_add_dir(state, pos, &nearest_points, &diagonal_dir);
+                    if !performed_commands.is_empty() {
+                        return performed_commands;
+                    }
+                }
+                vec![]
+            }
+
+            fn attempt_add_dir(
+                state: &mut State,
+                pos: &Pos,
+                nearest_points: &[Option<Pos>; DIR_MAX],
+                dir: &Dir,
+            ) -> Vec<Command> {
+                let dir_next = dir.next();
+                let dir_prev = dir.prev();
+
+                if let (Some(pos_prev), Some(pos_next)) = (
+                    &nearest_points[dir_prev.val() as usize],
+                    &nearest_points[dir_next.val() as usize],
+                ) {
+                    let new_pos = pos_next + &(pos_prev - pos);
+
+                    if !state.grid.is_valid(&new_pos) {
+                        return vec![];
+                    }
+                    if !state.grid.has_point(pos)
+                        || !state.grid.has_point(pos_prev)
+                        || !state.grid.has_point(pos_next)
+                        || state.grid.has_point(&new_pos)
+                    {
+                        return vec![];
+                    }
+
+                    let connect: [Pos; 2] = [*pos_prev, *pos_next];
+                    let square = Square::new(new_pos, *pos, connect);
+
+                    let performed_commands = state.perform_command(&Command::Add { square });
+                    if !performed_commands.is_empty() {
+                        return performed_commands;
+                    }
+                }
+
+                vec![]
+            }
+
+            fn perform_delete(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                if state.squares.is_empty() {
+                    return vec![];
+                }
+                let square = state.sample_square();
+                Neighborhood::attempt_delete(state, &square, config)
+            }
+
+            fn attempt_delete(state: &mut State, square: &Square, config: &SolverConfig) -> Vec<Command> {
+                // 削除する四角が多すぎるときは不採用
+                let limit = config.deletion_recursion_limit;
+                if state.calc_deletion_size(&square.new_pos, limit, 0) >= limit {
+                    return vec![];
+                }
+                state.perform_command(&Command::Delete { square: *square })
+            }
+
+            fn perform_change_square(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                // 四角を作っている点を探す
+                if state.squares.is_empty() {
+                    return vec![];
+                }
+                let square = state.sample_square();
+                Neighborhood::attempt_change_square(state, &square, config)
+            }
+
+            fn attempt_change_square(
+                state: &mut State,
+                square: &Square,
+                config: &SolverConfig,
+            ) -> Vec<Command> {
+                let start_score = state.get_score(1.);
+                let mut performed_commands = Neighborhood::attempt_delete(state, square, config);
+
+                // 四角を消せなかったら中止
+                if performed_commands.is_empty() {
+                    return performed_commands;
+                }
+
+                // 再帰的にposの点も消してしまった時は中止
+                if !state.grid.has_point(&square.diagonal) {
+                    return performed_commands;
+                }
+
+                let mut recursion_count: usize = 0;
+                Neighborhood::attempt_multiple_add(
+                    state,
+                    &square.diagonal,
+                    &mut recursion_count,
+                    &config.multiple_add_recursion_limit,
+                    &mut performed_commands,
+                    start_score,
+                );
+                performed_commands
+            }
+
+            fn perform_split_square(state: &mut State, config: &SolverConfig) -> Vec<Command> {
+                if state.squares.is_empty() {
+                    return vec![];
+                }
+                let selected_square = state.sample_square();
+                Neighborhood::attempt_split_square(state, &selected_square, config)
+            }
+
+            fn attempt_split_square(
+                state: &mut State,
+                square: &Square,
+                config: &SolverConfig,
+            ) -> Vec<Command> {
+                let nearest_points = state
+                    .grid
+                    .point(&square.diagonal)
+                    .as_ref()
+                    .unwrap()
+                    .nearest_points;
+                let dir1 = Pos::get_dir(&square.diagonal, &square.connect[0]);
+                let dir2 = Pos::get_dir(&square.diagonal, &square.connect[1]);
+
+                if nearest_points[dir1.val() as usize] != Some(square.connect[0])
+                    || nearest_points[dir2.val() as usize] != Some(square.connect[1])
+                {
+                    let start_score = state.get_score(1.);
+                    let mut performed_commands = Neighborhood::attempt_delete(state, square, config);
+
+                    if performed_commands.is_empty() {
+                        return vec![];
+                    }
+
+                    let mut recursion_count: usize = 0;
+                    Neighborhood::attempt_multiple_add(
+                        state,
+                        &square.diagonal,
+                        &mut recursion_count,
+                        &config.multiple_add_recursion_limit,
+                        &mut performed_commands,
+                        start_score,
+                    );
+                    return performed_commands;
+                }
+
+                vec![]
+            }
         }
-    }
 
-    fn output_statistics(&self) {
-        for i in 0..self.total_cnt.len() {
-            eprintln!(
-                "{:?}: (total_cnt: {}, adopted_cnt: {}, adopted_ratio: {:.4})",
-                Neighborhood::from_i32(i as i32),
-                self.total_cnt[i],
-                self.adopted_cnt[i],
-                self.adopted_cnt[i] as f32 / self.total_cnt[i] as f32,
-            );
+        impl Neighborhood {
+            pub fn all() -> [Neighborhood; 6] {
+                [
+                    Neighborhood::Add,
+                    Neighborhood::Delete,
+                    Neighborhood::ChangeSquare,
+                    Neighborhood::SplitSquare,
+                    Neighborhood::MultipleAdd,
+                    Neighborhood::OutwardGrowth,
+                ]
+            }
+
+            pub fn from_i32(v: i32) -> Neighborhood {
+                match v {
+                    0 => Neighborhood::Add,
+                    1 => Neighborhood::Delete,
+                    2 => Neighborhood::ChangeSquare,
+                    3 => Neighborhood::SplitSquare,
+                    4 => Neighborhood::MultipleAdd,
+                    5 => Neighborhood::OutwardGrowth,
+                    _ => panic!("Neighborhood value {} is invalid.", v),
+                }
+            }
         }
+
+
+
+
     }
-}
+    mod point_set {
+        use crate::ahc014::def::*;
+
+        // 点がある位置の集合。追加、削除がO(1)で、添字で一様に選べる
+        #[derive(Clone, Debug)]
+        pub struct PointSet {
+            positions: Vec<Pos>,
+            // 各マスの点のpositionsでの添字
+            index: Vec<Vec<Option<usize>>>,
+        }
+
+        impl PointSet {
+            pub fn new(width: usize, height: usize) -> PointSet {
+                PointSet {
+                    positions: vec![],
+                    index: vec![vec![None; width]; height],
+                }
+            }
 
-impl INeighborhoodSelector for NeighborhoodSelector {
-    fn select(&self) -> Neighborhood {
-        let p = rnd::nextf();
-        if p < 0.05 {
-            Neighborhood::Delete
-        } else if p < 0.15 {
-            Neighborhood::ChangeSquare
-        } else if p < 0.25 {
-            Neighborhood::SplitSquare
-        } else {
-            Neighborhood::Add
+            pub fn len(&self) -> usize {
+                self.positions.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.positions.is_empty()
+            }
+
+            pub fn positions(&self) -> &[Pos] {
+                &self.positions
+            }
+
+            pub fn contains(&self, pos: &Pos) -> bool {
+                self.index[pos.y as usize][pos.x as usize].is_some()
+            }
+
+            pub fn insert(&mut self, pos: &Pos) {
+                debug_assert!(!self.contains(pos));
+                self.index[pos.y as usize][pos.x as usize] = Some(self.positions.len());
+                self.positions.push(*pos);
+            }
+
+            // 最後の点を空いた場所に移す。posがあった添字を返す
+            pub fn remove(&mut self, pos: &Pos) -> usize {
+                let i = self.index[pos.y as usize][pos.x as usize].take().unwrap();
+                self.positions.swap_remove(i);
+                if let Some(moved) = self.positions.get(i) {
+                    self.index[moved.y as usize][moved.x as usize] = Some(i);
+                }
+                i
+            }
+
+            // removeの逆。iにあった点を最後に戻して、posをiに置く
+            pub fn insert_at(&mut self, pos: &Pos, i: usize) {
+                self.insert(pos);
+                let last = self.positions.len() - 1;
+                self.positions.swap(i, last);
+                for j in [i, last] {
+                    let moved = self.positions[j];
+                    self.index[moved.y as usize][moved.x as usize] = Some(j);
+                }
+            }
         }
-    }
 
-    fn step(&mut self, neighborhood: &Neighborhood, adopted: bool) {
-        self.total_cnt[*neighborhood as usize] += 1;
-        if adopted {
-            self.adopted_cnt[*neighborhood as usize] += 1;
+        // 並び順は追加と削除の順で変わるので、集合として比べる
+        impl PartialEq for PointSet {
+            fn eq(&self, other: &PointSet) -> bool {
+                self.len() == other.len() && self.positions.iter().all(|pos| other.contains(pos))
+            }
         }
-    }
-}
 
-struct Optimizer {
-    start_temp: f32,
-    end_temp: f32,
-    current_temp: f32,
-}
+        impl Eq for PointSet {}
 
-impl IOptimizer for Optimizer {
-    fn update_temp(&mut self, progress: f32) {
-        self.current_temp = self.start_temp + (self.end_temp - self.start_temp) * progress;
     }
+    mod report {
+        use std::fmt::Write;
+
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct NeighborhoodReport {
+            pub name: String,
+            pub total: i32,
+            pub adopted: i32,
+        }
+
+        // 1回の実行結果。バッチ処理で読めるようにJSONで出す
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct SolveReport {
+            pub loop_count: usize,
+            // 焼きなまし終了時の内部スコア
+            pub final_score: i32,
+            // 出力する状態の内部スコア
+            pub best_score: i32,
+            pub real_score: i32,
+            pub elapsed_seconds: f64,
+            pub neighborhoods: Vec<NeighborhoodReport>,
+        }
+
+        pub fn escape_json(s: &str) -> String {
+            let mut escaped = String::new();
+            for c in s.chars() {
+                match c {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+                    c => escaped.push(c),
+                }
+            }
+            escaped
+        }
+
+        impl SolveReport {
+            // 1行のJSONオブジェクト
+            pub fn to_json(&self) -> String {
+                let neighborhoods: Vec<String> = self
+                    .neighborhoods
+                    .iter()
+                    .map(|n| {
+                        format!(
+                            r#"{{"name":"{}","total":{},"adopted":{}}}"#,
+                            escape_json(&n.name),
+                            n.total,
+                            n.adopted
+                        )
+                    })
+                    .collect();
+                format!(
+                    r#"{{"loop_count":{},"final_score":{},"best_score":{},"real_score":{},"elapsed_seconds":{:.3},"neighborhoods":[{}]}}"#,
+                    self.loop_count,
+                    self.final_score,
+                    self.best_score,
+                    self.real_score,
+                    self.elapsed_seconds,
+                    neighborhoods.join(",")
+                )
+            }
+        }
 
-    fn should_adopt_new_state(&self, score_diff: f32) -> bool {
-        let prob = (score_diff / self.current_temp).exp();
-        return prob > rnd::nextf();
     }
-}
+    mod sampler {
+        use crate::ahc014::config::*;
+        use crate::ahc014::def::*;
+        use crate::ahc014::grid::*;
+        use crate::ahc014::util::*;
+
+        // 受理されないまま、この回数候補を引いたら最後の候補を使う
+        const MAX_TRIALS: usize = 64;
+        // 深さはこの値で打ち切って0..1にする
+        const DEPTH_SCALE: usize = 10;
+
+        // 焼きなましの進み具合に応じて、点や四角を重み付きで選ぶ
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct Sampler {
+            pub config: SamplerConfig,
+            // 0から1
+            pub progress: f32,
+        }
+
+        impl Sampler {
+            pub fn new(config: SamplerConfig) -> Sampler {
+                Sampler {
+                    config,
+                    progress: 0.,
+                }
+            }
 
-impl Optimizer {
-    fn new(start_temp: f32, end_temp: f32) -> Optimizer {
-        let mut optimizer = Optimizer {
-            start_temp,
-            end_temp,
-            current_temp: 0.,
-        };
-        optimizer.update_temp(0.);
-        optimizer
-    }
-}
+            pub fn uniform() -> Sampler {
+                Sampler::new(SamplerConfig::default())
+            }
+
+            fn is_uniform(&self) -> bool {
+                self.config == SamplerConfig::default()
+            }
+
+            // (係数, 0..1の値)の組
+            fn features(&self, grid: &Grid, pos: &Pos) -> [(f32, f32); 3] {
+                let config = &self.config;
+                let center = ((grid.width - 1) as f32 / 2., (grid.height - 1) as f32 / 2.);
+                let distance = |x: f32, y: f32| (x * x + y * y).sqrt();
+                let max_distance = f32::max(1., distance(center.0, center.1));
+                let center_distance = distance(pos.x as f32 - center.0, pos.y as f32 - center.1);
+
+                let point = grid.points[pos.y as usize][pos.x as usize]
+                    .as_ref()
+                    .unwrap();
+                let depth = usize::min(point.depth, DEPTH_SCALE);
+                let free_degree = (0..DIR_MAX)
+                    .filter(|&i| !grid.edges[pos.y as usize][pos.x as usize][i])
+                    .count();
+
+                let center_coef = config.center_distance_start
+                    + (config.center_distance_end - config.center_distance_start) * self.progress;
+                [
+                    (center_coef, f32::min(1., center_distance / max_distance)),
+                    (config.depth, depth as f32 / DEPTH_SCALE as f32),
+                    (config.free_degree, free_degree as f32 / DIR_MAX as f32),
+                ]
+            }
+
+            // 重みexp(Σ係数*値)を、取りうる最大値が1になるように割ったもの
+            pub fn acceptance(&self, grid: &Grid, pos: &Pos) -> f32 {
+                self.features(grid, pos)
+                    .iter()
+                    .map(|&(coef, value)| {
+                        if coef > 0. {
+                            coef * (value - 1.)
+                        } else {
+                            coef * value
+                        }
+                    })
+                    .sum::<f32>()
+                    .exp()
+            }
+
+            // 一様に引いた候補を、位置の重みに比例する確率で受理する
+            pub fn sample<T: Copy>(&self, grid: &Grid, candidates: &[T], pos_of: impl Fn(&T) -> Pos) -> T {
+                let mut candidate = candidates[rnd::gen_range(0, candidates.len())];
+                if self.is_uniform() {
+                    return candidate;
+                }
+                for _ in 1..MAX_TRIALS {
+                    if rnd::nextf() < self.acceptance(grid, &pos_of(&candidate)) {
+                        break;
+                    }
+                    candidate = candidates[rnd::gen_range(0, candidates.len())];
+                }
+                candidate
+            }
+        }
 
-impl IState for State {
-    #[allow(unused_variables)]
-    fn get_score(&self, progress: f32) -> f32 {
-        let base_score = self.score.base as f32;
-        base_score
     }
+    mod solver {
+        use crate::ahc014::config::*;
+        use crate::ahc014::def::*;
+        use crate::ahc014::framework::*;
+        use crate::ahc014::grid::*;
+        use crate::ahc014::input::*;
+        use crate::ahc014::neighborhood::*;
+        use crate::ahc014::report::*;
+        use crate::ahc014::sampler::*;
+        use crate::ahc014::state::*;
+        use crate::ahc014::telemetry::*;
+        use crate::ahc014::util::*;
+        use crate::ahc014::weight::*;
+        use std::collections::HashMap;
+        use std::rc::Rc;
+
+        pub struct NeighborhoodSelector {
+            // 累積確率と近傍。どれにも当たらなければAdd
+            thresholds: Vec<(f32, Neighborhood)>,
+            total_cnt: Vec<i32>,
+            adopted_cnt: Vec<i32>,
+        }
+
+        impl NeighborhoodSelector {
+            pub fn new(probs: &[f32]) -> NeighborhoodSelector {
+                let total: f32 = probs.iter().sum();
+                let mut cumulative = 0.;
+                let thresholds = [
+                    Neighborhood::Delete,
+                    Neighborhood::ChangeSquare,
+                    Neighborhood::SplitSquare,
+                    Neighborhood::MultipleAdd,
+                    Neighborhood::OutwardGrowth,
+                ]
+                .into_iter()
+                .map(|neighborhood| {
+                    cumulative += probs[neighborhood as usize] / total;
+                    (cumulative, neighborhood)
+                })
+                .collect();
+                NeighborhoodSelector {
+                    thresholds,
+                    total_cnt: vec![0; probs.len()],
+                    adopted_cnt: vec![0; probs.len()],
+                }
+            }
 
-    fn perform_command(&mut self, command: &Command) -> Vec<Command> {
-        match command {
-            Command::Add { square } => self.perform_add(square, false),
-            Command::Delete { square } => {
-                // 削除する四角が多すぎるときは不採用
-                if self.calc_deletion_size(&square.new_pos, DELETION_RECURSION_LIMIT, 0)
-                    >= DELETION_RECURSION_LIMIT
+            pub fn report(&self) -> Vec<NeighborhoodReport> {
+                (0..self.total_cnt.len())
+                    .map(|i| NeighborhoodReport {
+                        name: format!("{:?}", Neighborhood::from_i32(i as i32)),
+                        total: self.total_cnt[i],
+                        adopted: self.adopted_cnt[i],
+                    })
+                    .collect()
+            }
+        }
+
+        impl INeighborhoodSelector for NeighborhoodSelector {
+            fn select(&self) -> Neighborhood {
+                let p = rnd::nextf();
+                for (threshold, neighborhood) in &self.thresholds {
+                    if p < *threshold {
+                        return *neighborhood;
+                    }
+                }
+                Neighborhood::Add
+            }
+
+            fn step(&mut self, neighborhood: &Neighborhood, adopted: bool) {
+                self.total_cnt[*neighborhood as usize] += 1;
+                if adopted {
+                    self.adopted_cnt[*neighborhood as usize] += 1;
+                }
+            }
+        }
+
+        pub struct Optimizer {
+            start_temp: f32,
+            end_temp: f32,
+            pub current_temp: f32,
+        }
+
+        impl IOptimizer for Optimizer {
+            fn update_temp(&mut self, progress: f32) {
+                self.current_temp = self.start_temp + (self.end_temp - self.start_temp) * progress;
+            }
+
+            fn should_adopt_new_state(&self, score_diff: f32) -> bool {
+                let prob = (score_diff / self.current_temp).exp();
+                prob > rnd::nextf()
+            }
+        }
+
+        impl Optimizer {
+            pub fn new(start_temp: f32, end_temp: f32) -> Optimizer {
+                let mut optimizer = Optimizer {
+                    start_temp,
+                    end_temp,
+                    current_temp: 0.,
+                };
+                optimizer.update_temp(0.);
+                optimizer
+            }
+        }
+
+        pub struct Solver {
+            pub state: State,
+            pub neighborhood_selector: NeighborhoodSelector,
+            pub optimizer: Optimizer,
+            // --score-log、--chartで書き出すときだけ記録する
+            pub score_log: Option<ScoreLog>,
+            // 四角のidからその四角を作った近傍
+            pub square_origins: HashMap<i32, Neighborhood>,
+            pub trajectory: Option<Trajectory>,
+            pub config: SolverConfig,
+            pub loop_count: usize,
+            pub final_score: i32,
+        }
+
+        impl ISolver for Solver {
+            fn solve(&mut self, time_limit: f32) {
+                let mut loop_count = 0;
+                let mut best_state = self.state.clone();
+                let m = self.state.initial_points().len();
+                let mut progress = self.progress(loop_count, time_limit);
+                // ループ回数で打ち切るときは、進み具合の更新の間隔に関係なくちょうど止める
+                let iteration_limit = self.config.iteration_limit.unwrap_or(usize::MAX);
+                while progress < 1. && loop_count < iteration_limit {
+                    let is_interval = (loop_count % self.config.loop_interval) == 0;
+                    if is_interval {
+                        progress = self.progress(loop_count, time_limit);
+                        self.optimizer.update_temp(progress);
+                        self.state.sampler.progress = progress;
+                    }
+
+                    if let Some(trajectory) = &mut self.trajectory {
+                        if trajectory.should_record(loop_count) {
+                            trajectory.record(loop_count, self.optimizer.current_temp, &self.state);
+                        }
+                    }
+
+                    let neighborhood = self.neighborhood_selector.select();
+
+                    let current_score = self.state.get_score(progress);
+
+                    self.state.begin();
+                    let performed_commands = neighborhood.perform(&mut self.state, &self.config);
+
+                    let new_score = self.state.get_score(progress);
+
+                    let adopt_new_state = self
+                        .optimizer
+                        .should_adopt_new_state(new_score - current_score)
+                        && !performed_commands.is_empty();
+
+                    if adopt_new_state {
+                        self.state.commit();
+                        for command in &performed_commands {
+                            if let Command::Add { square } = command {
+                                self.square_origins.insert(square.id, neighborhood);
+                            }
+                        }
+                    } else {
+                        self.state.rollback();
+                    }
+
+                    self.neighborhood_selector
+                        .step(&neighborhood, adopt_new_state);
+
+                    if is_interval && self.state.get_score(1.) > best_state.get_score(1.) {
+                        best_state = self.state.clone();
+                    }
+                    if let (true, Some(score_log)) = (is_interval, &mut self.score_log) {
+                        let weight_map = &self.state.weight_map;
+                        score_log.record(
+                            loop_count,
+                            self.optimizer.current_temp,
+                            calc_real_score(weight_map, m, self.state.score.base),
+                            calc_real_score(weight_map, m, best_state.score.base),
+                            &self.neighborhood_selector.total_cnt,
+                            &self.neighborhood_selector.adopted_cnt,
+                        );
+                    }
+                    loop_count += 1;
+                }
+                self.loop_count = loop_count;
+                self.final_score = self.state.score.base;
+                self.state = best_state.clone();
+            }
+        }
+
+        impl Solver {
+            pub fn new(
+                state: State,
+                neighborhood_selector: NeighborhoodSelector,
+                optimizer: Optimizer,
+                config: SolverConfig,
+            ) -> Solver {
+                let telemetry = &config.telemetry;
+                let score_log = (telemetry.score_log.is_some() || telemetry.chart.is_some()).then(|| {
+                    ScoreLog::new(
+                        Neighborhood::all()
+                            .iter()
+                            .map(|neighborhood| format!("{:?}", neighborhood))
+                            .collect(),
+                    )
+                });
+                Solver {
+                    state: state.clone(),
+                    neighborhood_selector,
+                    optimizer,
+                    score_log,
+                    square_origins: HashMap::new(),
+                    trajectory: None,
+                    config,
+                    loop_count: 0,
+                    final_score: 0,
+                }
+            }
+
+            // 入力の点と盤面、重みから、設定した温度で解き始める
+            pub fn from_input(
+                input: &Input,
+                grid: Grid,
+                weight_map: WeightMap,
+                config: SolverConfig,
+            ) -> Solver {
+                let (n, m) = (input.n, input.m());
+                let optimizer = Optimizer::new(config.calc_start_temp(n, m), config.calc_end_temp(n, m));
+                let mut state = State::from_grid(grid, input.points.clone(), Rc::new(weight_map));
+                state.sampler = Sampler::new(config.sampler.clone());
+                Solver::new(
+                    state,
+                    NeighborhoodSelector::new(&config.neighborhood_probs),
+                    optimizer,
+                    config,
+                )
+            }
+
+            fn progress(&self, loop_count: usize, time_limit: f32) -> f32 {
+                match self.config.iteration_limit {
+                    Some(limit) => loop_count as f32 / usize::max(1, limit) as f32,
+                    None => time::elapsed_seconds() as f32 / time_limit,
+                }
+            }
+
+            pub fn output(&self) -> String {
+                // 依存関係の順に並べ替えて、初期状態から作り直せることを確認する
+                let ordered_squares = self.state.ordered_squares();
+                let replayed_state = self.state.replay(&ordered_squares);
+                if replayed_state.squares.len() != ordered_squares.len() {
+                    eprintln!(
+                        "failed to replay squares: {} / {}",
+                        replayed_state.squares.len(),
+                        ordered_squares.len()
+                    );
+                }
+
+                let mut output = format!("{}\n", replayed_state.squares.len());
+                for Square {
+                    id: _,
+                    new_pos,
+                    diagonal,
+                    connect,
+                } in &replayed_state.squares
                 {
-                    return vec![];
+                    output += &format!(
+                        "{} {} {} {} {} {} {} {}\n",
+                        new_pos.x,
+                        new_pos.y,
+                        connect[0].x,
+                        connect[0].y,
+                        diagonal.x,
+                        diagonal.y,
+                        connect[1].x,
+                        connect[1].y
+                    );
                 }
+                output
+            }
 
-                let mut performed_commands: Vec<Command> = vec![];
-                self.perform_delete(square, &mut performed_commands);
-                performed_commands
+            pub fn report(&self, m: usize) -> SolveReport {
+                SolveReport {
+                    loop_count: self.loop_count,
+                    final_score: self.final_score,
+                    best_score: self.state.score.base,
+                    real_score: calc_real_score(&self.state.weight_map, m, self.state.score.base),
+                    elapsed_seconds: time::elapsed_seconds(),
+                    neighborhoods: self.neighborhood_selector.report(),
+                }
             }
         }
+
+        // 公式の盤面と重みで解いて、出力を返す。時間はまだ測り始めていなければここから測る
+        pub fn solve(input: &Input, config: SolverConfig) -> String {
+            time::start_clock();
+            let time_limit = config.time_limit;
+            let mut solver = Solver::from_input(
+                input,
+                Grid::new(input.n),
+                WeightMap::center_distance(input.n, input.n),
+                config,
+            );
+            solver.solve(time_limit);
+            solver.output()
+        }
+
     }
+    mod state {
+        use crate::ahc014::def::*;
+        use crate::ahc014::framework::*;
+        use crate::ahc014::grid::*;
+        use crate::ahc014::journal::*;
+        use crate::ahc014::sampler::*;
+        use crate::ahc014::weight::*;
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+        use std::fmt;
+        use std::rc::Rc;
+
+        // 四角を作れない理由
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum AddError {
+            OutOfBoard(Pos),
+            Blocked(Pos),
+            Occupied(Pos),
+            CornerMissing(Pos),
+            Connect(ConnectError),
+        }
+
+        impl fmt::Display for AddError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    AddError::OutOfBoard(pos) => write!(f, "({}, {}) is out of the board", pos.x, pos.y),
+                    AddError::Blocked(pos) => write!(f, "({}, {}) is a blocked cell", pos.x, pos.y),
+                    AddError::Occupied(pos) => {
+                        write!(f, "new point ({}, {}) is already occupied", pos.x, pos.y)
+                    }
+                    AddError::CornerMissing(pos) => {
+                        write!(f, "corner ({}, {}) has no point", pos.x, pos.y)
+                    }
+                    AddError::Connect(e) => e.fmt(f),
+                }
+            }
+        }
 
-    fn reverse_command(&mut self, command: &Command) {
-        match command {
-            Command::Add { square } => {
-                let mut performed_commands: Vec<Command> = vec![];
-                self.perform_delete(square, &mut performed_commands);
-                performed_commands
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct State {
+            pub grid: Grid,
+            pub squares: Vec<Square>,
+            pub score: Score,
+            pub weight_map: Rc<WeightMap>,
+            pub sampler: Sampler,
+        }
+
+        impl State {
+            pub fn new(n: usize, p: Vec<Pos>) -> State {
+                State::with_weight_map(n, p, Rc::new(WeightMap::center_distance(n, n)))
             }
-            Command::Delete { square } => self.perform_add(square, true),
-        };
-    }
-}
 
-struct Solver {
-    state: State,
-    neighborhood_selector: NeighborhoodSelector,
-    optimizer: Optimizer,
-    score_history: Vec<f32>,
-}
+            pub fn with_weight_map(n: usize, p: Vec<Pos>, weight_map: Rc<WeightMap>) -> State {
+                State::from_grid(Grid::new(n), p, weight_map)
+            }
 
-impl ISolver for Solver {
-    fn solve(&mut self, time_limit: f32) {
-        let mut loop_count = 0;
-        let mut best_state = self.state.clone();
-        let mut progress = time::elapsed_seconds() as f32 / time_limit;
-        while progress < 1. {
-            let is_interval = (loop_count % LOOP_INTERVAL) == 0;
-            if is_interval {
-                progress = time::elapsed_seconds() as f32 / time_limit;
-                self.optimizer.update_temp(progress);
+            pub fn from_grid(grid: Grid, p: Vec<Pos>, weight_map: Rc<WeightMap>) -> State {
+                debug_assert_eq!(weight_map.width, grid.width);
+                debug_assert_eq!(weight_map.height, grid.height);
+                let mut state = State {
+                    grid,
+                    squares: vec![],
+                    score: Score::new(),
+                    weight_map,
+                    sampler: Sampler::uniform(),
+                };
+                for pos in p.iter() {
+                    debug_assert!(state.grid.is_valid(pos));
+                    state.grid.add_point(pos, Point::new(pos), None);
+                    state.score.base += state.weight(pos);
+                }
+                state
             }
+        }
 
-            let neighborhood = self.neighborhood_selector.select();
+        impl State {
+            pub fn can_perform_add(&mut self, square: &Square, is_reverse: bool) -> bool {
+                self.check_add(square, is_reverse).is_ok()
+            }
 
-            let current_score = self.state.get_score(progress);
+            pub fn check_add(&self, square: &Square, is_reverse: bool) -> Result<(), AddError> {
+                debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[0]));
+                debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[1]));
+                debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[0]));
+                debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[1]));
 
-            let performed_commands = neighborhood.perform(&mut self.state);
+                for pos in square.all_pos() {
+                    if !self.grid.is_inside(pos) {
+                        return Err(AddError::OutOfBoard(*pos));
+                    }
+                }
 
-            let new_score = self.state.get_score(progress);
+                // new_posに既に点がないか確認
+                if self.grid.is_blocked(&square.new_pos) {
+                    return Err(AddError::Blocked(square.new_pos));
+                }
+                if self.grid.has_point(&square.new_pos) {
+                    return Err(AddError::Occupied(square.new_pos));
+                }
 
-            let adopt_new_state = self
-                .optimizer
-                .should_adopt_new_state(new_score - current_score)
-                && performed_commands.len() > 0;
+                if is_reverse {
+                    return Ok(());
+                }
 
-            if !adopt_new_state {
-                for command in performed_commands.iter().rev() {
-                    self.state.reverse_command(command);
+                // 残りの頂点に点があるか確認
+                for pos in &[square.connect[0], square.diagonal, square.connect[1]] {
+                    if !self.grid.has_point(pos) {
+                        return Err(AddError::CornerMissing(*pos));
+                    }
                 }
+
+                // 作ろうとしてる四角の辺に既に点、辺がないか確認する
+                self.grid
+                    .check_connect(&square.connect[0], &square.new_pos)
+                    .and_then(|_| self.grid.check_connect(&square.connect[1], &square.new_pos))
+                    .and_then(|_| {
+                        self.grid
+                            .check_connect(&square.connect[0], &square.diagonal)
+                    })
+                    .and_then(|_| {
+                        self.grid
+                            .check_connect(&square.connect[1], &square.diagonal)
+                    })
+                    .map_err(AddError::Connect)
             }
 
-            self.neighborhood_selector
-                .step(&neighborhood, adopt_new_state);
+            pub fn perform_add(&mut self, square: &Square, is_reverse: bool) -> Vec<Command> {
+                if !self.can_perform_add(square, is_reverse) {
+                    return vec![];
+                }
+
+                self.grid.create_square(square, is_reverse);
+
+                self.squares.push(*square);
+                self.grid.journal.record(Change::SquarePush);
+
+                // スコアの更新
+                self.add_score(self.weight(&square.new_pos));
 
-            if is_interval && self.state.get_score(1.) > best_state.get_score(1.) {
-                best_state = self.state.clone();
+                vec![Command::Add { square: *square }]
+            }
+
+            pub fn perform_delete(&mut self, square: &Square, performed_commands: &mut Vec<Command>) {
+                debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[0]));
+                debug_assert!(Pos::is_aligned(&square.diagonal, &square.connect[1]));
+                debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[0]));
+                debug_assert!(Pos::is_aligned(&square.new_pos, &square.connect[1]));
+
+                debug_assert!(self.grid.has_point(&square.new_pos));
+
+                // new_posの点を使って作られた四角を再帰的に消す
+                let created_points = self
+                    .grid
+                    .point(&square.new_pos)
+                    .as_ref()
+                    .unwrap()
+                    .created_points
+                    .clone();
+                for created_point in &created_points {
+                    // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
+                    // TODO: 正当性の確認
+                    if !self.grid.has_point(created_point) {
+                        continue;
+                    }
+                    let created_square = *self
+                        .grid
+                        .point(created_point)
+                        .as_ref()
+                        .unwrap()
+                        .added_info
+                        .as_ref()
+                        .unwrap();
+                    self.perform_delete(&created_square, performed_commands);
+                }
+
+                self.grid.delete_square(square);
+
+                // FIXME: O(n)
+                let index = self.squares.iter().position(|x| *x == *square).unwrap();
+                self.squares.remove(index);
+                self.grid.journal.record(Change::SquareRemove {
+                    index,
+                    square: *square,
+                });
+                self.add_score(-self.weight(&square.new_pos));
+                performed_commands.push(Command::Delete { square: *square });
+            }
+
+            pub fn sample_point_pos(&self) -> Pos {
+                self.sampler
+                    .sample(&self.grid, self.grid.point_set.positions(), |pos| *pos)
+            }
+
+            // 四角は作った点の位置で重みを付ける
+            pub fn sample_square(&self) -> Square {
+                self.sampler
+                    .sample(&self.grid, &self.squares, |square| square.new_pos)
+            }
+
+            pub fn calc_deletion_size(
+                &mut self,
+                new_pos: &Pos,
+                recursion_limit: usize,
+                parent_dep_size: usize,
+            ) -> usize {
+                let mut dep_size: usize = 1 + parent_dep_size;
+
+                let created_points = self
+                    .grid
+                    .point(new_pos)
+                    .as_ref()
+                    .unwrap()
+                    .created_points
+                    .clone();
+                for created_point in &created_points {
+                    if dep_size >= recursion_limit {
+                        return dep_size - parent_dep_size;
+                    }
+
+                    // 再帰的に処理する場合、既に削除されている時があるので、その時は何もしない
+                    // TODO: 正当性の確認
+                    if !self.grid.has_point(created_point) {
+                        continue;
+                    }
+                    dep_size += self.calc_deletion_size(created_point, recursion_limit, dep_size);
+                }
+
+                dep_size - parent_dep_size
             }
-            loop_count += 1;
         }
-        self.state = best_state.clone();
-    }
-}
 
-impl Solver {
-    fn new(
-        state: State,
-        neighborhood_selector: NeighborhoodSelector,
-        optimizer: Optimizer,
-    ) -> Solver {
-        Solver {
-            state: state.clone(),
-            neighborhood_selector,
-            optimizer,
-            score_history: vec![],
+        impl State {
+            fn add_score(&mut self, delta: i32) {
+                self.grid.journal.record(Change::Score {
+                    old: self.score.base,
+                });
+                self.score.base += delta;
+            }
+
+            // 以降の変更を記録し、rollbackで元に戻せるようにする
+            // 入れ子にでき、内側のcommitは外側のrollbackで戻る
+            pub fn begin(&mut self) {
+                self.grid.journal.begin();
+            }
+
+            pub fn commit(&mut self) {
+                self.grid.journal.commit();
+            }
+
+            // 合法性を確認せずに、記録した変更を逆順に戻す
+            pub fn rollback(&mut self) {
+                for change in self.grid.journal.pop_transaction() {
+                    match change {
+                        Change::SquarePush => {
+                            self.squares.pop();
+                        }
+                        Change::SquareRemove { index, square } => {
+                            self.squares.insert(index, square);
+                        }
+                        Change::Score { old } => {
+                            self.score.base = old;
+                        }
+                        _ => self.grid.undo(change),
+                    }
+                }
+            }
+
+            pub fn initial_points(&self) -> Vec<Pos> {
+                let mut initial_points = vec![];
+                for row in &self.grid.points {
+                    for point in row.iter().flatten() {
+                        if point.added_info.is_none() {
+                            initial_points.push(point.pos);
+                        }
+                    }
+                }
+                initial_points
+            }
+
+            // idは作成順を保証しない（reverse_commandで古いidが戻ってくる）ので、
+            // 依存関係からトポロジカルソートして出力順を決める
+            pub fn ordered_squares(&self) -> Vec<Square> {
+                let square_count = self.squares.len();
+                let mut index_of_new_pos = vec![vec![None; self.grid.width]; self.grid.height];
+                for (i, square) in self.squares.iter().enumerate() {
+                    index_of_new_pos[square.new_pos.y as usize][square.new_pos.x as usize] = Some(i);
+                }
+
+                // edges[i]に含まれる四角は、i番目の四角より後に作る必要がある
+                let mut edges: Vec<Vec<usize>> = vec![vec![]; square_count];
+                let mut in_degree: Vec<usize> = vec![0; square_count];
+                for (i, square) in self.squares.iter().enumerate() {
+                    // 四角の頂点を作った四角が先
+                    for pos in &[square.connect[0], square.diagonal, square.connect[1]] {
+                        if let Some(j) = index_of_new_pos[pos.y as usize][pos.x as usize] {
+                            edges[j].push(i);
+                            in_degree[i] += 1;
+                        }
+                    }
+
+                    // 辺上に点が作られた場合は、辺を作った四角が先
+                    let corners = square.all_pos();
+                    for k in 0..4 {
+                        for pos in Pos::between(corners[k], corners[(k + 1) % 4]) {
+                            if let Some(j) = index_of_new_pos[pos.y as usize][pos.x as usize] {
+                                edges[i].push(j);
+                                in_degree[j] += 1;
+                            }
+                        }
+                    }
+                }
+
+                // 同じ順位の中ではidが小さいものを先に出す
+                let mut queue = BinaryHeap::new();
+                for (i, square) in self.squares.iter().enumerate() {
+                    if in_degree[i] == 0 {
+                        queue.push(Reverse((square.id, i)));
+                    }
+                }
+                let mut ordered_squares = Vec::with_capacity(square_count);
+                while let Some(Reverse((_, i))) = queue.pop() {
+                    ordered_squares.push(self.squares[i]);
+                    for &j in &edges[i] {
+                        in_degree[j] -= 1;
+                        if in_degree[j] == 0 {
+                            queue.push(Reverse((self.squares[j].id, j)));
+                        }
+                    }
+                }
+                debug_assert_eq!(ordered_squares.len(), square_count);
+                ordered_squares
+            }
+
+            // 初期状態からsquaresの順に四角を作り直す
+            // 作れなかった四角（とそれに依存する四角）は飛ばされる
+            pub fn replay(&self, squares: &[Square]) -> State {
+                let mut state = State::from_grid(
+                    self.grid.cleared(),
+                    self.initial_points(),
+                    self.weight_map.clone(),
+                );
+                state.sampler = self.sampler.clone();
+                for square in squares {
+                    state.perform_add(square, false);
+                }
+                state
+            }
         }
-    }
 
-    fn output(&mut self) {
-        println!("{}", self.state.squares.len());
-        self.state.squares.sort_by(|a, b| a.id.cmp(&b.id));
-        for Square {
-            id: _,
-            new_pos,
-            diagonal,
-            connect,
-        } in &self.state.squares
-        {
-            println!(
-                "{} {} {} {} {} {} {} {}",
-                new_pos.x,
-                new_pos.y,
-                connect[0].x,
-                connect[0].y,
-                diagonal.x,
-                diagonal.y,
-                connect[1].x,
-                connect[1].y
-            );
+        impl State {
+            #[inline]
+            pub fn weight(&self, pos: &Pos) -> i32 {
+                self.weight_map.get(pos)
+            }
         }
+
+        impl IState for State {
+            #[allow(unused_variables)]
+            fn get_score(&self, progress: f32) -> f32 {
+                self.score.base as f32
+            }
+
+            fn perform_command(&mut self, command: &Command) -> Vec<Command> {
+                match command {
+                    Command::Add { square } => self.perform_add(square, false),
+                    Command::Delete { square } => {
+                        let mut performed_commands: Vec<Command> = vec![];
+                        self.perform_delete(square, &mut performed_commands);
+                        performed_commands
+                    }
+                }
+            }
+
+            fn reverse_command(&mut self, command: &Command) {
+                match command {
+                    Command::Add { square } => {
+                        let mut performed_commands: Vec<Command> = vec![];
+                        self.perform_delete(square, &mut performed_commands);
+                        performed_commands
+                    }
+                    Command::Delete { square } => self.perform_add(square, true),
+                };
+            }
+        }
+
+
+
+
+
+
+
+
+
+
+
     }
+    mod telemetry {
+        use crate::ahc014::def::*;
+        use crate::ahc014::state::*;
+        use std::fmt::Write;
+
+        // 焼きなまし途中のある時点の状態
+        #[derive(Clone, Debug)]
+        pub struct Snapshot {
+            pub iteration: usize,
+            pub temp: f32,
+            // 内部スコア（重みの和）
+            pub score: i32,
+            // 初期状態から作り直せる順に並べた四角
+            pub squares: Vec<Square>,
+        }
+
+        // interval回ごとにSnapshotを記録する
+        #[derive(Clone, Debug)]
+        pub struct Trajectory {
+            pub interval: usize,
+            pub snapshots: Vec<Snapshot>,
+        }
+
+        impl Trajectory {
+            pub fn new(interval: usize) -> Trajectory {
+                Trajectory {
+                    interval: usize::max(1, interval),
+                    snapshots: vec![],
+                }
+            }
 
-    #[allow(dead_code)]
-    fn output_statistics(&self, n: usize, m: usize) {
-        eprintln!("state_score: {}", self.state.get_score(1.));
-        eprintln!(
-            "real_score: {}",
-            calc_real_score(n, m, self.state.score.base as i32)
-        );
-        self.neighborhood_selector.output_statistics();
+            pub fn should_record(&self, iteration: usize) -> bool {
+                iteration % self.interval == 0
+            }
 
-        if WRITE_SCORE_LOG {
-            // スコア遷移の書き出し
-            let mut file = fs::File::create("tools/out/score_log.txt").unwrap();
-            for score in &self.score_history {
-                let score = calc_real_score(n, m, *score as i32);
-                file.write((score.to_string() + "\n").as_bytes()).unwrap();
+            pub fn record(&mut self, iteration: usize, temp: f32, state: &State) {
+                self.snapshots.push(Snapshot {
+                    iteration,
+                    temp,
+                    score: state.score.base,
+                    squares: state.ordered_squares(),
+                });
             }
         }
+
+
+        // LOOP_INTERVAL回ごとのスコア、温度、近傍ごとの累計試行・採用回数
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct LogEntry {
+            pub iteration: usize,
+            pub temp: f32,
+            pub score: i32,
+            pub best_score: i32,
+            pub total: Vec<i32>,
+            pub adopted: Vec<i32>,
+        }
+
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct ScoreLog {
+            pub neighborhoods: Vec<String>,
+            pub entries: Vec<LogEntry>,
+        }
+
+        impl ScoreLog {
+            pub fn new(neighborhoods: Vec<String>) -> ScoreLog {
+                ScoreLog {
+                    neighborhoods,
+                    entries: vec![],
+                }
+            }
+
+            pub fn record(
+                &mut self,
+                iteration: usize,
+                temp: f32,
+                score: i32,
+                best_score: i32,
+                total: &[i32],
+                adopted: &[i32],
+            ) {
+                debug_assert_eq!(total.len(), self.neighborhoods.len());
+                self.entries.push(LogEntry {
+                    iteration,
+                    temp,
+                    score,
+                    best_score,
+                    total: total.to_vec(),
+                    adopted: adopted.to_vec(),
+                });
+            }
+
+            // タブ区切りで、1行目はヘッダ
+            pub fn to_tsv(&self) -> String {
+                let mut tsv = String::from("iteration\ttemp\tscore\tbest_score");
+                for name in &self.neighborhoods {
+                    write!(tsv, "\t{}.total\t{}.adopted", name, name).unwrap();
+                }
+                tsv.push('\n');
+                for entry in &self.entries {
+                    write!(
+                        tsv,
+                        "{}\t{}\t{}\t{}",
+                        entry.iteration, entry.temp, entry.score, entry.best_score
+                    )
+                    .unwrap();
+                    for (total, adopted) in entry.total.iter().zip(&entry.adopted) {
+                        write!(tsv, "\t{}\t{}", total, adopted).unwrap();
+                    }
+                    tsv.push('\n');
+                }
+                tsv
+            }
+
+            pub fn parse_tsv(s: &str) -> Result<ScoreLog, String> {
+                let mut lines = s.lines().enumerate();
+                let header: Vec<&str> = match lines.next() {
+                    Some((_, line)) => line.split('\t').collect(),
+                    None => return Err("empty score log".to_string()),
+                };
+                if header.len() < 4 || (header.len() - 4) % 2 != 0 {
+                    return Err(format!("invalid header {:?}", header.join("\t")));
+                }
+                let neighborhoods = header[4..]
+                    .chunks(2)
+                    .map(|names| names[0].trim_end_matches(".total").to_string())
+                    .collect();
+                let mut log = ScoreLog::new(neighborhoods);
+
+                for (i, line) in lines {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let values: Vec<&str> = line.split('\t').collect();
+                    if values.len() != header.len() {
+                        return Err(format!(
+                            "line {}: expected {} columns, found {}",
+                            i + 1,
+                            header.len(),
+                            values.len()
+                        ));
+                    }
+                    let invalid = |v: &str| format!("line {}: invalid value {:?}", i + 1, v);
+                    let int = |v: &str| v.parse::<i32>().map_err(|_| invalid(v));
+                    let counts = values[4..]
+                        .iter()
+                        .map(|v| int(v))
+                        .collect::<Result<Vec<i32>, _>>()?;
+                    log.entries.push(LogEntry {
+                        iteration: values[0].parse().map_err(|_| invalid(values[0]))?,
+                        temp: values[1].parse().map_err(|_| invalid(values[1]))?,
+                        score: int(values[2])?,
+                        best_score: int(values[3])?,
+                        total: counts.iter().step_by(2).copied().collect(),
+                        adopted: counts.iter().skip(1).step_by(2).copied().collect(),
+                    });
+                }
+                Ok(log)
+            }
+        }
+
     }
-}
+    mod util {
+        use crate::ahc014::weight::WeightMap;
 
-fn main() {
-    time::start_clock();
+        pub mod rnd {
+            use std::cell::Cell;
+
+            // 並列に解くときに干渉しないように、スレッドごとに持つ
+            thread_local! {
+                static S: Cell<usize> = const { Cell::new(88172645463325252) };
+            }
+
+            #[allow(unused)]
+            #[inline]
+            pub fn next() -> usize {
+                S.with(|s| {
+                    let mut x = s.get();
+                    x = x ^ x << 7;
+                    x = x ^ x >> 9;
+                    s.set(x);
+                    x
+                })
+            }
+
+            // 再現できるように、このスレッドの乱数を初期化する
+            #[allow(unused)]
+            pub fn seed(seed: u64) {
+                S.with(|s| s.set(Xorshift::new(seed).s));
+            }
+
+            #[allow(unused)]
+            #[inline]
+            pub fn nextf() -> f32 {
+                (next() & 4294967295) as f32 / 4294967296.
+            }
 
-    input! {
-        n: usize,
-        m: usize,
-        p: [Pos; m]
+            #[allow(unused)]
+            #[inline]
+            pub fn gen_range(low: usize, high: usize) -> usize {
+                (next() % (high - low)) + low
+            }
+
+            // グローバルな乱数とは独立した、シード付きの乱数
+            #[derive(Clone, Debug)]
+            pub struct Xorshift {
+                s: usize,
+            }
+
+            impl Xorshift {
+                pub fn new(seed: u64) -> Xorshift {
+                    // 近いシードでも列がばらけるようにsplitmix64で混ぜる
+                    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                    z ^= z >> 31;
+                    Xorshift {
+                        s: if z == 0 {
+                            88172645463325252
+                        } else {
+                            z as usize
+                        },
+                    }
+                }
+
+                #[inline]
+                #[allow(clippy::should_implement_trait)]
+                pub fn next(&mut self) -> usize {
+                    self.s = self.s ^ self.s << 7;
+                    self.s = self.s ^ self.s >> 9;
+                    self.s
+                }
+
+                #[inline]
+                pub fn nextf(&mut self) -> f64 {
+                    (self.next() & 4294967295) as f64 / 4294967296.
+                }
+
+                // [low, high]
+                #[inline]
+                pub fn gen_range_inclusive(&mut self, low: usize, high: usize) -> usize {
+                    (self.next() % (high - low + 1)) + low
+                }
+            }
+        }
+
+        pub mod time {
+            use std::cell::Cell;
+
+            thread_local! {
+                static START: Cell<f64> = const { Cell::new(-1.) };
+            }
+
+            #[allow(unused)]
+            pub fn start_clock() {
+                let _ = elapsed_seconds();
+            }
+
+            // 複数の入力を続けて解くときに、時間を測り直す
+            #[allow(unused)]
+            pub fn restart_clock() {
+                START.with(|start| start.set(-1.));
+                start_clock();
+            }
+
+            #[allow(unused)]
+            #[inline]
+            pub fn elapsed_seconds() -> f64 {
+                let t = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64();
+                START.with(|start| {
+                    if start.get() < 0. {
+                        start.set(t);
+                    }
+                    t - start.get()
+                })
+            }
+        }
+
+        pub fn calc_real_score(weight_map: &WeightMap, m: usize, score: i32) -> i32 {
+            let cell_count = (weight_map.width * weight_map.height) as f64;
+            let s = weight_map.total();
+            let result = 1e6 * cell_count * score as f64 / (m as f64 * s as f64);
+            result.round() as i32
+        }
     }
+    mod weight {
+        use crate::ahc014::def::*;
+        use std::fmt;
+        use std::fs;
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum WeightError {
+            Malformed(String),
+            // 重みの和で正規化するので、和が正でないとスコアが求まらない
+            NonPositiveTotal(i64),
+        }
+
+        impl fmt::Display for WeightError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    WeightError::Malformed(message) => write!(f, "{}", message),
+                    WeightError::NonPositiveTotal(total) => {
+                        write!(f, "total weight must be positive, found {}", total)
+                    }
+                }
+            }
+        }
 
-    let start_temp: f32 = calc_start_temp(n, m);
-    let end_temp: f32 = calc_end_temp(n, m);
+        // 各マスの点の重み
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct WeightMap {
+            pub width: usize,
+            pub height: usize,
+            weights: Vec<Vec<i32>>,
+            total: i64,
+        }
+
+        impl WeightMap {
+            pub fn from_fn<F: Fn(&Pos) -> i32>(width: usize, height: usize, f: F) -> WeightMap {
+                let weights: Vec<Vec<i32>> = (0..height)
+                    .map(|y| {
+                        (0..width)
+                            .map(|x| {
+                                f(&Pos {
+                                    x: x as i32,
+                                    y: y as i32,
+                                })
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let total = weights.iter().flatten().map(|&w| w as i64).sum();
+                WeightMap {
+                    width,
+                    height,
+                    weights,
+                    total,
+                }
+            }
+
+            // AHC014の重み (x-c)^2+(y-c)^2+1
+            pub fn center_distance(width: usize, height: usize) -> WeightMap {
+                let center = Pos {
+                    x: ((width - 1) / 2) as i32,
+                    y: ((height - 1) / 2) as i32,
+                };
+                WeightMap::off_center(width, height, center)
+            }
+
+            // 中心をcenterにずらした重み
+            pub fn off_center(width: usize, height: usize, center: Pos) -> WeightMap {
+                WeightMap::from_fn(width, height, |pos| Pos::weight(pos, &center) + 1)
+            }
+
+            // 点の数を最大化する
+            pub fn uniform(width: usize, height: usize) -> WeightMap {
+                WeightMap::from_fn(width, height, |_| 1)
+            }
 
-    let state = State::new(n, p);
-    let mut solver = Solver::new(
-        state,
-        NeighborhoodSelector::new(Neighborhood::all().len()),
-        Optimizer::new(start_temp, end_temp),
-    );
+            // height行、各行width個の整数で、i行目がy=iの重みを表す
+            pub fn parse_matrix(width: usize, height: usize, s: &str) -> Result<WeightMap, WeightError> {
+                let mut rows = vec![];
+                for line in s.lines() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let row = line
+                        .split_whitespace()
+                        .map(|v| v.parse::<i32>())
+                        .collect::<Result<Vec<i32>, _>>()
+                        .map_err(|e| WeightError::Malformed(format!("line {}: {}", rows.len() + 1, e)))?;
+                    if row.len() != width {
+                        return Err(WeightError::Malformed(format!(
+                            "line {}: expected {} weights, found {}",
+                            rows.len() + 1,
+                            width,
+                            row.len()
+                        )));
+                    }
+                    rows.push(row);
+                }
+                if rows.len() != height {
+                    return Err(WeightError::Malformed(format!(
+                        "expected {} rows, found {}",
+                        height,
+                        rows.len()
+                    )));
+                }
+                let weight_map =
+                    WeightMap::from_fn(width, height, |pos| rows[pos.y as usize][pos.x as usize]);
+                if weight_map.total <= 0 {
+                    return Err(WeightError::NonPositiveTotal(weight_map.total));
+                }
+                Ok(weight_map)
+            }
+
+            // center | uniform | center:x,y | file:path
+            pub fn from_spec(width: usize, height: usize, spec: &str) -> Result<WeightMap, WeightError> {
+                if spec == "center" {
+                    return Ok(WeightMap::center_distance(width, height));
+                }
+                if spec == "uniform" {
+                    return Ok(WeightMap::uniform(width, height));
+                }
+                if let Some(center) = spec.strip_prefix("center:") {
+                    let v = center
+                        .split(',')
+                        .map(|v| v.trim().parse::<i32>())
+                        .collect::<Result<Vec<i32>, _>>()
+                        .map_err(|e| {
+                            WeightError::Malformed(format!("invalid center {:?}: {}", center, e))
+                        })?;
+                    if v.len() != 2 {
+                        return Err(WeightError::Malformed(format!(
+                            "invalid center {:?}",
+                            center
+                        )));
+                    }
+                    return Ok(WeightMap::off_center(
+                        width,
+                        height,
+                        Pos { x: v[0], y: v[1] },
+                    ));
+                }
+                if let Some(path) = spec.strip_prefix("file:") {
+                    let s = fs::read_to_string(path)
+                        .map_err(|e| WeightError::Malformed(format!("{}: {}", path, e)))?;
+                    return WeightMap::parse_matrix(width, height, &s).map_err(|e| match e {
+                        WeightError::Malformed(message) => {
+                            WeightError::Malformed(format!("{}: {}", path, message))
+                        }
+                        e => e,
+                    });
+                }
+                Err(WeightError::Malformed(format!(
+                    "unknown weight spec {:?}",
+                    spec
+                )))
+            }
+
+            #[inline]
+            pub fn get(&self, pos: &Pos) -> i32 {
+                self.weights[pos.y as usize][pos.x as usize]
+            }
+
+            // 全マスの重みの和
+            pub fn total(&self) -> i64 {
+                self.total
+            }
+        }
+
+
+    }
 
-    solver.solve(TIME_LIMIT);
-    solver.output();
+    pub use input::Input;
+    pub use solver::solve;
+    pub use state::State;
 }
@@ -1,12 +1,14 @@
 import matplotlib.pyplot as plt
 
+# ahc014 solve <input> --score-log tools/out/score_log.txt で書き出したTSV
 score_log_file = "tools/out/score_log.txt"
 
 scores = []
 
 with open(score_log_file, "r") as f:
+    column = f.readline().rstrip("\n").split("\t").index("score")
     for line in f:
-        scores.append(float(line))
+        scores.append(float(line.split("\t")[column]))
 
 plt.plot(scores)
 plt.show()